@@ -6,7 +6,7 @@ use egui::pos2;
 use std::time::Duration;
 use worms::{
     gui::SimInterface,
-    scene::{Scene, SceneParameters},
+    scene::{Scene, SceneParameters, UpdateMode},
 };
 
 fn get_bench_group<'a>(
@@ -33,12 +33,32 @@ fn get_scene_2000() -> Scene {
             starvation: 5000,
             expiration: 1000,
             body_size: 2.0,
+            seed: Some(42),
+            ..Default::default()
         },
         2000,
         200,
     )
 }
 
+fn get_scene_20000(update: UpdateMode) -> Scene {
+    Scene::new(
+        3000,
+        3000,
+        SceneParameters {
+            worm_size: 8,
+            starvation: 5000,
+            expiration: 1000,
+            body_size: 2.0,
+            seed: Some(42),
+            update,
+            ..Default::default()
+        },
+        20000,
+        2000,
+    )
+}
+
 pub fn execute_2000(c: &mut Criterion) {
     let mut group = get_bench_group(c, "execute_2000", 500);
     for i in 0..10 {
@@ -50,6 +70,20 @@ pub fn execute_2000(c: &mut Criterion) {
     group.finish();
 }
 
+pub fn execute_20000(c: &mut Criterion) {
+    let mut group = get_bench_group(c, "execute_20000", 10);
+    let mut scene = get_scene_20000(UpdateMode::Sequential);
+    group.bench_function("execute", |b| b.iter(|| scene.execute()));
+    group.finish();
+}
+
+pub fn execute_20000_parallel(c: &mut Criterion) {
+    let mut group = get_bench_group(c, "execute_20000_parallel", 10);
+    let mut scene = get_scene_20000(UpdateMode::Parallel);
+    group.bench_function("execute", |b| b.iter(|| scene.execute()));
+    group.finish();
+}
+
 pub fn print_2000(c: &mut Criterion) {
     let mut group = get_bench_group(c, "print_2000", 500);
     for i in 0..10 {
@@ -62,5 +96,11 @@ pub fn print_2000(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, execute_2000, print_2000);
+criterion_group!(
+    benches,
+    execute_2000,
+    execute_20000,
+    execute_20000_parallel,
+    print_2000
+);
 criterion_main!(benches);
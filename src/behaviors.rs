@@ -0,0 +1,99 @@
+use crate::{
+    bodies::{BodyStore, WormBody},
+    composites::{Genome, Reward, WormBehavior},
+    metabolism::Metabolism,
+    movement::{MovementDetails, MovementResult, Mover, PartSizes},
+    planning::PathPlanner,
+    predators::Predator,
+    scene::SceneParameters,
+    scent::ScentField,
+    spatial::SpatialGrid,
+    species::Species,
+};
+
+/// Id of a behavior registered in a scene (its position in the registry)
+pub type BehaviorId = usize;
+
+/// A worm, as seen by the registered behaviors
+#[derive(Clone, Copy)]
+pub struct WormView<'a> {
+    /// Index of the worm in the tables of the scene
+    pub id: usize,
+    pub body: WormBody<'a>,
+    pub genome: &'a Genome,
+    pub species: &'a Species,
+    /// Energy reserve (only used by the energy metabolism)
+    pub energy: f32,
+    /// How the hunger of the worms is modeled in the scene
+    pub metabolism: Metabolism,
+}
+
+impl WormView<'_> {
+    /// Checks if the worm starves, by the rule of the scene (counter: ticks since it last ate)
+    pub fn starving(&self, counter: usize) -> bool {
+        self.metabolism
+            .starving(self.genome, self.body.size(), self.energy, counter)
+    }
+}
+
+/// What the mover of a registered behavior can use: the worm and its movement details,
+/// and the content of the scene (along with the spatial indexes used to find what is nearby)
+#[derive(Clone, Copy)]
+pub struct MoverContext<'a> {
+    pub worm: WormView<'a>,
+    pub details: &'a MovementDetails,
+    pub params: &'a SceneParameters,
+    pub rewards: &'a Vec<Reward>,
+    pub bodies: &'a BodyStore,
+    /// Radii of the parts of the worms (see Mover::collides)
+    pub part_sizes: PartSizes<'a>,
+    pub behaviors: &'a Vec<WormBehavior>,
+    pub predators: &'a [Predator],
+    /// Scent field (None if the worms leave no scent)
+    pub scent: Option<&'a ScentField>,
+    /// Path planner of the scene (see the planning parameters for when the movers use it)
+    pub planner: &'a PathPlanner,
+    pub reward_index: &'a SpatialGrid,
+    pub body_index: &'a SpatialGrid,
+}
+
+/// Change of behavior of a worm after its movement
+pub enum Transition {
+    /// The worm keeps its behavior (one more tick in it)
+    Stay,
+    /// The worm takes another behavior (a built-in or a registered one)
+    /// Dead(_) kills it and Removed takes it out of the scene, as for the built-in ones
+    Switch(WormBehavior),
+}
+
+/// Behavior added to the built-in ones ('Alive', 'Chasing' and 'Dead') without editing
+/// the scene, registered with Scene::register_behavior
+/// The worms with the behavior move with its mover, their body following the new head
+/// (whether the target is hit or not), then change behavior according to its transition
+pub trait Behavior: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Checks if a worm takes up the behavior, instead of its movement: an 'Alive' worm
+    /// (with the ticks since it last ate) or a 'Chasing' one
+    fn enters(&self, _worm: &WormView, _behavior: WormBehavior) -> bool {
+        false
+    }
+
+    /// Checks if the targets hit by the mover are rewards, eaten as by the 'Alive' worms
+    /// (otherwise the worm just moves onto them)
+    fn eats(&self) -> bool {
+        false
+    }
+
+    /// Mover of a worm with the behavior, for the current tick
+    fn mover<'a>(&'a self, context: MoverContext<'a>) -> Box<dyn Mover + 'a>;
+
+    /// Behavior of the worm after its movement (counter: ticks spent with the behavior so far)
+    fn transition(&self, worm: &WormView, counter: usize, result: &MovementResult) -> Transition;
+
+    /// First error the behavior ran into, if any (reported by the scene with a
+    /// BehaviorFailed event)
+    fn error(&self) -> Option<String> {
+        None
+    }
+}
@@ -1,16 +1,42 @@
-use worms::gui::SimInterface;
+use clap::Parser;
+use egui::vec2;
+use worms::{
+    cli::ConfigArgs,
+    gui::{SimInterface, StartMode},
+};
+
+/// Worms simulation
+#[derive(Parser)]
+struct Args {
+    #[command(flatten)]
+    config: ConfigArgs,
+    /// Initial width of the window
+    #[arg(long, requires = "height")]
+    width: Option<f32>,
+    /// Initial height of the window
+    #[arg(long, requires = "width")]
+    height: Option<f32>,
+    /// Open the window in fullscreen
+    #[arg(long)]
+    fullscreen: bool,
+    /// State of the simulation when the window opens
+    #[arg(long, value_enum, default_value_t = StartMode::Empty)]
+    start: StartMode,
+}
 
 fn main() {
+    let args = Args::parse();
     let options = eframe::NativeOptions {
         // maximized: true,
-        // fullscreen: true,
         // resizable: false,
+        fullscreen: args.fullscreen,
+        initial_window_size: args.width.zip(args.height).map(|(w, h)| vec2(w, h)),
         ..Default::default()
     };
 
     eframe::run_native(
         "Worms",
         options,
-        Box::new(|cc| Box::new(SimInterface::new(cc))),
+        Box::new(move |cc| Box::new(SimInterface::new(cc, args.config, args.start))),
     );
 }
@@ -0,0 +1,250 @@
+use clap::{Parser, ValueEnum};
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    process::ExitCode,
+};
+use worms::{
+    cli::ConfigArgs, events::SimEvent, metrics::SceneMetrics, scenario::Scenario, scene::Scene,
+};
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    Csv,
+    Jsonl,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LineageFormat {
+    Newick,
+    Dot,
+}
+
+/// Runs a simulation without GUI, writing the population metrics of every tick
+#[derive(Parser)]
+struct Args {
+    #[command(flatten)]
+    config: ConfigArgs,
+    /// Scenario file with the initial state of the scene (replaces the configuration file,
+    /// only the seed can be overridden)
+    #[arg(long)]
+    scenario: Option<String>,
+    /// Number of ticks to execute
+    #[arg(long, default_value_t = 1000)]
+    ticks: u64,
+    /// Width of the scene (overrides the one in the configuration)
+    #[arg(long)]
+    width: Option<usize>,
+    /// Height of the scene (overrides the one in the configuration)
+    #[arg(long)]
+    height: Option<usize>,
+    /// Format of the metrics
+    #[arg(long, value_enum, default_value_t = OutputFormat::Csv)]
+    format: OutputFormat,
+    /// Writes the metrics of each species on a line of its own (after the name of the species)
+    #[arg(long)]
+    by_species: bool,
+    /// File to write the metrics to (standard output if not set)
+    #[arg(long)]
+    output: Option<PathBuf>,
+    /// File to write the lineage of the worms to, at the end of the run
+    #[arg(long)]
+    lineage: Option<PathBuf>,
+    /// Format of the lineage: a Newick tree or a GraphViz (DOT) graph
+    #[arg(long, value_enum, default_value_t = LineageFormat::Newick)]
+    lineage_format: LineageFormat,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &Args) -> Result<(), String> {
+    let mut scene = match &args.scenario {
+        Some(path) => {
+            let mut scenario = Scenario::from_json(path)
+                .map_err(|error| format!("Error loading scenario file {path}:\n{error}"))?;
+            scenario.config.seed = args.config.seed.or(scenario.config.seed);
+            let mut scene = Scene::from_scenario(&scenario);
+            scenario
+                .config
+                .register_script(&mut scene)
+                .map_err(|error| format!("Error loading the script:\n{error}"))?;
+            scene
+        }
+        None => new_scene(args)?,
+    };
+
+    let mut output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path).map_err(|error| {
+            format!("Error creating output file {}:\n{error}", path.display())
+        })?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+
+    write_metrics(&mut output, args, &mut scene)
+        .map_err(|error| format!("Error writing the metrics:\n{error}"))?;
+
+    if let Some(path) = &args.lineage {
+        let lineage = match args.lineage_format {
+            LineageFormat::Newick => scene.lineage().to_newick(),
+            LineageFormat::Dot => scene.lineage().to_dot(),
+        };
+        std::fs::write(path, lineage).map_err(|error| {
+            format!("Error writing the lineage to {}:\n{error}", path.display())
+        })?;
+    }
+    Ok(())
+}
+
+// Scene populated randomly, according to the configuration
+fn new_scene(args: &Args) -> Result<Scene, String> {
+    let mut config = args.config.load().map_err(|error| {
+        format!(
+            "Error loading configuration file {}:\n{error}",
+            args.config.config
+        )
+    })?;
+    config.width = args.width.unwrap_or(config.width);
+    config.height = args.height.unwrap_or(config.height);
+    config
+        .validate()
+        .map_err(|error| format!("Invalid arguments:\n{error}"))?;
+    let mut scene = Scene::new(
+        config.width,
+        config.height,
+        config.scene_params(),
+        config.n_worms,
+        config.n_rewards,
+    );
+    config
+        .register_script(&mut scene)
+        .map_err(|error| format!("Error loading the script:\n{error}"))?;
+    Ok(scene)
+}
+
+/// Metrics of a species, named
+#[derive(Serialize)]
+struct SpeciesMetrics<'a> {
+    species: &'a str,
+    #[serde(flatten)]
+    metrics: SceneMetrics,
+}
+
+fn write_metrics(output: &mut dyn Write, args: &Args, scene: &mut Scene) -> io::Result<()> {
+    if let OutputFormat::Csv = args.format {
+        let species = if args.by_species { "species," } else { "" };
+        writeln!(output, "{species}{}", SceneMetrics::CSV_HEADER)?;
+    }
+    for _ in 0..args.ticks {
+        scene.execute();
+        report_failures(scene);
+        if args.by_species {
+            for (index, species) in scene.species().iter().enumerate() {
+                let metrics = SceneMetrics::of_species(scene, index);
+                match args.format {
+                    OutputFormat::Csv => writeln!(output, "{},{}", species.name, metrics.to_csv())?,
+                    OutputFormat::Jsonl => write_json(
+                        output,
+                        &SpeciesMetrics {
+                            species: &species.name,
+                            metrics,
+                        },
+                    )?,
+                }
+            }
+        } else {
+            let metrics = SceneMetrics::from(scene);
+            match args.format {
+                OutputFormat::Csv => writeln!(output, "{}", metrics.to_csv())?,
+                OutputFormat::Jsonl => write_json(output, &metrics)?,
+            }
+        }
+    }
+    output.flush()
+}
+
+fn write_json(output: &mut dyn Write, metrics: &impl Serialize) -> io::Result<()> {
+    serde_json::to_writer(&mut *output, metrics)?;
+    writeln!(output)
+}
+
+// Reports the first error of the registered behaviors (the run goes on with the built-in rules)
+fn report_failures(scene: &Scene) {
+    for event in scene.events() {
+        if let SimEvent::BehaviorFailed { behavior, .. } = *event {
+            if let Some(behavior) = scene.behavior(behavior) {
+                let error = behavior.error().unwrap_or_default();
+                eprintln!("Error in the behavior {}:\n{error}", behavior.name());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use worms::{metrics::SceneMetrics, scenario::Scenario, scene::Scene};
+
+    use super::{write_metrics, Args};
+
+    fn written(arguments: &[&str]) -> Vec<String> {
+        let scenario = Scenario::parse(
+            r#"{
+                "config": {"width": 300, "height": 300, "part_size": 3.0, "worm_size": 2,
+                           "seed": 1, "species": [
+                           {"name": "red", "count": 0}, {"name": "blue", "count": 0}]},
+                "worms": [
+                    {"head": {"x": 50, "y": 150}},
+                    {"head": {"x": 150, "y": 50}, "species": "blue"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let args = Args::parse_from([&["worms-headless", "--ticks", "2"], arguments].concat());
+        let mut output = Vec::new();
+        write_metrics(&mut output, &args, &mut Scene::from_scenario(&scenario)).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(str::to_owned)
+            .collect()
+    }
+
+    #[test]
+    fn csv_output() {
+        let lines = written(&[]);
+        assert_eq!(lines.len(), 1 + 2);
+        assert_eq!(lines[0], SceneMetrics::CSV_HEADER);
+        assert!(lines[1].starts_with("1,2,0,0,0,2.000,"));
+        assert!(lines[2].starts_with("2,"));
+        assert!(lines.iter().all(|line| line.split(',').count() == 10));
+
+        // a line per species and tick, the species first
+        let lines = written(&["--by-species"]);
+        assert_eq!(lines.len(), 1 + 2 * 2);
+        assert_eq!(lines[0], format!("species,{}", SceneMetrics::CSV_HEADER));
+        assert!(lines[1].starts_with("red,1,1,0,0,0,2.000,"));
+        assert!(lines[2].starts_with("blue,1,1,"));
+        assert!(lines.iter().all(|line| line.split(',').count() == 11));
+    }
+
+    #[test]
+    fn jsonl_output() {
+        let lines = written(&["--format", "jsonl", "--by-species"]);
+        assert_eq!(lines.len(), 2 * 2);
+        let line: serde_json::Value = serde_json::from_str(&lines[3]).unwrap();
+        assert_eq!(line["species"], "blue");
+        assert_eq!(line["tick"], 2);
+        assert_eq!(line["alive"], 1);
+    }
+}
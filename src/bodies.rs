@@ -0,0 +1,593 @@
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+
+use crate::{
+    composites::{WormPart, MAX_SIZE},
+    geometry::{Area, BoundaryMode, Direction, Point, SimRng},
+    obstacles::ObstacleMap,
+    spawning::MAX_ATTEMPTS,
+};
+
+/// Range of the arrays of the store holding the parts of a worm, used as a ring buffer:
+/// the head at start, preceded by the other parts (wrapping around the range)
+#[derive(Clone, Copy, Default)]
+struct Slot {
+    offset: usize,
+    capacity: usize,
+    start: usize,
+    size: usize,
+    target: Point,
+}
+
+/// Bodies of all the worms, as a structure of arrays: the coordinates of all the parts are
+/// held in two contiguous arrays (x and y), each worm owning a range of them, which grows
+/// with the worm (up to max_size parts)
+/// The ranges released by the removed worms (or left by the grown ones) are reclaimed when
+/// they make up half of the arrays, the indexes of the worms staying the same
+#[derive(Clone, Deserialize)]
+#[serde(from = "Vec<StoredBody>")]
+pub struct BodyStore {
+    xs: Vec<f32>,
+    ys: Vec<f32>,
+    slots: Vec<Slot>,
+    max_size: usize,
+    /// Number of entries of the arrays owned by no worm
+    unused: usize,
+}
+
+impl Default for BodyStore {
+    fn default() -> Self {
+        Self::new(MAX_SIZE)
+    }
+}
+
+impl BodyStore {
+    /// Creates a store without worms, each of them holding up to max_size parts
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            xs: Vec::new(),
+            ys: Vec::new(),
+            slots: Vec::new(),
+            max_size,
+            unused: 0,
+        }
+    }
+
+    /// Maximum number of parts of a worm
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    pub fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size;
+    }
+
+    /// Number of worms (including the removed ones)
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Body of the worm with the index
+    pub fn get(&self, id: usize) -> WormBody<'_> {
+        let slot = &self.slots[id];
+        let range = slot.offset..slot.offset + slot.capacity;
+        WormBody {
+            xs: &self.xs[range.clone()],
+            ys: &self.ys[range],
+            start: slot.start,
+            size: slot.size,
+            target: slot.target,
+        }
+    }
+
+    /// Bodies of all the worms, in the order of their indexes
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = WormBody<'_>> {
+        (0..self.len()).map(|id| self.get(id))
+    }
+
+    /// Adds a worm made of the parts (from the head to the tail, the ones beyond max_size
+    /// being left out), returning its index
+    pub fn push(&mut self, parts: &[WormPart], target: Point) -> usize {
+        let id = self.push_empty();
+        self.reserve(id, parts.len().min(self.max_size));
+        for &part in parts.iter().take(self.max_size).rev() {
+            self.grow(id, part);
+        }
+        self.slots[id].target = target;
+        id
+    }
+
+    /// Adds a worm without parts (nor space for them), returning its index
+    pub fn push_empty(&mut self) -> usize {
+        self.slots.push(Slot::default());
+        self.slots.len() - 1
+    }
+
+    /// Adds a worm of size parts, in a line going from the head in the direction
+    pub fn push_line(
+        &mut self,
+        size: usize,
+        head: WormPart,
+        direction: Direction,
+        part_size: f32,
+    ) -> usize {
+        let parts = std::iter::successors(Some(head), |part| {
+            Some(part.copy(direction, part_size * 2.))
+        })
+        .take(size)
+        .collect::<Vec<_>>();
+        self.push(&parts, head)
+    }
+
+    /// Adds a worm of size parts, in a line from a random head in a random direction,
+    /// away from the obstacles (if possible: the last line tried is kept otherwise)
+    pub fn push_rand(
+        &mut self,
+        rng: &mut SimRng,
+        size: usize,
+        part_size: f32,
+        n_directions: u8,
+        area: Area,
+        obstacles: &ObstacleMap,
+    ) -> usize {
+        let line = |rng: &mut SimRng| {
+            let head = WormPart::rand(rng, area.width, area.height);
+            (head, Direction::rand(rng, n_directions))
+        };
+        let blocked = |(head, direction): (WormPart, Direction)| {
+            (0..size).any(|i| {
+                let part = head.copy(direction, part_size * 2. * i as f32);
+                let previous = head.copy(direction, part_size * 2. * i.saturating_sub(1) as f32);
+                obstacles.collides(area, previous, part, part_size)
+            })
+        };
+        let mut placement = line(rng);
+        for _ in 1..MAX_ATTEMPTS {
+            if !blocked(placement) {
+                break;
+            }
+            placement = line(rng);
+        }
+        let (head, direction) = placement;
+        self.push_line(size, head, direction, part_size)
+    }
+
+    /// Moves the worm: the part becomes its head and its tail is left behind
+    /// (a worm without range only gets the target: it has no parts to move)
+    pub fn roll(&mut self, id: usize, part: WormPart, target: Point) {
+        let slot = &mut self.slots[id];
+        slot.target = target;
+        if slot.capacity == 0 {
+            return;
+        }
+        slot.start = (slot.start + 1) % slot.capacity;
+        self.xs[slot.offset + slot.start] = part.x;
+        self.ys[slot.offset + slot.start] = part.y;
+    }
+
+    /// Adds the part as the new head of the worm (rolling it if it already has max_size parts)
+    pub fn grow(&mut self, id: usize, part: WormPart) {
+        let slot = self.slots[id];
+        if slot.size == slot.capacity && slot.capacity < self.max_size {
+            self.reserve(id, (slot.capacity * 2).max(4).min(self.max_size));
+        }
+        self.roll(id, part, part);
+        let slot = &mut self.slots[id];
+        slot.size = slot.capacity.min(slot.size + 1);
+    }
+
+    /// Keeps the size parts closest to the head (a worm left without parts releases its range)
+    pub fn set_size(&mut self, id: usize, size: usize) {
+        if size == 0 {
+            self.release(id);
+        } else {
+            self.slots[id].size = size;
+        }
+    }
+
+    /// Removes the size parts closest to the head (all of them, at most)
+    pub fn shrink(&mut self, id: usize, size: usize) {
+        let slot = &mut self.slots[id];
+        let size = size.min(slot.size);
+        if size > 0 {
+            slot.start = (slot.capacity + slot.start - size) % slot.capacity;
+            slot.size -= size;
+        }
+    }
+
+    /// Replaces every part of the worm by the result of the function
+    pub fn transform(&mut self, id: usize, function: impl Fn(WormPart) -> WormPart) {
+        let slot = self.slots[id];
+        for i in 0..slot.size {
+            let pos = slot.offset + (slot.capacity + slot.start - i) % slot.capacity;
+            let part = function(Point {
+                x: self.xs[pos],
+                y: self.ys[pos],
+            });
+            self.xs[pos] = part.x;
+            self.ys[pos] = part.y;
+        }
+    }
+
+    /// Number of parts the worm can still grow
+    pub fn available_space(&self, id: usize) -> usize {
+        self.max_size.saturating_sub(self.slots[id].size)
+    }
+
+    /// Moves the parts of the worm to a new range at the end of the arrays,
+    /// if its own one cannot hold capacity parts
+    fn reserve(&mut self, id: usize, capacity: usize) {
+        let slot = self.slots[id];
+        if slot.capacity >= capacity {
+            return;
+        }
+        let parts = self.get(id).iter().rev().collect::<Vec<_>>();
+        let offset = self.xs.len();
+        self.xs.extend(parts.iter().map(|part| part.x));
+        self.ys.extend(parts.iter().map(|part| part.y));
+        self.xs.resize(offset + capacity, 0.);
+        self.ys.resize(offset + capacity, 0.);
+        self.unused += slot.capacity;
+        self.slots[id] = Slot {
+            offset,
+            capacity,
+            start: (slot.size + capacity - 1) % capacity,
+            ..slot
+        };
+        self.compact_if_sparse();
+    }
+
+    /// Takes all the parts of the worm, leaving its range unused
+    fn release(&mut self, id: usize) {
+        let slot = &mut self.slots[id];
+        self.unused += slot.capacity;
+        *slot = Slot {
+            target: slot.target,
+            ..Default::default()
+        };
+        self.compact_if_sparse();
+    }
+
+    fn compact_if_sparse(&mut self) {
+        if self.unused * 2 > self.xs.len() {
+            self.compact();
+        }
+    }
+
+    /// Packs the ranges of the worms, in the order of their indexes (and each one from
+    /// the tail to the head), dropping the unused entries
+    fn compact(&mut self) {
+        let used = self.xs.len() - self.unused;
+        let mut xs = Vec::with_capacity(used);
+        let mut ys = Vec::with_capacity(used);
+        for id in 0..self.len() {
+            let body = self.get(id);
+            let offset = xs.len();
+            let capacity = body.capacity();
+            xs.extend(body.iter().rev().map(|part| part.x));
+            ys.extend(body.iter().rev().map(|part| part.y));
+            xs.resize(offset + capacity, 0.);
+            ys.resize(offset + capacity, 0.);
+            let slot = &mut self.slots[id];
+            slot.offset = offset;
+            slot.start = (slot.size + capacity).saturating_sub(1) % capacity.max(1);
+        }
+        self.xs = xs;
+        self.ys = ys;
+        self.unused = 0;
+    }
+}
+
+/// Body as saved in the snapshots: its parts from the head to the tail
+#[derive(Serialize)]
+struct BodyRecord {
+    target: Point,
+    parts: Vec<WormPart>,
+}
+
+impl Serialize for BodyStore {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter().map(|body| BodyRecord {
+            target: body.target,
+            parts: body.iter().collect(),
+        }))
+    }
+}
+
+/// Body read from a snapshot
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredBody {
+    /// Ring buffer of max_size parts (snapshot version 2)
+    Ring {
+        target: Point,
+        parts: Vec<WormPart>,
+        start: usize,
+        size: usize,
+    },
+    Parts {
+        target: Point,
+        parts: Vec<WormPart>,
+    },
+}
+
+impl From<Vec<StoredBody>> for BodyStore {
+    /// Store with the bodies (its maximum size is the default one, or the size of the largest
+    /// ring buffer)
+    fn from(bodies: Vec<StoredBody>) -> Self {
+        let max_size = bodies
+            .iter()
+            .map(|body| match body {
+                StoredBody::Ring { parts, .. } => parts.len(),
+                StoredBody::Parts { parts, .. } => parts.len(),
+            })
+            .fold(MAX_SIZE, usize::max);
+        let mut store = Self::new(max_size);
+        for body in bodies {
+            match body {
+                StoredBody::Ring {
+                    target,
+                    parts,
+                    start,
+                    size,
+                } => {
+                    let capacity = parts.len();
+                    let parts = (0..size)
+                        .map(|i| parts[(capacity + start - i) % capacity])
+                        .collect::<Vec<_>>();
+                    store.push(&parts, target)
+                }
+                StoredBody::Parts { target, parts } => store.push(&parts, target),
+            };
+        }
+        store
+    }
+}
+
+/// Parts of a worm, borrowed from the store
+#[derive(Clone, Copy)]
+pub struct WormBody<'a> {
+    /// Range of the worm in the arrays of the store
+    xs: &'a [f32],
+    ys: &'a [f32],
+    start: usize,
+    size: usize,
+    pub target: Point,
+}
+
+impl<'a> WormBody<'a> {
+    fn part(&self, pos: usize) -> WormPart {
+        Point {
+            x: self.xs[pos],
+            y: self.ys[pos],
+        }
+    }
+
+    /// First part of the worm (None for a worm without parts, like the removed ones)
+    pub fn head(&self) -> Option<WormPart> {
+        (self.size > 0).then(|| self.part(self.start))
+    }
+
+    /// Last part of the worm (None for a worm without parts)
+    pub fn tail(&self) -> Option<WormPart> {
+        (self.size > 0)
+            .then(|| self.part((self.capacity() + self.start + 1 - self.size) % self.capacity()))
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Number of parts the range of the worm can hold (before moving to a larger one)
+    pub fn capacity(&self) -> usize {
+        self.xs.len()
+    }
+
+    /// Parts from the head to the tail
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = WormPart> + ExactSizeIterator + 'a {
+        let body = *self;
+        (0..self.size).map(move |i| body.part((body.capacity() + body.start - i) % body.capacity()))
+    }
+
+    /// Body made of the size parts closest to the head
+    pub fn front(&self, size: usize) -> Self {
+        Self {
+            size: size.min(self.size),
+            ..*self
+        }
+    }
+
+    /// Checks if a part is closer than distance to the point
+    /// Without wrapping borders, the coordinates are scanned with plain arithmetic (which the
+    /// compiler can vectorize), in a torus each offset is wrapped across the borders
+    pub fn closer_than(&self, point: Point, distance: f32, area: Area) -> bool {
+        if distance <= 0. {
+            return false;
+        }
+        let limit = distance * distance;
+        self.runs().iter().any(|(xs, ys)| match area.boundary {
+            BoundaryMode::Torus => xs.iter().zip(*ys).any(|(&x, &y)| {
+                let offset = area.offset(point, Point { x, y });
+                offset.x * offset.x + offset.y * offset.y < limit
+            }),
+            BoundaryMode::Bounded | BoundaryMode::Reflect => {
+                xs.iter().zip(*ys).fold(false, |close, (&x, &y)| {
+                    let (dx, dy) = (x - point.x, y - point.y);
+                    close | (dx * dx + dy * dy < limit)
+                })
+            }
+        })
+    }
+
+    /// Coordinates of the parts, as (at most) two contiguous runs of the range
+    fn runs(&self) -> [(&'a [f32], &'a [f32]); 2] {
+        let end = self.start + 1;
+        if self.size == 0 {
+            [(&[], &[]); 2]
+        } else if self.size <= end {
+            let from = end - self.size;
+            [(&self.xs[from..end], &self.ys[from..end]), (&[], &[])]
+        } else {
+            let wrapped = self.capacity() + end - self.size;
+            [
+                (&self.xs[..end], &self.ys[..end]),
+                (&self.xs[wrapped..], &self.ys[wrapped..]),
+            ]
+        }
+    }
+}
+
+impl fmt::Display for WormBody<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[ {}]",
+            self.iter()
+                .map(|part| format!("{part} "))
+                .collect::<String>()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use radians::{Angle, Degrees};
+
+    use crate::geometry::{Area, BoundaryMode, Direction, Point};
+
+    use super::BodyStore;
+
+    #[test]
+    fn bodies() {
+        let direction = Direction::from_radians(Angle::new(0.));
+        let mut store = BodyStore::new(32);
+        let worm = store.push_line(4, Point::default(), direction, 5.0);
+        let display = store.get(worm).to_string();
+        assert_eq!(
+            display,
+            "[ (0.00, 0.00) (10.00, 0.00) (20.00, 0.00) (30.00, 0.00) ]".to_owned()
+        );
+
+        let mut angle: Angle<f32, Degrees> = Angle::new(0.);
+        let mut new_head = store.get(worm).head().unwrap();
+        for _ in 0..1000 {
+            let direction = Direction::from_radians(angle.rad()).opposite();
+            angle += Angle::new(90.);
+            new_head = new_head.copy(direction, 10.);
+            store.roll(worm, new_head, new_head);
+        }
+        let display = store.get(worm).to_string();
+        assert_eq!(
+            display,
+            "[ (-0.00, -0.00) (0.00, -10.00) (-10.00, -10.00) (-10.00, -0.00) ]".to_owned()
+        );
+
+        let direction = Direction::from_radians(angle.rad());
+        for _ in 0..5 {
+            new_head = new_head.copy(direction, 10.);
+            store.grow(worm, new_head);
+        }
+        let display = store.get(worm).to_string();
+        assert_eq!(
+            display,
+            "[ (50.00, -0.00) (40.00, -0.00) (30.00, -0.00) (20.00, -0.00) \
+            (10.00, -0.00) (-0.00, -0.00) (0.00, -10.00) (-10.00, -10.00) (-10.00, -0.00) ]"
+                .to_owned()
+        );
+    }
+
+    #[test]
+    fn fill() {
+        let direction = Direction::from_radians(Angle::new(0.));
+        let mut store = BodyStore::new(32);
+        let worm1 = store.push_line(4, Point::default(), direction, 5.0);
+        let worm2 = store.push_empty();
+        let display = store.get(worm1).to_string();
+        assert_eq!(
+            display,
+            "[ (0.00, 0.00) (10.00, 0.00) (20.00, 0.00) (30.00, 0.00) ]".to_owned()
+        );
+
+        let parts = store.get(worm1).iter().rev().take(2).collect::<Vec<_>>();
+        parts.into_iter().for_each(|part| store.grow(worm2, part));
+        let display = store.get(worm2).to_string();
+        assert_eq!(display, "[ (20.00, 0.00) (30.00, 0.00) ]".to_owned());
+    }
+
+    #[test]
+    fn limits_and_compaction() {
+        let direction = Direction::from_radians(Angle::new(0.));
+        let mut store = BodyStore::new(6);
+        let worms = (0..5)
+            .map(|i| store.push_line(3, Point { x: 0., y: i as f32 }, direction, 0.5))
+            .collect::<Vec<_>>();
+        // a worm grows up to the maximum size, then rolls (leaving its tail behind)
+        for i in 1..=5 {
+            store.grow(
+                worms[0],
+                Point {
+                    x: -(i as f32),
+                    y: 0.,
+                },
+            );
+        }
+        let body = store.get(worms[0]);
+        assert_eq!(body.size(), 6);
+        assert_eq!(body.head(), Some(Point { x: -5., y: 0. }));
+        assert_eq!(body.tail(), Some(Point { x: 0., y: 0. }));
+        assert_eq!(store.available_space(worms[0]), 0);
+
+        // the removed worms free their parts, the others keeping their indexes and parts
+        let kept = store.get(worms[4]).to_string();
+        for &worm in &worms[1..4] {
+            store.set_size(worm, 0);
+        }
+        assert_eq!(store.len(), 5);
+        assert_eq!(store.get(worms[2]).size(), 0);
+        // a removed worm has neither head nor tail, and nothing to move
+        assert_eq!(store.get(worms[2]).head(), None);
+        assert_eq!(store.get(worms[2]).tail(), None);
+        store.roll(worms[2], Point::default(), Point::default());
+        store.shrink(worms[2], 1);
+        assert_eq!(store.get(worms[2]).size(), 0);
+        assert_eq!(store.get(worms[4]).to_string(), kept);
+        assert_eq!(store.xs.len(), 6 + 3);
+
+        // the collision scan covers the parts on both sides of the ring (and the borders)
+        let body = store.get(worms[0]);
+        let area = Area::new(100, 100, BoundaryMode::Torus);
+        assert!(body.closer_than(Point { x: 0.5, y: 0. }, 1., area));
+        assert!(body.closer_than(Point { x: 94.5, y: 0. }, 1., area));
+        assert!(!body.closer_than(Point { x: 1.5, y: 0. }, 1., area));
+        assert!(!body.front(5).closer_than(Point { x: 0.5, y: 0. }, 1., area));
+        let area = Area::new(100, 100, BoundaryMode::Bounded);
+        assert!(body.closer_than(Point { x: 0.5, y: 0. }, 1., area));
+        assert!(!body.closer_than(Point { x: 94.5, y: 0. }, 1., area));
+    }
+
+    #[test]
+    fn stored_bodies() {
+        let direction = Direction::from_radians(Angle::new(0.));
+        let mut store = BodyStore::new(8);
+        store.push_line(3, Point::default(), direction, 5.0);
+        store.push_empty();
+        let json = serde_json::to_string(&store).unwrap();
+        let loaded: BodyStore = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded.get(0).to_string(), store.get(0).to_string());
+        assert_eq!(loaded.get(1).size(), 0);
+
+        // the ring buffers of the older snapshots (head at start, the parts before it)
+        let ring = r#"[{"target": {"x": 0, "y": 0}, "start": 0, "size": 3, "parts": [
+            {"x": 1, "y": 0}, {"x": 9, "y": 9}, {"x": 3, "y": 0}, {"x": 2, "y": 0}]}]"#;
+        let loaded: BodyStore = serde_json::from_str(ring).unwrap();
+        assert_eq!(
+            loaded.get(0).to_string(),
+            "[ (1.00, 0.00) (2.00, 0.00) (3.00, 0.00) ]".to_owned()
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use clap::Args;
+use serde_json::Value;
+
+use crate::config::{ConfigError, SimConfig, DEFAULT_CONFIG_FILE};
+
+/// Command line arguments selecting the configuration of a simulation
+#[derive(Args, Clone)]
+pub struct ConfigArgs {
+    /// Configuration file of the simulation
+    #[arg(long, default_value = DEFAULT_CONFIG_FILE)]
+    pub config: String,
+    /// Seed of the simulation (overrides the one in the configuration)
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Overrides a configuration key, e.g --set n_worms=500 (the value is read as JSON,
+    /// or as a string if it is not valid JSON; nested keys are separated by dots)
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_assignment)]
+    pub overrides: Vec<(String, Value)>,
+}
+
+impl Default for ConfigArgs {
+    fn default() -> Self {
+        Self {
+            config: DEFAULT_CONFIG_FILE.to_owned(),
+            seed: None,
+            overrides: Vec::new(),
+        }
+    }
+}
+
+impl ConfigArgs {
+    /// Reads the configuration file, applying the overrides given in the command line
+    pub fn load(&self) -> Result<SimConfig, ConfigError> {
+        let file_content = std::fs::read_to_string(&self.config)?;
+        let mut json_config = serde_json::from_str::<Value>(&file_content)?;
+        for (key, value) in &self.overrides {
+            set_key(&mut json_config, key, value.clone())?;
+        }
+        let mut config = SimConfig::from_value(json_config)?;
+        config.seed = self.seed.or(config.seed);
+        Ok(config)
+    }
+
+    /// Same as load, but reports the errors and falls back to the default configuration
+    pub fn load_or_default(&self) -> SimConfig {
+        self.load().unwrap_or_else(|error| {
+            println!("Error loading configuration file {}:\n{error}", self.config);
+            SimConfig::default()
+        })
+    }
+}
+
+fn parse_assignment(assignment: &str) -> Result<(String, Value), String> {
+    let (key, value) = assignment
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, found '{assignment}'"))?;
+    let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_owned()));
+    Ok((key.to_owned(), value))
+}
+
+/// Sets the value of a (dot separated) key in a JSON object, creating the missing objects
+fn set_key(json_config: &mut Value, key: &str, value: Value) -> Result<(), ConfigError> {
+    let not_an_object = || ConfigError::Parse {
+        path: key.to_owned(),
+        message: "cannot set a key in a value that is not an object".to_owned(),
+    };
+    let (parents, last) = key
+        .rsplit_once('.')
+        .map_or((None, key), |(p, l)| (Some(p), l));
+    let mut target = json_config;
+    for parent in parents.into_iter().flat_map(|parents| parents.split('.')) {
+        target = target
+            .as_object_mut()
+            .ok_or_else(not_an_object)?
+            .entry(parent)
+            .or_insert_with(|| Value::Object(Default::default()));
+    }
+    target
+        .as_object_mut()
+        .ok_or_else(not_an_object)?
+        .insert(last.to_owned(), value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{parse_assignment, set_key};
+
+    #[test]
+    fn assignments() {
+        assert_eq!(
+            parse_assignment("n_worms=500").unwrap(),
+            ("n_worms".to_owned(), json!(500))
+        );
+        assert_eq!(
+            parse_assignment("name=abc").unwrap(),
+            ("name".to_owned(), json!("abc"))
+        );
+        assert!(parse_assignment("n_worms").is_err());
+
+        let mut config = json!({"n_worms": 10, "nested": {"value": 1}});
+        set_key(&mut config, "n_worms", json!(500)).unwrap();
+        set_key(&mut config, "nested.value", json!(2)).unwrap();
+        set_key(&mut config, "other.value", json!(true)).unwrap();
+        assert_eq!(
+            config,
+            json!({"n_worms": 500, "nested": {"value": 2}, "other": {"value": true}})
+        );
+        assert!(set_key(&mut config, "n_worms.value", json!(1)).is_err());
+    }
+}
@@ -1,239 +1,320 @@
-use crate::geometry::{Angle, Direction, Point};
+use crate::behaviors::BehaviorId;
+use crate::geometry::{angle_serde, Angle, Point, SimRng};
+use crate::neural::Brain;
+use rand::Rng;
+use rand_distr::Normal;
+use serde::{Deserialize, Serialize};
 use std::f32::consts::PI;
-use std::fmt;
+use std::ops::RangeInclusive;
 
-const MAX_SIZE: usize = 32;
+/// Default maximum number of parts of a worm
+pub const MAX_SIZE: usize = 32;
 pub type WormPart = Point;
-pub type Reward = Point;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum WormBehavior {
     Alive(usize),
     Dead(usize),
     Chasing,
     Removed,
+    /// Behavior registered in the scene (its id), with the number of ticks spent with it
+    Custom(BehaviorId, usize),
 }
 
-/// Fixed space allocated for all parts
-type BodyContainer = [WormPart; MAX_SIZE];
-
-/// Struct to hold all the parts of a worm (emulates a deque)
-pub struct WormBody {
-    pub target: Point,
-    parts: BodyContainer,
-    start: usize,
-    size: usize,
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct WormStats {
+    #[serde(with = "angle_serde")]
+    pub vision_range: Angle,
+    pub vision_distance: f32,
 }
 
-impl Default for WormBody {
+impl Default for WormStats {
     fn default() -> Self {
         Self {
-            target: Point::default(),
-            parts: [Point::default(); MAX_SIZE],
-            start: 0,
-            size: 0,
+            vision_range: Angle::new(5. * PI / 4.),
+            vision_distance: 300.,
         }
     }
 }
 
-impl WormBody {
-    pub fn new(size: usize, head: WormPart, direction: Direction, part_size: f32) -> Self {
-        // Allocate the array for the body parts
-        let mut parts = [head; MAX_SIZE];
-        // create the desired number of the body parts copying to the allocated array
-        let start = size - 1;
-        for i in (1..=start).rev() {
-            parts[i - 1] = parts[i].copy(direction, part_size * 2.)
-        }
-        Self {
-            target: head,
-            parts,
-            start,
-            size,
-        }
-    }
-
-    pub fn rand(size: usize, part_size: f32, xlimit: usize, ylimit: usize) -> Self {
-        Self::new(
-            size,
-            WormPart::rand(xlimit, ylimit),
-            Direction::rand(),
-            part_size,
-        )
-    }
-
-    pub fn head(&self) -> &WormPart {
-        &self.parts[self.start]
-    }
-
-    pub fn tail(&self) -> &WormPart {
-        &self.parts[(MAX_SIZE + self.start - self.size + 1) % MAX_SIZE]
-    }
-
-    pub fn size(&self) -> usize {
-        self.size
-    }
-
-    pub fn full(&self) -> bool {
-        self.size == MAX_SIZE
-    }
-
-    pub fn set_size(&mut self, size: usize) {
-        self.size = size;
-    }
-
-    pub fn available_space(&self) -> usize {
-        MAX_SIZE - self.size
-    }
-
-    pub fn shrink(&mut self, size: usize) {
-        self.start = (MAX_SIZE + self.start - size) % MAX_SIZE;
-        self.size -= size;
-    }
+/// Kinds of rewards, differing in their frequency, movement and effect on the worms
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RewardKind {
+    Plant,
+    #[default]
+    Prey,
+    Poison,
+    SuperFood,
+}
 
-    pub fn shift(&mut self, point: Point) {
-        for i in 0..self.size {
-            self.parts[(MAX_SIZE + self.start - i) % MAX_SIZE] =
-                self.parts[(MAX_SIZE + self.start - i) % MAX_SIZE] + point;
+impl RewardKind {
+    pub const ALL: [RewardKind; 4] = [
+        RewardKind::Plant,
+        RewardKind::Prey,
+        RewardKind::Poison,
+        RewardKind::SuperFood,
+    ];
+
+    /// Name of the kind in the configuration
+    pub fn name(&self) -> &'static str {
+        match self {
+            RewardKind::Plant => "plant",
+            RewardKind::Prey => "prey",
+            RewardKind::Poison => "poison",
+            RewardKind::SuperFood => "super_food",
         }
     }
+}
 
-    pub fn roll(&mut self, part: WormPart, target: Point) {
-        self.start = (self.start + 1) % MAX_SIZE;
-        self.parts[self.start] = part;
-        self.target = target;
-    }
+/// Something a worm can eat, moving towards its destination
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Reward {
+    /// (the rewards of the snapshots taken before the kinds existed are preys)
+    #[serde(default)]
+    pub kind: RewardKind,
+    pub position: Point,
+    pub destination: Point,
+}
 
-    pub fn grow(&mut self, part: WormPart) {
-        self.roll(part, part);
-        self.size = MAX_SIZE.min(self.size + 1);
-    }
+/// Properties of a kind of reward
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RewardType {
+    /// Frequency of the kind among the spawned rewards (relative to the other kinds)
+    pub spawn_rate: f32,
+    /// Speed of the reward, relative to the reward speed of the scene (0 for a static reward)
+    pub speed: f32,
+    /// Number of parts grown by the worm eating it (lost if negative)
+    pub growth: i32,
+    /// Worth of the reward for the worms: weighs the choice of a target,
+    /// and multiplies the energy delivered (negative for a harmful reward)
+    pub value: f32,
+    /// Color of the reward in the interface (RGB)
+    pub color: [u8; 3],
+}
 
-    pub fn iter(&self) -> WormBodyIterator {
-        WormBodyIterator {
-            body: self,
-            counter: 0,
+impl Default for RewardType {
+    fn default() -> Self {
+        Self {
+            spawn_rate: 0.,
+            speed: 1.,
+            growth: 1,
+            value: 1.,
+            color: [0xF8, 0xFF, 0xE5],
         }
     }
 }
 
-impl fmt::Display for WormBody {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "[ {}]",
-            self.iter()
-                .map(|part| part.to_string())
-                .collect::<Vec<_>>()
-                .join(" ")
-        )
-    }
+/// Properties of every kind of reward
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RewardTypes {
+    pub plant: RewardType,
+    pub prey: RewardType,
+    pub poison: RewardType,
+    pub super_food: RewardType,
 }
 
-pub struct WormBodyIterator<'a> {
-    body: &'a WormBody,
-    counter: usize,
+impl Default for RewardTypes {
+    /// Only preys are spawned by default
+    fn default() -> Self {
+        Self {
+            plant: RewardType {
+                speed: 0.,
+                color: [0x9C, 0xD8, 0x6B],
+                ..Default::default()
+            },
+            prey: RewardType {
+                spawn_rate: 1.,
+                ..Default::default()
+            },
+            poison: RewardType {
+                speed: 0.5,
+                growth: -2,
+                value: -1.,
+                color: [0xD6, 0x45, 0x45],
+                ..Default::default()
+            },
+            super_food: RewardType {
+                speed: 0.,
+                growth: 3,
+                value: 3.,
+                color: [0xFF, 0xC8, 0x3D],
+                ..Default::default()
+            },
+        }
+    }
 }
 
-impl<'a> Iterator for WormBodyIterator<'a> {
-    type Item = &'a Point;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.counter < self.body.size {
-            self.counter += 1;
-            let pos = (1 + MAX_SIZE + self.body.start - self.counter) % MAX_SIZE;
-            return Some(&self.body.parts[pos]);
+impl RewardTypes {
+    pub fn get(&self, kind: RewardKind) -> &RewardType {
+        match kind {
+            RewardKind::Plant => &self.plant,
+            RewardKind::Prey => &self.prey,
+            RewardKind::Poison => &self.poison,
+            RewardKind::SuperFood => &self.super_food,
         }
-        None
     }
-}
 
-impl<'a> DoubleEndedIterator for WormBodyIterator<'a> {
-    fn next_back(&mut self) -> Option<Self::Item> {
-        if self.counter < self.body.size {
-            self.counter += 1;
-            let pos = (MAX_SIZE + self.body.start + self.counter - self.body.size) % MAX_SIZE;
-            return Some(&self.body.parts[pos]);
+    /// Chooses the kind of a new reward according to the spawn rates
+    /// (when a single kind can be spawned, no random number is drawn)
+    pub fn sample_kind(&self, rng: &mut SimRng) -> RewardKind {
+        let spawned = RewardKind::ALL
+            .into_iter()
+            .filter(|&kind| self.get(kind).spawn_rate > 0.)
+            .collect::<Vec<_>>();
+        if spawned.len() <= 1 {
+            return spawned.first().copied().unwrap_or(RewardKind::Prey);
+        }
+        let total = spawned
+            .iter()
+            .map(|&kind| self.get(kind).spawn_rate)
+            .sum::<f32>();
+        let mut drawn = rng.gen_range(0. ..total);
+        for &kind in &spawned {
+            drawn -= self.get(kind).spawn_rate;
+            if drawn < 0. {
+                return kind;
+            }
         }
-        None
+        spawned[spawned.len() - 1]
     }
 }
 
-#[derive(Clone, Copy)]
-pub struct WormStats {
-    pub vision_range: Angle,
-    pub vision_distance: f32,
+/// Heritable traits of a worm
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Genome {
+    pub stats: WormStats,
+    /// Length of a movement, relative to the default one (twice the part size)
+    pub speed: f32,
+    /// Number of ticks a worm of a single part survives without eating
+    /// (divided by the size of bigger worms)
+    pub starvation: usize,
+    /// Size at which the worm splits
+    pub split_size: usize,
+    /// Network driving the movements of the worm (None: the scripted movers)
+    #[serde(default)]
+    pub brain: Option<Brain>,
 }
 
-impl Default for WormStats {
-    fn default() -> Self {
+impl Genome {
+    /// Copy of the genome with each trait changed by a random (gaussian) factor:
+    /// the deviation is relative to the value of the trait
+    /// (the split size is kept in the given range)
+    pub fn mutate(
+        &self,
+        rng: &mut SimRng,
+        deviation: f32,
+        split_range: RangeInclusive<usize>,
+    ) -> Self {
+        if deviation <= 0. {
+            return self.clone();
+        }
+        let noise = Normal::new(1., deviation).expect("deviation is positive and finite");
+        let mut mutate = |value: f32| value * rng.sample(noise).max(0.);
         Self {
-            vision_range: Angle::new(5. * PI / 4.),
-            vision_distance: 300.,
+            stats: WormStats {
+                vision_range: Angle::new(
+                    mutate(self.stats.vision_range.val()).clamp(0.01, 2. * PI),
+                ),
+                vision_distance: mutate(self.stats.vision_distance).max(1.),
+            },
+            speed: mutate(self.speed).clamp(0.1, 10.),
+            starvation: (mutate(self.starvation as f32).round() as usize).max(1),
+            split_size: (mutate(self.split_size as f32).round() as usize)
+                .clamp(*split_range.start(), *split_range.end()),
+            brain: self
+                .brain
+                .as_ref()
+                .map(|brain| brain.mutate(rng, deviation)),
+        }
+    }
+
+    /// Average of two genomes, weighted by the number of parts coming from each worm
+    pub fn combine(&self, weight: usize, other: &Self, other_weight: usize) -> Self {
+        let total = (weight + other_weight).max(1) as f32;
+        let average =
+            |lhs: f32, rhs: f32| (lhs * weight as f32 + rhs * other_weight as f32) / total;
+        Self {
+            stats: WormStats {
+                vision_range: Angle::new(average(
+                    self.stats.vision_range.val(),
+                    other.stats.vision_range.val(),
+                )),
+                vision_distance: average(self.stats.vision_distance, other.stats.vision_distance),
+            },
+            speed: average(self.speed, other.speed),
+            starvation: average(self.starvation as f32, other.starvation as f32).round() as usize,
+            split_size: average(self.split_size as f32, other.split_size as f32).round() as usize,
+            // a worm without brain keeps on following the scripts
+            brain: match (&self.brain, &other.brain) {
+                (Some(brain), Some(other_brain)) => {
+                    Some(brain.combine(weight, other_brain, other_weight))
+                }
+                (brain, _) => brain.clone(),
+            },
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use radians::{Angle, Degrees};
+    use rand::SeedableRng;
 
-    use crate::geometry::{Direction, Point};
+    use crate::geometry::SimRng;
 
-    use super::WormBody;
+    use super::{Genome, RewardKind, RewardTypes, WormStats};
 
     #[test]
-    fn bodies() {
-        let direction = Direction::from_radians(Angle::new(0.));
-        let mut worm = WormBody::new(4, Point::default(), direction, 5.0);
-        let display = worm.to_string();
-        assert_eq!(
-            display,
-            "[ (0.00, 0.00) (10.00, 0.00) (20.00, 0.00) (30.00, 0.00) ]".to_owned()
-        );
-
-        let mut angle: Angle<f32, Degrees> = Angle::new(0.);
-        let mut new_head = *worm.head();
-        for _ in 0..1000 {
-            let direction = Direction::from_radians(angle.rad()).opposite();
-            angle += Angle::new(90.);
-            new_head = new_head.copy(direction, 10.);
-            worm.roll(new_head, new_head);
-        }
-        let display = worm.to_string();
-        assert_eq!(
-            display,
-            "[ (-0.00, 0.00) (-0.00, -10.00) (-10.00, -10.00) (-10.00, 0.00) ]".to_owned()
-        );
-
-        let direction = Direction::from_radians(angle.rad());
-        for _ in 0..5 {
-            new_head = new_head.copy(direction, 10.);
-            worm.grow(new_head);
-        }
-        let display = worm.to_string();
-        assert_eq!(
-            display,
-            "[ (50.00, -0.00) (40.00, -0.00) (30.00, -0.00) (20.00, -0.00) \
-            (10.00, -0.00) (-0.00, 0.00) (-0.00, -10.00) (-10.00, -10.00) (-10.00, 0.00) ]"
-                .to_owned()
-        );
+    fn genomes() {
+        let genome = Genome {
+            stats: WormStats::default(),
+            speed: 1.,
+            starvation: 1000,
+            split_size: 24,
+            brain: None,
+        };
+        let mut rng = SimRng::seed_from_u64(1);
+        let unchanged = genome.mutate(&mut rng, 0., 8..=32);
+        assert_eq!(unchanged.starvation, 1000);
+        assert_eq!(unchanged.split_size, 24);
+
+        let mutated = genome.mutate(&mut rng, 10., 8..=32);
+        assert!((8..=32).contains(&mutated.split_size));
+        assert!(mutated.speed >= 0.1 && mutated.starvation >= 1);
+
+        let other = Genome {
+            speed: 2.,
+            starvation: 2000,
+            split_size: 32,
+            ..genome.clone()
+        };
+        let combined = genome.combine(3, &other, 1);
+        assert_eq!(combined.speed, 1.25);
+        assert_eq!(combined.starvation, 1250);
+        assert_eq!(combined.split_size, 26);
     }
 
     #[test]
-    fn fill() {
-        let direction = Direction::from_radians(Angle::new(0.));
-        let worm1 = WormBody::new(4, Point::default(), direction, 5.0);
-        let mut worm2 = WormBody::default();
-        let display = worm1.to_string();
-        assert_eq!(
-            display,
-            "[ (0.00, 0.00) (10.00, 0.00) (20.00, 0.00) (30.00, 0.00) ]".to_owned()
-        );
-
-        let parts = worm1.iter().rev().take(2).cloned().collect::<Vec<_>>();
-        parts.into_iter().for_each(|part| worm2.grow(part));
-        let display = worm2.to_string();
-        assert_eq!(display, "[ (20.00, 0.00) (30.00, 0.00) ]".to_owned());
+    fn reward_kinds() {
+        let mut rng = SimRng::seed_from_u64(1);
+        let mut types = RewardTypes::default();
+        assert_eq!(types.sample_kind(&mut rng), RewardKind::Prey);
+
+        types.prey.spawn_rate = 0.;
+        types.plant.spawn_rate = 3.;
+        types.poison.spawn_rate = 1.;
+        let kinds = (0..1000)
+            .map(|_| types.sample_kind(&mut rng))
+            .collect::<Vec<_>>();
+        let plants = kinds
+            .iter()
+            .filter(|&&kind| kind == RewardKind::Plant)
+            .count();
+        assert!(kinds
+            .iter()
+            .all(|&kind| kind == RewardKind::Plant || kind == RewardKind::Poison));
+        assert!((700..800).contains(&plants));
     }
 }
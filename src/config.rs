@@ -1,66 +1,964 @@
-use serde_json::Value;
+use serde::{Deserialize, Serialize};
+use std::{fmt, io};
 
-use crate::scene::SceneParameters;
+use crate::{
+    composites::{RewardKind, RewardTypes, WormStats},
+    geometry::BoundaryMode,
+    metabolism::{EnergyParameters, Metabolism},
+    movement::{MovementModel, TargetSelection},
+    neural::Controller,
+    obstacles::{Obstacle, ObstacleMap},
+    planning::{Planner, PlanningParameters},
+    predators::PredatorParameters,
+    scene::{Scene, SceneParameters, UpdateMode},
+    scent::ScentParameters,
+    spawning::{Seasons, SpawnParameters, SpawnPattern},
+    species::{MergeRule, Species},
+};
 
+/// Configuration file used when none is given
+pub const DEFAULT_CONFIG_FILE: &str = "./conf/default.json";
+
+/// Configuration of a simulation, as read from a JSON file
+/// (missing fields take their default value)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct SimConfig {
     pub n_worms: usize,
     pub n_rewards: usize,
-    pub scene_params: SceneParameters,
+    /// Time between two ticks of the simulation (in milliseconds)
+    #[serde(rename = "milisec")]
     pub interval: u64,
+    /// Number of ticks executed when the simulation is created, before being displayed
+    pub warmup_ticks: usize,
+    /// Dimensions of the scene (when not given by a window)
+    pub width: usize,
+    pub height: usize,
+    /// Initial number of parts of a worm (also the number of parts split from a full worm)
+    pub worm_size: usize,
+    /// Radius of a worm part
+    #[serde(rename = "part_size")]
+    pub body_size: f32,
+    pub starvation: usize,
+    pub expiration: usize,
+    /// Seed of the random number generator (a random one is used if not set)
+    pub seed: Option<u64>,
+    /// Maximum number of parts of a worm
+    pub max_size: usize,
+    /// Number of possible movement directions
+    pub n_directions: u8,
+    /// Angle covered by the vision of a worm (in degrees)
+    pub vision_range: f32,
+    pub vision_distance: f32,
+    /// Distance covered by a reward in a tick (a quarter of part_size if not set)
+    pub reward_speed: Option<f32>,
+    /// Behavior of the borders of the scene: "bounded", "torus" or "reflect"
+    pub boundary: BoundaryMode,
+    /// Movement model of the worms: "discrete" (n_directions fixed directions)
+    /// or "continuous" (any heading, limited by max_turn_rate)
+    pub movement: MovementKind,
+    /// Maximum change of heading in a tick, in the continuous model (in degrees)
+    pub max_turn_rate: f32,
+    /// Number of random headings tried (after the desired one) in the continuous model
+    pub heading_samples: usize,
+    /// Standard deviation of the mutations of an offspring genome, relative to each trait
+    pub mutation: f32,
+    /// Hunger model: "counter" (starvation after a number of ticks without eating)
+    /// or "energy" (starvation when the energy reserve, filled by rewards, is empty)
+    pub metabolism: MetabolismKind,
+    /// Energy of a new worm, in the energy metabolism
+    pub energy_initial: f32,
+    /// Energy spent in a movement per part of the body
+    pub energy_per_part: f32,
+    /// Energy spent in a movement (multiplied by the speed of the worm)
+    pub energy_per_move: f32,
+    /// Energy delivered by a reward
+    pub energy_per_reward: f32,
+    /// Energy needed to grow a part when eating
+    pub energy_growth_cost: f32,
+    /// Energy needed to split
+    pub energy_split_cost: f32,
+    /// Spawn rate, speed, growth, value and color of each kind of reward
+    /// ("plant", "prey", "poison" and "super_food")
+    pub reward_types: RewardTypes,
+    /// How a worm chooses among the rewards in sight: "closest"
+    /// or "value" (distance weighed by the value, harmful rewards avoided)
+    pub target_selection: TargetSelection,
+    /// Where the rewards spawn: "uniform", "patches" (around patch_count fixed centers)
+    /// or "gradient" (denser towards gradient_direction)
+    pub spawn_pattern: SpawnKind,
+    pub patch_count: usize,
+    /// Radius of the patches around their centers
+    pub patch_radius: f32,
+    /// Direction the density of the gradient grows towards (in degrees)
+    pub gradient_direction: f32,
+    /// Density on the sparse side of the gradient, relative to the dense side
+    pub gradient_min_density: f32,
+    /// Length of a cycle of abundance and scarcity, in ticks (constant rewards if not set)
+    pub season_period: Option<u64>,
+    /// Fraction of the rewards missing at the peak of the scarcity
+    pub season_amplitude: f32,
+    /// Number of eaten rewards regrown per tick (replaced right away if not set)
+    pub regrowth_rate: Option<f32>,
+    /// Static obstacles: circles, rects or segments,
+    /// e.g {"shape": "segment", "from": {"x": 0, "y": 50}, "to": {"x": 80, "y": 50}}
+    pub obstacles: Vec<Obstacle>,
+    /// JSON file with more obstacles (a list, in the same format)
+    pub map: Option<String>,
+    /// If the obstacles hide the rewards and worms behind them
+    pub block_vision: bool,
+    /// How the 'Alive' and 'Chasing' worms find their way: "greedy" (straight to the destination)
+    /// or "astar" (shortest path around the bodies and obstacles)
+    pub alive_planner: Planner,
+    pub chasing_planner: Planner,
+    /// Size of the cells of the planning grid (twice part_size if not set)
+    pub planner_cell_size: Option<f32>,
+    /// Maximum number of cells explored when planning a path
+    pub planner_budget: usize,
+    /// Number of predators hunting the worms
+    pub n_predators: usize,
+    /// Distance covered by a predator in a tick (twice part_size if not set, as the worms)
+    pub predator_speed: Option<f32>,
+    /// Angle covered by the vision of a predator (in degrees)
+    pub predator_vision_range: f32,
+    pub predator_vision_distance: f32,
+    /// Number of ticks a predator survives without biting a worm
+    pub predator_starvation: usize,
+    /// Number of parts a predator bites off the tail of a worm
+    pub predator_bite: usize,
+    /// Number of parts a predator eats before reproducing (never if 0)
+    pub predator_reproduction: usize,
+    /// Distance under which the worms flee from the predators they see
+    pub flee_distance: f32,
+    /// Scent deposited by a worm at each movement (the worms leave no scent if not set)
+    pub scent_deposit: Option<f32>,
+    /// Extra scent deposited by a worm that just ate
+    pub scent_meal_deposit: f32,
+    /// Fraction of the scent of a cell spreading to its neighbours in a tick
+    pub scent_diffusion: f32,
+    /// Fraction of the scent lost in a tick
+    pub scent_evaporation: f32,
+    /// Size of the cells of the scent field (four times part_size if not set)
+    pub scent_cell_size: Option<f32>,
+    /// Weakest scent followed by the worms
+    pub scent_threshold: f32,
+    /// Species of the worms, each with its own count (replacing n_worms) and, optionally,
+    /// worm_size, part_size, starvation, vision and colors (the values above if not set)
+    pub species: Vec<SpeciesConfig>,
+    /// Which worms a chasing worm may merge with: "any" or "same" (its own species)
+    pub merge_rule: MergeRule,
+    /// What drives the movements of the alive worms: "scripted" (the best reward in sight)
+    /// or "neural" (a network of random weights in the genome, evolving with the mutations)
+    pub controller: Controller,
+    /// Rhai script with the rules of the alive worms (target selection and transitions),
+    /// only with the scripting feature
+    pub script: Option<String>,
+    /// How the worms are updated in a tick: "sequential" (one after the other)
+    /// or "parallel" (all at once, the conflicts being resolved afterwards)
+    pub update: UpdateMode,
+    /// Obstacles read from the map file
+    #[serde(skip)]
+    pub map_obstacles: Vec<Obstacle>,
+}
+
+/// Species of worms, as given in the configuration
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpeciesConfig {
+    pub name: String,
+    pub count: usize,
+    pub worm_size: Option<usize>,
+    pub part_size: Option<f32>,
+    pub starvation: Option<usize>,
+    /// In degrees
+    pub vision_range: Option<f32>,
+    pub vision_distance: Option<f32>,
+    pub controller: Option<Controller>,
+    /// Colors as [r, g, b]
+    pub head_color: Option<[u8; 3]>,
+    pub body_color: Option<[u8; 3]>,
+}
+
+/// Spawn patterns that can be selected in the configuration
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpawnKind {
+    #[default]
+    Uniform,
+    Patches,
+    Gradient,
+}
+
+/// Metabolisms that can be selected in the configuration
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetabolismKind {
+    #[default]
+    Counter,
+    Energy,
+}
+
+/// Movement models that can be selected in the configuration
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MovementKind {
+    #[default]
+    Discrete,
+    Continuous,
 }
 
 impl Default for SimConfig {
     fn default() -> Self {
+        let scene_params = SceneParameters::default();
+        let energy = EnergyParameters::default();
+        let predators = PredatorParameters::default();
+        let scent = ScentParameters::default();
         Self {
             n_worms: 15,
             n_rewards: 5,
-            scene_params: SceneParameters {
-                worm_size: 8,
-                body_size: 7.0,
-                starvation: 2000,
-                expiration: 25,
-            },
             interval: 200,
+            warmup_ticks: 50,
+            width: 1000,
+            height: 1000,
+            worm_size: scene_params.worm_size,
+            body_size: scene_params.body_size,
+            starvation: scene_params.starvation,
+            expiration: scene_params.expiration,
+            seed: scene_params.seed,
+            max_size: scene_params.max_size,
+            n_directions: scene_params.n_directions,
+            vision_range: scene_params.stats.vision_range.deg().val(),
+            vision_distance: scene_params.stats.vision_distance,
+            reward_speed: None,
+            boundary: scene_params.boundary,
+            movement: MovementKind::Discrete,
+            max_turn_rate: 30.,
+            heading_samples: 8,
+            mutation: scene_params.mutation,
+            metabolism: MetabolismKind::Counter,
+            energy_initial: energy.initial,
+            energy_per_part: energy.per_part,
+            energy_per_move: energy.per_move,
+            energy_per_reward: energy.per_reward,
+            energy_growth_cost: energy.growth_cost,
+            energy_split_cost: energy.split_cost,
+            reward_types: scene_params.reward_types,
+            target_selection: scene_params.target_selection,
+            spawn_pattern: SpawnKind::Uniform,
+            patch_count: 5,
+            patch_radius: 60.,
+            gradient_direction: 0.,
+            gradient_min_density: 0.1,
+            season_period: None,
+            season_amplitude: 0.8,
+            regrowth_rate: None,
+            obstacles: Vec::new(),
+            map: None,
+            block_vision: false,
+            alive_planner: Planner::Greedy,
+            chasing_planner: Planner::Greedy,
+            planner_cell_size: None,
+            planner_budget: scene_params.planning.budget,
+            n_predators: predators.count,
+            predator_speed: None,
+            predator_vision_range: predators.stats.vision_range.deg().val(),
+            predator_vision_distance: predators.stats.vision_distance,
+            predator_starvation: predators.starvation,
+            predator_bite: predators.bite,
+            predator_reproduction: predators.reproduction,
+            flee_distance: predators.flee_distance,
+            scent_deposit: None,
+            scent_meal_deposit: scent.meal_deposit,
+            scent_diffusion: scent.diffusion,
+            scent_evaporation: scent.evaporation,
+            scent_cell_size: None,
+            scent_threshold: scent.threshold,
+            species: Vec::new(),
+            merge_rule: MergeRule::Any,
+            controller: Controller::Scripted,
+            script: None,
+            update: scene_params.update,
+            map_obstacles: Vec::new(),
+        }
+    }
+}
+
+/// Errors when reading a configuration
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    /// The content could not be parsed: path of the offending field and cause
+    Parse {
+        path: String,
+        message: String,
+    },
+    /// A field has a value not allowed: name of the field and cause
+    Invalid {
+        field: &'static str,
+        reason: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(error) => write!(f, "{error}"),
+            ConfigError::Parse { path, message } => write!(f, "{path}: {message}"),
+            ConfigError::Invalid { field, reason } => write!(f, "{field}: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(error: io::Error) -> Self {
+        ConfigError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for ConfigError {
+    fn from(error: serde_json::Error) -> Self {
+        ConfigError::Parse {
+            path: ".".to_owned(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<serde_path_to_error::Error<serde_json::Error>> for ConfigError {
+    fn from(error: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        ConfigError::Parse {
+            path: error.path().to_string(),
+            message: error.into_inner().to_string(),
         }
     }
 }
 
 impl SimConfig {
-    pub fn read_default() -> Self {
-        let default_conf_file = "./conf/default.json";
-        Self::from_json(default_conf_file).unwrap_or_else(|error| {
-            println!("Error loading default configuration file {default_conf_file}:\n{error}");
-            SimConfig::default()
-        })
+    pub fn from_json(file_path: &str) -> Result<Self, ConfigError> {
+        Self::parse(&std::fs::read_to_string(file_path)?)
     }
 
-    fn from_json(file_path: &str) -> Result<Self, String> {
-        let file_content = std::fs::read_to_string(file_path).map_err(|err| format!("{err}"))?;
+    /// Parses and validates a configuration in JSON (reading its map file, if any)
+    pub fn parse(content: &str) -> Result<Self, ConfigError> {
+        let deserializer = &mut serde_json::Deserializer::from_str(content);
+        let mut config: Self = serde_path_to_error::deserialize(deserializer)?;
+        config.load_map()?;
+        config.validate()?;
+        Ok(config)
+    }
 
-        let json_config =
-            serde_json::from_str::<Value>(&file_content).map_err(|err| format!("{err}"))?;
+    /// Builds and validates a configuration from an already parsed JSON value
+    /// (reading its map file, if any)
+    pub fn from_value(json_config: serde_json::Value) -> Result<Self, ConfigError> {
+        let mut config: Self = serde_path_to_error::deserialize(json_config)?;
+        config.load_map()?;
+        config.validate()?;
+        Ok(config)
+    }
 
-        let get_int_attr = |attr: &str| {
-            json_config[attr]
-                .as_u64()
-                .ok_or_else(|| format!("Error reading {attr}"))
+    /// Reads the obstacles of the map file into map_obstacles (none without map file)
+    pub fn load_map(&mut self) -> Result<(), ConfigError> {
+        self.map_obstacles = match &self.map {
+            Some(path) => {
+                let content = std::fs::read_to_string(path)?;
+                let deserializer = &mut serde_json::Deserializer::from_str(&content);
+                serde_path_to_error::deserialize(deserializer).map_err(|error| {
+                    ConfigError::Parse {
+                        path: format!("map {path} {}", error.path()),
+                        message: error.into_inner().to_string(),
+                    }
+                })?
+            }
+            None => Vec::new(),
         };
-        let get_float_attr = |attr: &str| {
-            json_config[attr]
-                .as_f64()
-                .ok_or_else(|| format!("Error reading {attr}"))
+        Ok(())
+    }
+
+    /// Checks the values that would make a scene impossible to build or to run
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let check = |valid: bool, field: &'static str, reason: &str| {
+            valid.then_some(()).ok_or_else(|| ConfigError::Invalid {
+                field,
+                reason: reason.to_owned(),
+            })
         };
+        check(self.width > 0, "width", "must be positive")?;
+        check(self.height > 0, "height", "must be positive")?;
+        check(self.interval > 0, "milisec", "must be positive")?;
+        check(self.worm_size >= 1, "worm_size", "must be at least 1")?;
+        check(
+            self.worm_size * 2 <= self.max_size,
+            "worm_size",
+            &format!("must be at most half of max_size ({})", self.max_size),
+        )?;
+        check(
+            self.body_size.is_finite() && self.body_size > 0.,
+            "part_size",
+            "must be positive",
+        )?;
+        check(
+            (4..=64).contains(&self.n_directions) && self.n_directions.is_multiple_of(2),
+            "n_directions",
+            "must be an even number between 4 and 64",
+        )?;
+        check(
+            self.vision_range > 0. && self.vision_range <= 360.,
+            "vision_range",
+            "must be in the range ]0, 360] (degrees)",
+        )?;
+        check(
+            self.vision_distance.is_finite() && self.vision_distance > 0.,
+            "vision_distance",
+            "must be positive",
+        )?;
+        check(
+            self.reward_speed
+                .is_none_or(|speed| speed.is_finite() && speed >= 0.),
+            "reward_speed",
+            "must not be negative",
+        )?;
+        check(
+            self.max_turn_rate > 0. && self.max_turn_rate <= 180.,
+            "max_turn_rate",
+            "must be in the range ]0, 180] (degrees)",
+        )?;
+        check(
+            self.heading_samples >= 1,
+            "heading_samples",
+            "must be at least 1",
+        )?;
+        check(
+            self.mutation.is_finite() && self.mutation >= 0.,
+            "mutation",
+            "must not be negative",
+        )?;
+        check(
+            self.energy_initial.is_finite() && self.energy_initial > 0.,
+            "energy_initial",
+            "must be positive",
+        )?;
+        check(
+            RewardKind::ALL
+                .iter()
+                .any(|&kind| self.reward_types.get(kind).spawn_rate > 0.),
+            "reward_types",
+            "at least one kind must have a positive spawn_rate",
+        )?;
+        RewardKind::ALL.into_iter().try_for_each(|kind| {
+            let reward_type = self.reward_types.get(kind);
+            let name = kind.name();
+            check(
+                reward_type.spawn_rate.is_finite() && reward_type.spawn_rate >= 0.,
+                "reward_types",
+                &format!("{name}.spawn_rate must not be negative"),
+            )?;
+            check(
+                reward_type.speed.is_finite() && reward_type.speed >= 0.,
+                "reward_types",
+                &format!("{name}.speed must not be negative"),
+            )?;
+            check(
+                reward_type.value.is_finite(),
+                "reward_types",
+                &format!("{name}.value must be a finite number"),
+            )
+        })?;
+        check(self.patch_count >= 1, "patch_count", "must be at least 1")?;
+        check(
+            self.patch_radius.is_finite() && self.patch_radius > 0.,
+            "patch_radius",
+            "must be positive",
+        )?;
+        check(
+            self.gradient_direction.is_finite(),
+            "gradient_direction",
+            "must be a finite number",
+        )?;
+        check(
+            (0. ..=1.).contains(&self.gradient_min_density),
+            "gradient_min_density",
+            "must be in the range [0, 1]",
+        )?;
+        check(
+            self.season_period.is_none_or(|period| period > 0),
+            "season_period",
+            "must be positive",
+        )?;
+        check(
+            (0. ..=1.).contains(&self.season_amplitude),
+            "season_amplitude",
+            "must be in the range [0, 1]",
+        )?;
+        check(
+            self.regrowth_rate
+                .is_none_or(|rate| rate.is_finite() && rate > 0.),
+            "regrowth_rate",
+            "must be positive",
+        )?;
+        self.obstacles
+            .iter()
+            .chain(&self.map_obstacles)
+            .try_for_each(|obstacle| match obstacle.invalid() {
+                Some(reason) => check(false, "obstacles", reason),
+                None => Ok(()),
+            })?;
+        check(
+            self.planner_cell_size
+                .is_none_or(|size| size.is_finite() && size > 0.),
+            "planner_cell_size",
+            "must be positive",
+        )?;
+        check(
+            self.planner_budget >= 1,
+            "planner_budget",
+            "must be at least 1",
+        )?;
+        check(
+            self.predator_speed
+                .is_none_or(|speed| speed.is_finite() && speed > 0.),
+            "predator_speed",
+            "must be positive",
+        )?;
+        check(
+            self.predator_vision_range > 0. && self.predator_vision_range <= 360.,
+            "predator_vision_range",
+            "must be in the range ]0, 360] (degrees)",
+        )?;
+        check(
+            self.predator_vision_distance.is_finite() && self.predator_vision_distance > 0.,
+            "predator_vision_distance",
+            "must be positive",
+        )?;
+        check(
+            self.predator_bite >= 1,
+            "predator_bite",
+            "must be at least 1",
+        )?;
+        check(
+            self.flee_distance.is_finite() && self.flee_distance >= 0.,
+            "flee_distance",
+            "must not be negative",
+        )?;
+        check(
+            self.scent_deposit
+                .is_none_or(|deposit| deposit.is_finite() && deposit >= 0.),
+            "scent_deposit",
+            "must not be negative",
+        )?;
+        check(
+            self.scent_meal_deposit.is_finite() && self.scent_meal_deposit >= 0.,
+            "scent_meal_deposit",
+            "must not be negative",
+        )?;
+        check(
+            (0. ..=1.).contains(&self.scent_diffusion),
+            "scent_diffusion",
+            "must be in the range [0, 1]",
+        )?;
+        check(
+            (0. ..=1.).contains(&self.scent_evaporation),
+            "scent_evaporation",
+            "must be in the range [0, 1]",
+        )?;
+        check(
+            self.scent_cell_size
+                .is_none_or(|size| size.is_finite() && size > 0.),
+            "scent_cell_size",
+            "must be positive",
+        )?;
+        check(
+            self.scent_threshold.is_finite() && self.scent_threshold > 0.,
+            "scent_threshold",
+            "must be positive",
+        )?;
+        check(
+            self.script.is_none() || cfg!(feature = "scripting"),
+            "script",
+            "needs the scripting feature (cargo build --features scripting)",
+        )?;
+        for (i, species) in self.species.iter().enumerate() {
+            let name = &species.name;
+            check(
+                !name.is_empty() && self.species[..i].iter().all(|other| &other.name != name),
+                "species",
+                &format!("species {i}: the name must be unique and not empty"),
+            )?;
+            let worm_size = species.worm_size.unwrap_or(self.worm_size);
+            check(
+                worm_size >= 1 && worm_size * 2 <= self.max_size,
+                "species",
+                &format!("{name}: worm_size must be between 1 and half of max_size"),
+            )?;
+            check(
+                species
+                    .part_size
+                    .is_none_or(|size| size.is_finite() && size > 0.),
+                "species",
+                &format!("{name}: part_size must be positive"),
+            )?;
+            check(
+                species
+                    .vision_range
+                    .is_none_or(|range| range > 0. && range <= 360.),
+                "species",
+                &format!("{name}: vision_range must be in the range ]0, 360] (degrees)"),
+            )?;
+            check(
+                species
+                    .vision_distance
+                    .is_none_or(|distance| distance.is_finite() && distance > 0.),
+                "species",
+                &format!("{name}: vision_distance must be positive"),
+            )?;
+        }
+        [
+            ("energy_per_part", self.energy_per_part),
+            ("energy_per_move", self.energy_per_move),
+            ("energy_per_reward", self.energy_per_reward),
+            ("energy_growth_cost", self.energy_growth_cost),
+            ("energy_split_cost", self.energy_split_cost),
+        ]
+        .into_iter()
+        .try_for_each(|(field, value)| {
+            check(
+                value.is_finite() && value >= 0.,
+                field,
+                "must not be negative",
+            )
+        })
+    }
 
-        Ok(Self {
-            n_worms: get_int_attr("n_worms")? as _,
-            n_rewards: get_int_attr("n_rewards")? as _,
-            scene_params: SceneParameters {
-                worm_size: get_int_attr("worm_size")? as _,
-                body_size: get_float_attr("part_size")? as _,
-                starvation: get_int_attr("starvation")? as _,
-                expiration: get_int_attr("expiration")? as _,
+    /// Registers the behavior of the script in the scene, if there is one
+    pub fn register_script(&self, scene: &mut Scene) -> Result<(), ConfigError> {
+        #[cfg(feature = "scripting")]
+        if let Some(path) = &self.script {
+            scene.register_behavior(crate::scripting::ScriptBehavior::from_file(path)?);
+        }
+        #[cfg(not(feature = "scripting"))]
+        let _ = scene;
+        Ok(())
+    }
+
+    /// Parameters of the scene described by the configuration
+    pub fn scene_params(&self) -> SceneParameters {
+        let vision_range: radians::Angle<f32, radians::Degrees> =
+            radians::Angle::new(self.vision_range);
+        let max_turn: radians::Angle<f32, radians::Degrees> =
+            radians::Angle::new(self.max_turn_rate);
+        let gradient_direction: radians::Angle<f32, radians::Degrees> =
+            radians::Angle::new(self.gradient_direction);
+        let predator_vision_range: radians::Angle<f32, radians::Degrees> =
+            radians::Angle::new(self.predator_vision_range);
+        SceneParameters {
+            worm_size: self.worm_size,
+            body_size: self.body_size,
+            starvation: self.starvation,
+            expiration: self.expiration,
+            seed: self.seed,
+            stats: WormStats {
+                vision_range: vision_range.rad(),
+                vision_distance: self.vision_distance,
             },
-            interval: get_int_attr("milisec")? as _,
-        })
+            max_size: self.max_size,
+            n_directions: self.n_directions,
+            reward_speed: self.reward_speed.unwrap_or(self.body_size / 4.),
+            boundary: self.boundary,
+            movement: match self.movement {
+                MovementKind::Discrete => MovementModel::Discrete,
+                MovementKind::Continuous => MovementModel::Continuous {
+                    max_turn: max_turn.rad(),
+                    n_samples: self.heading_samples,
+                },
+            },
+            mutation: self.mutation,
+            metabolism: match self.metabolism {
+                MetabolismKind::Counter => Metabolism::Counter,
+                MetabolismKind::Energy => Metabolism::Energy(EnergyParameters {
+                    initial: self.energy_initial,
+                    per_part: self.energy_per_part,
+                    per_move: self.energy_per_move,
+                    per_reward: self.energy_per_reward,
+                    growth_cost: self.energy_growth_cost,
+                    split_cost: self.energy_split_cost,
+                }),
+            },
+            reward_types: self.reward_types,
+            target_selection: self.target_selection,
+            spawning: SpawnParameters {
+                pattern: match self.spawn_pattern {
+                    SpawnKind::Uniform => SpawnPattern::Uniform,
+                    SpawnKind::Patches => SpawnPattern::Patches {
+                        count: self.patch_count,
+                        radius: self.patch_radius,
+                    },
+                    SpawnKind::Gradient => SpawnPattern::Gradient {
+                        direction: gradient_direction.rad(),
+                        min_density: self.gradient_min_density,
+                    },
+                },
+                seasons: self.season_period.map(|period| Seasons {
+                    period,
+                    amplitude: self.season_amplitude,
+                }),
+                regrowth: self.regrowth_rate,
+            },
+            obstacles: ObstacleMap {
+                obstacles: self
+                    .obstacles
+                    .iter()
+                    .chain(&self.map_obstacles)
+                    .copied()
+                    .collect(),
+                block_vision: self.block_vision,
+            },
+            planning: PlanningParameters {
+                alive: self.alive_planner,
+                chasing: self.chasing_planner,
+                cell_size: self.planner_cell_size.unwrap_or(self.body_size * 2.),
+                budget: self.planner_budget,
+            },
+            predators: PredatorParameters {
+                count: self.n_predators,
+                speed: self.predator_speed.unwrap_or(self.body_size * 2.),
+                stats: WormStats {
+                    vision_range: predator_vision_range.rad(),
+                    vision_distance: self.predator_vision_distance,
+                },
+                starvation: self.predator_starvation,
+                bite: self.predator_bite,
+                reproduction: self.predator_reproduction,
+                flee_distance: self.flee_distance,
+            },
+            scent: self.scent_deposit.map(|deposit| ScentParameters {
+                deposit,
+                meal_deposit: self.scent_meal_deposit,
+                diffusion: self.scent_diffusion,
+                evaporation: self.scent_evaporation,
+                cell_size: self.scent_cell_size.unwrap_or(self.body_size * 4.),
+                threshold: self.scent_threshold,
+            }),
+            species: self
+                .species
+                .iter()
+                .map(|species| {
+                    let vision_range: radians::Angle<f32, radians::Degrees> =
+                        radians::Angle::new(species.vision_range.unwrap_or(self.vision_range));
+                    Species {
+                        name: species.name.clone(),
+                        count: species.count,
+                        worm_size: species.worm_size.unwrap_or(self.worm_size),
+                        body_size: species.part_size.unwrap_or(self.body_size),
+                        starvation: species.starvation.unwrap_or(self.starvation),
+                        stats: WormStats {
+                            vision_range: vision_range.rad(),
+                            vision_distance: species
+                                .vision_distance
+                                .unwrap_or(self.vision_distance),
+                        },
+                        controller: species.controller.unwrap_or(self.controller),
+                        head_color: species.head_color.unwrap_or(Species::HEAD_COLOR),
+                        body_color: species.body_color.unwrap_or(Species::BODY_COLOR),
+                    }
+                })
+                .collect(),
+            merge_rule: self.merge_rule,
+            controller: self.controller,
+            update: self.update,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConfigError, SimConfig};
+    use crate::{
+        geometry::BoundaryMode,
+        movement::MovementModel,
+        neural::Controller,
+        planning::Planner,
+        spawning::SpawnPattern,
+        species::{MergeRule, Species},
+    };
+
+    #[test]
+    fn defaults() {
+        let config = SimConfig::parse(r#"{"n_worms": 100, "part_size": 2.0}"#).unwrap();
+        assert_eq!(config.n_worms, 100);
+        assert_eq!(config.n_rewards, SimConfig::default().n_rewards);
+
+        let params = config.scene_params();
+        assert_eq!(params.body_size, 2.0);
+        assert_eq!(params.reward_speed, 0.5);
+        assert_eq!(params.boundary, BoundaryMode::Bounded);
+
+        assert_eq!(params.movement, MovementModel::Discrete);
+
+        let config = SimConfig::parse(
+            r#"{"boundary": "torus", "movement": "continuous", "max_turn_rate": 90}"#,
+        )
+        .unwrap();
+        let params = config.scene_params();
+        assert_eq!(params.boundary, BoundaryMode::Torus);
+        assert!(matches!(
+            params.movement,
+            MovementModel::Continuous { max_turn, n_samples: 8 }
+                if (max_turn.val() - std::f32::consts::PI / 2.).abs() < 1e-6
+        ));
+        assert!((params.stats.vision_range.val() - 5. * std::f32::consts::PI / 4.).abs() < 1e-6);
+
+        let config =
+            SimConfig::parse(r#"{"spawn_pattern": "patches", "regrowth_rate": 0.5}"#).unwrap();
+        let params = config.scene_params();
+        assert!(matches!(
+            params.spawning.pattern,
+            SpawnPattern::Patches { count: 5, .. }
+        ));
+        assert_eq!(params.spawning.regrowth, Some(0.5));
+        assert_eq!(params.spawning.seasons, None);
+
+        let config = SimConfig::parse(
+            r#"{"obstacles": [{"shape": "rect", "min": {"x": 0, "y": 0}, "max": {"x": 5, "y": 5}}],
+                "block_vision": true}"#,
+        )
+        .unwrap();
+        let params = config.scene_params();
+        assert_eq!(params.obstacles.obstacles.len(), 1);
+        assert!(params.obstacles.block_vision);
+
+        let config = SimConfig::parse(r#"{"chasing_planner": "astar", "part_size": 3.0}"#).unwrap();
+        let params = config.scene_params();
+        assert_eq!(params.planning.alive, Planner::Greedy);
+        assert_eq!(params.planning.chasing, Planner::AStar);
+        assert_eq!(params.planning.cell_size, 6.);
+        assert!(params.scent.is_none());
+
+        let config = SimConfig::parse(r#"{"scent_deposit": 2, "part_size": 3.0}"#).unwrap();
+        let scent = config.scene_params().scent.unwrap();
+        assert_eq!((scent.deposit, scent.cell_size), (2., 12.));
+
+        let config = SimConfig::parse(
+            r#"{"part_size": 3.0, "merge_rule": "same", "species": [
+                {"name": "small", "count": 10},
+                {"name": "large", "count": 5, "part_size": 6.0, "head_color": [1, 2, 3]}]}"#,
+        )
+        .unwrap();
+        let params = config.scene_params();
+        assert_eq!(params.merge_rule, MergeRule::Same);
+        assert_eq!(params.species.len(), 2);
+        assert_eq!(
+            (params.species[0].count, params.species[0].body_size),
+            (10, 3.)
+        );
+        assert_eq!(params.species[0].head_color, Species::HEAD_COLOR);
+        assert_eq!(
+            (params.species[1].count, params.species[1].body_size),
+            (5, 6.)
+        );
+        assert_eq!(params.species[1].head_color, [1, 2, 3]);
+        assert_eq!(params.species_index("large"), Some(1));
+
+        let config = SimConfig::parse(
+            r#"{"controller": "neural",
+                "species": [{"name": "a", "count": 1}, {"name": "b", "count": 1, "controller": "scripted"}]}"#,
+        )
+        .unwrap();
+        let params = config.scene_params();
+        assert_eq!(params.controller, Controller::Neural);
+        assert_eq!(params.species[0].controller, Controller::Neural);
+        assert_eq!(params.species[1].controller, Controller::Scripted);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = SimConfig::parse(r#"{"n_worms": -3}"#).unwrap_err();
+        assert!(matches!(error, ConfigError::Parse { path, .. } if path == "n_worms"));
+
+        let error = SimConfig::parse(r#"{"n_worm": 3}"#).unwrap_err();
+        assert!(matches!(error, ConfigError::Parse { .. }));
+
+        let error = SimConfig::parse(r#"{"map": "no/such/map.json"}"#).unwrap_err();
+        assert!(matches!(error, ConfigError::Io(_)));
+
+        let error = SimConfig::parse(r#"{"boundary": "sphere"}"#).unwrap_err();
+        assert!(matches!(error, ConfigError::Parse { path, .. } if path == "boundary"));
+
+        let error = SimConfig::parse(r#"{"reward_types": {"poison": {"rate": 1}}}"#).unwrap_err();
+        assert!(
+            matches!(error, ConfigError::Parse { path, .. } if path == "reward_types.poison.rate")
+        );
+    }
+
+    #[test]
+    fn validation() {
+        let invalid_field = |content| match SimConfig::parse(content) {
+            Err(ConfigError::Invalid { field, .. }) => field,
+            _ => "",
+        };
+        assert_eq!(invalid_field(r#"{"worm_size": 0}"#), "worm_size");
+        assert_eq!(
+            invalid_field(r#"{"worm_size": 9, "max_size": 16}"#),
+            "worm_size"
+        );
+        assert_eq!(invalid_field(r#"{"width": 0}"#), "width");
+        assert_eq!(invalid_field(r#"{"n_directions": 7}"#), "n_directions");
+        assert_eq!(invalid_field(r#"{"part_size": -1.0}"#), "part_size");
+        assert_eq!(invalid_field(r#"{"max_turn_rate": 0}"#), "max_turn_rate");
+        assert_eq!(
+            invalid_field(r#"{"heading_samples": 0}"#),
+            "heading_samples"
+        );
+        assert_eq!(invalid_field(r#"{"mutation": -0.1}"#), "mutation");
+        assert_eq!(invalid_field(r#"{"season_period": 0}"#), "season_period");
+        assert_eq!(invalid_field(r#"{"planner_budget": 0}"#), "planner_budget");
+        assert_eq!(invalid_field(r#"{"predator_bite": 0}"#), "predator_bite");
+        assert_eq!(
+            invalid_field(r#"{"species": [{"name": "a", "count": 1}, {"name": "a", "count": 2}]}"#),
+            "species"
+        );
+        assert_eq!(
+            invalid_field(r#"{"species": [{"name": "a", "count": 1, "worm_size": 40}]}"#),
+            "species"
+        );
+        assert_eq!(
+            invalid_field(r#"{"scent_diffusion": 1.5}"#),
+            "scent_diffusion"
+        );
+        assert_eq!(
+            invalid_field(r#"{"predator_vision_range": 400}"#),
+            "predator_vision_range"
+        );
+        assert_eq!(
+            invalid_field(r#"{"planner_cell_size": 0}"#),
+            "planner_cell_size"
+        );
+        assert_eq!(
+            invalid_field(
+                r#"{"obstacles": [{"shape": "circle", "center": {"x": 1, "y": 1}, "radius": 0}]}"#
+            ),
+            "obstacles"
+        );
+        assert_eq!(
+            invalid_field(r#"{"gradient_min_density": 1.5}"#),
+            "gradient_min_density"
+        );
+        assert_eq!(
+            invalid_field(r#"{"energy_per_move": -1.0}"#),
+            "energy_per_move"
+        );
+        assert_eq!(
+            invalid_field(r#"{"reward_types": {"prey": {"spawn_rate": 0}}}"#),
+            "reward_types"
+        );
+        assert_eq!(
+            invalid_field(r#"{"reward_types": {"plant": {"speed": -1}}}"#),
+            "reward_types"
+        );
+        assert!(SimConfig::parse(r#"{"worm_size": 8, "max_size": 16}"#).is_ok());
+        assert!(SimConfig::parse(
+            r#"{"reward_types": {"prey": {"spawn_rate": 0}, "plant": {"spawn_rate": 2}}}"#
+        )
+        .is_ok());
     }
 }
@@ -0,0 +1,136 @@
+use serde::Serialize;
+
+use crate::{behaviors::BehaviorId, composites::RewardKind, geometry::Point, lineage::WormId};
+
+/// Events happening during a tick of the simulation
+/// (worms are identified by their index in the scene, which a later worm can take over,
+/// and by their id in the lineage, which stays unique; positions are the worm heads)
+#[derive(Clone, Copy, Debug, Serialize)]
+pub enum SimEvent {
+    /// A worm ate a reward (and grew)
+    RewardEaten {
+        tick: u64,
+        worm_id: usize,
+        worm: WormId,
+        reward_id: usize,
+        kind: RewardKind,
+        position: Point,
+    },
+    /// A worm went too long without eating: it starts chasing other worms
+    Starved {
+        tick: u64,
+        worm_id: usize,
+        worm: WormId,
+        position: Point,
+    },
+    /// A full worm split, creating the offspring worm
+    Split {
+        tick: u64,
+        worm_id: usize,
+        worm: WormId,
+        offspring_id: usize,
+        offspring: WormId,
+        position: Point,
+    },
+    /// A chasing worm reached the tail of the target worm, taking its parts
+    /// (the worm is the new one born from the merge, see Lineage)
+    Merged {
+        tick: u64,
+        worm_id: usize,
+        worm: WormId,
+        target_id: usize,
+        target: WormId,
+        position: Point,
+    },
+    /// A worm could not move anymore
+    Died {
+        tick: u64,
+        worm_id: usize,
+        worm: WormId,
+        position: Point,
+    },
+    /// A predator bit parts off the tail of a worm (the position is the one of the predator)
+    Bitten {
+        tick: u64,
+        worm_id: usize,
+        worm: WormId,
+        predator_id: usize,
+        parts: usize,
+        position: Point,
+    },
+    /// A worm left the scene (expired after dying, fully merged into another one or eaten)
+    Removed {
+        tick: u64,
+        worm_id: usize,
+        worm: WormId,
+        position: Point,
+    },
+    /// A registered behavior ran into an error while moving the worm (only the first error of
+    /// each behavior is reported, see Behavior::error): the built-in rules replace the failing part
+    BehaviorFailed {
+        tick: u64,
+        worm_id: usize,
+        worm: WormId,
+        behavior: BehaviorId,
+        position: Point,
+    },
+}
+
+impl SimEvent {
+    pub fn tick(&self) -> u64 {
+        match *self {
+            SimEvent::RewardEaten { tick, .. }
+            | SimEvent::Starved { tick, .. }
+            | SimEvent::Split { tick, .. }
+            | SimEvent::Merged { tick, .. }
+            | SimEvent::Died { tick, .. }
+            | SimEvent::Bitten { tick, .. }
+            | SimEvent::Removed { tick, .. }
+            | SimEvent::BehaviorFailed { tick, .. } => tick,
+        }
+    }
+
+    /// Index of the worm originating the event
+    pub fn worm_id(&self) -> usize {
+        match *self {
+            SimEvent::RewardEaten { worm_id, .. }
+            | SimEvent::Starved { worm_id, .. }
+            | SimEvent::Split { worm_id, .. }
+            | SimEvent::Merged { worm_id, .. }
+            | SimEvent::Died { worm_id, .. }
+            | SimEvent::Bitten { worm_id, .. }
+            | SimEvent::Removed { worm_id, .. }
+            | SimEvent::BehaviorFailed { worm_id, .. } => worm_id,
+        }
+    }
+
+    /// Lineage id of the worm originating the event
+    pub fn worm(&self) -> WormId {
+        match *self {
+            SimEvent::RewardEaten { worm, .. }
+            | SimEvent::Starved { worm, .. }
+            | SimEvent::Split { worm, .. }
+            | SimEvent::Merged { worm, .. }
+            | SimEvent::Died { worm, .. }
+            | SimEvent::Bitten { worm, .. }
+            | SimEvent::Removed { worm, .. }
+            | SimEvent::BehaviorFailed { worm, .. } => worm,
+        }
+    }
+
+    pub fn position(&self) -> Point {
+        match *self {
+            SimEvent::RewardEaten { position, .. }
+            | SimEvent::Starved { position, .. }
+            | SimEvent::Split { position, .. }
+            | SimEvent::Merged { position, .. }
+            | SimEvent::Died { position, .. }
+            | SimEvent::Bitten { position, .. }
+            | SimEvent::Removed { position, .. }
+            | SimEvent::BehaviorFailed { position, .. } => position,
+        }
+    }
+}
+
+/// Callback receiving the events of the simulation, registered with Scene::add_observer
+pub type Observer = Box<dyn FnMut(&SimEvent) + Send>;
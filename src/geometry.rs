@@ -1,6 +1,6 @@
-use lazy_static::lazy_static;
 use radians::{self, Radians};
-use rand::{Rng, RngCore};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::{
     f32::consts::PI,
@@ -9,15 +9,16 @@ use std::{
 
 pub type Angle = radians::Angle<f32, Radians>;
 
-/// Number of possible movement directions (North, South, etc)
-const N_DIRECTIONS: u8 = 8;
+/// Random number generator used by the simulation (seedable, so that runs can be reproduced)
+pub type SimRng = rand_pcg::Pcg64Mcg;
 
-lazy_static! {
-    /// The arc covered by a direction (eg: 4 directions = 90°)
-    static ref ARC_RANGE: Angle = Angle::new(2. * PI / N_DIRECTIONS as f32);
-}
+/// Default number of possible movement directions (North, South, etc)
+pub const N_DIRECTIONS: u8 = 8;
+
+/// Tolerance used when comparing angles (absorbs f32 rounding at the edges of a range)
+const ANGLE_TOLERANCE: f32 = 1e-6;
 
-#[derive(Default, PartialEq, Clone, Copy)]
+#[derive(Debug, Default, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub struct Point {
     pub x: f32,
     pub y: f32,
@@ -50,8 +51,7 @@ impl fmt::Display for Point {
 }
 
 impl Point {
-    pub fn rand(xlimit: usize, ylimit: usize) -> Self {
-        let rng = &mut rand::thread_rng();
+    pub fn rand(rng: &mut SimRng, xlimit: usize, ylimit: usize) -> Self {
         Self {
             x: rng.gen_range(0..=xlimit) as f32,
             y: rng.gen_range(0..=ylimit) as f32,
@@ -73,7 +73,12 @@ impl Point {
 
     // Create a copy of the point at a given direction and distance
     pub fn copy(&self, direction: Direction, distance: f32) -> Self {
-        self.add(direction.point().scale(distance))
+        self.project(direction.to_radians(), distance)
+    }
+
+    // Create a copy of the point at a given angle and distance
+    pub fn project(&self, angle: Angle, distance: f32) -> Self {
+        self.add(Point::unit().rotate(angle).scale(distance))
     }
 
     pub fn angle(&self, other: Self) -> Angle {
@@ -96,9 +101,18 @@ impl Point {
     }
 }
 
-#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+/// A movement direction: one of the n_directions splitting a full turn
+/// (i.e value * arc, with arc = 360° / n_directions)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Direction {
     value: i8,
+    n_directions: u8,
+}
+
+impl Default for Direction {
+    fn default() -> Self {
+        Self::new(0)
+    }
 }
 
 impl Add<Direction> for Direction {
@@ -106,6 +120,7 @@ impl Add<Direction> for Direction {
     fn add(self, rhs: Self) -> Self::Output {
         Self {
             value: self.value + rhs.value,
+            n_directions: self.n_directions,
         }
     }
 }
@@ -117,26 +132,42 @@ impl fmt::Display for Direction {
 }
 
 impl Direction {
-    pub fn rand() -> Self {
+    pub fn rand(rng: &mut SimRng, n_directions: u8) -> Self {
         Self {
-            value: (rand::random::<u32>() % N_DIRECTIONS as u32) as _,
+            value: rng.gen_range(0..n_directions) as _,
+            n_directions,
         }
     }
 
+    /// Creates a direction among the default number of directions
     pub fn new(value: i8) -> Self {
-        Self { value }
+        Self {
+            value,
+            n_directions: N_DIRECTIONS,
+        }
     }
 
+    /// Closest direction to the angle, among the default number of directions
     pub fn from_radians(angle: Angle) -> Self {
+        Self::quantize(angle, N_DIRECTIONS)
+    }
+
+    /// Closest direction to the angle, among the given number of directions
+    pub fn quantize(angle: Angle, n_directions: u8) -> Self {
+        let arc = arc_range(n_directions);
+        let value = ((angle + (arc / 2.)).val() / arc.val()).floor() as i64;
+        // normalize the value to a single turn (-N/2, N/2], so that it fits the i8
+        let half_turn = (n_directions / 2) as i64;
         Self {
-            value: ((angle + (*ARC_RANGE / 2.)).val() / ARC_RANGE.val()).floor() as _,
+            value: ((value + half_turn - 1).rem_euclid(n_directions as i64) - half_turn + 1) as _,
+            n_directions,
         }
     }
 
     /// Checks if the destination is at this direction from the origin, with a range tolerance
     /// i.e the direction 'connects' the origin to the destination
     pub fn connect(&self, origin: Point, destination: Point, range: Angle) -> bool {
-        (self.to_radians().wrap() - origin.angle(destination)).mag() <= range / 2.
+        in_arc(self.to_radians(), origin.angle(destination), range)
     }
 
     pub fn point(&self) -> Point {
@@ -145,12 +176,175 @@ impl Direction {
 
     pub fn opposite(&self) -> Self {
         Self {
-            value: (((N_DIRECTIONS / 2) + (self.value % N_DIRECTIONS as i8) as u8) % N_DIRECTIONS) as _,
+            value: (self.value + (self.n_directions / 2) as i8).rem_euclid(self.n_directions as i8),
+            n_directions: self.n_directions,
         }
     }
 
     pub fn to_radians(&self) -> Angle {
-        *ARC_RANGE * self.value as f32
+        arc_range(self.n_directions) * self.value as f32
+    }
+}
+
+/// Checks if the angle is within the range (the arc width) centered on the given angle
+pub fn in_arc(center: Angle, angle: Angle, range: Angle) -> bool {
+    angle_between(center, angle) <= range.val() / 2. + ANGLE_TOLERANCE
+}
+
+/// Magnitude of the smallest turn from one angle to the other, in [0, PI]
+pub fn angle_between(from: Angle, to: Angle) -> f32 {
+    (to - from).wrap().mag().val()
+}
+
+/// The arc covered by a direction (eg: 4 directions = 90°)
+fn arc_range(n_directions: u8) -> Angle {
+    Angle::new(2. * PI / n_directions as f32)
+}
+
+/// Behavior of the borders of the scene
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BoundaryMode {
+    /// Positions outside the area are not allowed
+    #[default]
+    Bounded,
+    /// Opposite borders are connected: positions and distances wrap around
+    Torus,
+    /// Positions crossing a border are mirrored back into the area
+    Reflect,
+}
+
+/// The area of a scene, from (0, 0) to (width, height), along with the behavior of its borders
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Area {
+    pub width: usize,
+    pub height: usize,
+    pub boundary: BoundaryMode,
+}
+
+impl Area {
+    pub fn new(width: usize, height: usize, boundary: BoundaryMode) -> Self {
+        Self {
+            width,
+            height,
+            boundary,
+        }
+    }
+
+    pub fn rand_point(&self, rng: &mut SimRng) -> Point {
+        Point::rand(rng, self.width, self.height)
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        (0. ..=self.width as f32).contains(&point.x)
+            && (0. ..=self.height as f32).contains(&point.y)
+    }
+
+    /// Position taken by a movement ending at the given point, if the borders allow it
+    pub fn place(&self, point: Point) -> Option<Point> {
+        match self.boundary {
+            BoundaryMode::Bounded => self.contains(point).then_some(point),
+            BoundaryMode::Torus => Some(self.wrap(point)),
+            BoundaryMode::Reflect => Some(Point {
+                x: reflect(point.x, self.width as f32),
+                y: reflect(point.y, self.height as f32),
+            }),
+        }
+    }
+
+    /// The point brought inside the area, when the borders wrap around (unchanged otherwise)
+    pub fn wrap(&self, point: Point) -> Point {
+        match self.boundary {
+            BoundaryMode::Torus => Point {
+                x: wrap_value(point.x, self.width as f32),
+                y: wrap_value(point.y, self.height as f32),
+            },
+            BoundaryMode::Bounded | BoundaryMode::Reflect => point,
+        }
+    }
+
+    /// Shortest displacement going from one point to the other (possibly across the borders)
+    pub fn offset(&self, from: Point, to: Point) -> Point {
+        let diff = to - from;
+        match self.boundary {
+            BoundaryMode::Torus => Point {
+                x: wrap_offset(diff.x, self.width as f32),
+                y: wrap_offset(diff.y, self.height as f32),
+            },
+            BoundaryMode::Bounded | BoundaryMode::Reflect => diff,
+        }
+    }
+
+    pub fn distance(&self, from: Point, to: Point) -> f32 {
+        let diff = self.offset(from, to);
+        f32::hypot(diff.x, diff.y)
+    }
+
+    /// Shortest distance from the point to the segment going from one point to the other
+    pub fn segment_distance(&self, point: Point, from: Point, to: Point) -> f32 {
+        let segment = self.offset(from, to);
+        let diff = self.offset(from, point);
+        let length = segment.x * segment.x + segment.y * segment.y;
+        let along = if length > 0. {
+            ((diff.x * segment.x + diff.y * segment.y) / length).clamp(0., 1.)
+        } else {
+            0.
+        };
+        let closest = diff - segment.scale(along);
+        f32::hypot(closest.x, closest.y)
+    }
+
+    /// Angle of the shortest displacement from one point to the other
+    pub fn angle(&self, from: Point, to: Point) -> Angle {
+        let diff = self.offset(from, to);
+        Angle::new(f32::atan2(diff.y, diff.x))
+    }
+}
+
+/// Brings a coordinate into [0, limit[ (an empty dimension leaves it unchanged)
+fn wrap_value(value: f32, limit: f32) -> f32 {
+    if limit > 0. {
+        value.rem_euclid(limit)
+    } else {
+        value
+    }
+}
+
+/// Brings a coordinate difference into [-limit / 2, limit / 2]
+fn wrap_offset(diff: f32, limit: f32) -> f32 {
+    let wrapped = wrap_value(diff, limit);
+    if wrapped > limit / 2. {
+        wrapped - limit
+    } else {
+        wrapped
+    }
+}
+
+/// Mirrors a coordinate on the borders at 0 and limit
+/// (clamped, in case it is still outside after being mirrored)
+fn reflect(value: f32, limit: f32) -> f32 {
+    let mirrored = if value < 0. {
+        -value
+    } else if value > limit {
+        2. * limit - value
+    } else {
+        value
+    };
+    mirrored.clamp(0., limit.max(0.))
+}
+
+/// (De)serialization of angles as their value in radians
+/// to be used with #[serde(with = "angle_serde")]
+pub mod angle_serde {
+    use super::Angle;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(angle: &Angle, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f32(angle.val())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Angle, D::Error> {
+        f32::deserialize(deserializer).map(Angle::new)
     }
 }
 
@@ -163,9 +357,8 @@ pub struct Rotator {
 }
 
 impl Rotator {
-    pub fn new(direction: Direction) -> Self {
-        let rng = &mut rand::thread_rng();
-        let rotation = if rng.next_u64() % 2 == 0 {
+    pub fn new(direction: Direction, rng: &mut SimRng) -> Self {
+        let rotation = if rng.gen_bool(0.5) {
             Rotation::Clockwise
         } else {
             Rotation::CounterClockwise
@@ -182,7 +375,7 @@ impl Iterator for Rotator {
     type Item = Direction;
 
     fn next(&mut self) -> Option<Direction> {
-        if self.times == N_DIRECTIONS {
+        if self.times == self.direction.n_directions {
             return None;
         }
         self.times += 1;
@@ -216,7 +409,7 @@ mod tests {
 
     use radians::{Angle, Degrees};
 
-    use crate::geometry::{rotate, Direction, Point, Rotation, Rotator};
+    use crate::geometry::{rotate, Area, BoundaryMode, Direction, Point, Rotation, Rotator};
 
     #[test]
     fn connect() {
@@ -309,4 +502,28 @@ mod tests {
         assert_eq!(rotator.next(), Some(Direction::new(-4)));
         assert_eq!(rotator.next(), None);
     }
+
+    #[test]
+    fn boundaries() {
+        let point = |x, y| Point { x, y };
+        let bounded = Area::new(100, 50, BoundaryMode::Bounded);
+        assert_eq!(bounded.place(point(10., 50.)), Some(point(10., 50.)));
+        assert_eq!(bounded.place(point(-1., 10.)), None);
+        assert_eq!(bounded.place(point(10., 51.)), None);
+        assert_eq!(bounded.distance(point(5., 0.), point(95., 0.)), 90.);
+
+        let torus = Area::new(100, 50, BoundaryMode::Torus);
+        assert_eq!(torus.place(point(-5., 55.)), Some(point(95., 5.)));
+        assert_eq!(
+            torus.offset(point(5., 45.), point(95., 5.)),
+            point(-10., 10.)
+        );
+        assert_eq!(torus.distance(point(5., 0.), point(95., 0.)), 10.);
+        assert_eq!(torus.angle(point(5., 0.), point(95., 0.)).deg().val(), 180.);
+
+        let reflect = Area::new(100, 50, BoundaryMode::Reflect);
+        assert_eq!(reflect.place(point(-5., 55.)), Some(point(5., 45.)));
+        assert_eq!(reflect.place(point(250., 10.)), Some(point(0., 10.)));
+        assert_eq!(reflect.distance(point(5., 0.), point(95., 0.)), 90.);
+    }
 }
@@ -1,26 +1,67 @@
 use crate::{
-    composites::{WormBehavior, WormBody},
+    bodies::WormBody,
+    cli::ConfigArgs,
+    composites::{Reward, RewardTypes, WormBehavior},
     config::SimConfig,
-    geometry::Point,
+    geometry::{Area, BoundaryMode, Point},
+    obstacles::Obstacle,
+    predators::Predator,
     scene::Scene,
+    scent::ScentField,
+    species::Species,
 };
 use eframe::{
     egui::{self, Context},
-    epaint::{mutex::Mutex, vec2, CircleShape, Color32, Pos2},
+    epaint::{mutex::Mutex, vec2, CircleShape, Color32, Pos2, Stroke},
     CreationContext,
 };
 use std::{ops::DerefMut, sync::Arc};
 
+/// File used by the Save and Load buttons
+const SNAPSHOT_FILE: &str = "./snapshot.json";
+
+/// State of the simulation when the interface opens
+#[derive(Clone, Copy, Default, clap::ValueEnum)]
+pub enum StartMode {
+    /// No simulation until Reset or Step is pressed
+    #[default]
+    Empty,
+    /// A new simulation is created, waiting for Continue
+    Paused,
+    /// A new simulation is created and started
+    Running,
+}
+
 pub struct SimInterface {
+    config_args: ConfigArgs,
+    /// Start mode to apply on the first frame (when the size of the scene is known)
+    pending_start: Option<StartMode>,
     config: Option<SimConfig>,
     scene: Arc<Mutex<Option<Scene>>>,
     tick_interval: Arc<Mutex<u64>>,
     width: f32,
     height: f32,
+    /// If the scent field is drawn under the worms
+    show_scent: bool,
+    /// Error raised when loading the script of the configuration, shown in the control bar
+    script_error: Option<String>,
 }
 
 impl eframe::App for SimInterface {
     fn update(&mut self, ctx: &egui::Context, _: &mut eframe::Frame) {
+        // errors of the script, when it is loaded or while it runs
+        let errors = self
+            .script_error
+            .iter()
+            .cloned()
+            .chain(self.scene.lock().iter().flat_map(|scene_sim| {
+                scene_sim
+                    .behavior_errors()
+                    .map(|(name, error)| format!("Error in the behavior {name}: {error}"))
+                    .collect::<Vec<_>>()
+            }))
+            .collect::<Vec<_>>();
+
         // ----------- create the control bar -------------
         egui::TopBottomPanel::bottom("Control")
             .resizable(false)
@@ -55,6 +96,16 @@ impl eframe::App for SimInterface {
                     {
                         self.start(ctx.clone());
                     }
+                    if ui.button("Save".to_owned()).clicked() {
+                        self.save_simulation();
+                    }
+                    if ui.button("Load".to_owned()).clicked() {
+                        self.load_simulation();
+                    }
+                    ui.checkbox(&mut self.show_scent, "Scent");
+                    for error in &errors {
+                        ui.colored_label(Color32::RED, error);
+                    }
                 })
             });
 
@@ -71,43 +122,64 @@ impl eframe::App for SimInterface {
         if let Some(simulation) = self.scene.lock().as_mut() {
             simulation.resize(self.width as usize, self.height as usize);
         }
+
+        match self.pending_start.take() {
+            Some(StartMode::Paused) => self.reset_simulation(),
+            Some(StartMode::Running) => {
+                self.reset_simulation();
+                self.start(ctx.clone());
+            }
+            Some(StartMode::Empty) | None => (),
+        }
     }
 }
 
 impl SimInterface {
-    pub fn new(_: &CreationContext) -> Self {
+    pub fn new(_: &CreationContext, config_args: ConfigArgs, start_mode: StartMode) -> Self {
         Self {
+            config_args,
+            pending_start: Some(start_mode),
             config: None,
             scene: Arc::new(Mutex::new(None)),
             tick_interval: Arc::new(Mutex::new(0)),
             width: f32::default(),
             height: f32::default(),
+            show_scent: false,
+            script_error: None,
         }
     }
 
     pub fn from(scene: Scene) -> Self {
         Self {
+            config_args: ConfigArgs::default(),
+            pending_start: None,
             config: None,
             scene: Arc::new(Mutex::new(Some(scene))),
             tick_interval: Arc::new(Mutex::new(0)),
             width: f32::default(),
             height: f32::default(),
+            show_scent: false,
+            script_error: None,
         }
     }
 
     fn reset_simulation(&mut self) {
-        // Read the default configuration
-        let new_config = SimConfig::read_default();
+        // Read the configuration (again, so that changes in the file are applied)
+        let new_config = self.config_args.load_or_default();
 
         // Build the new_scene using the config read
         let mut new_scene = Scene::new(
             self.width as usize,
             self.height as usize,
-            new_config.scene_params.clone(),
+            new_config.scene_params(),
             new_config.n_worms,
             new_config.n_rewards,
         );
-        for _ in 0..50 {
+        self.script_error = new_config
+            .register_script(&mut new_scene)
+            .err()
+            .map(|error| format!("Error loading the script: {error}"));
+        for _ in 0..new_config.warmup_ticks {
             new_scene.execute();
         }
 
@@ -116,11 +188,40 @@ impl SimInterface {
         self.scene.lock().replace(new_scene);
     }
 
+    fn save_simulation(&self) {
+        if let Some(Err(error)) = self
+            .scene
+            .lock()
+            .as_ref()
+            .map(|scene_sim| scene_sim.save(SNAPSHOT_FILE))
+        {
+            println!("Error saving the simulation to {SNAPSHOT_FILE}:\n{error}");
+        }
+    }
+
+    fn load_simulation(&mut self) {
+        match Scene::load(SNAPSHOT_FILE) {
+            Ok(mut loaded_scene) => {
+                // a configuration is still needed for the tick interval (and the script)
+                if self.config.is_none() {
+                    self.config = Some(self.config_args.load_or_default());
+                }
+                self.script_error = self
+                    .config
+                    .as_ref()
+                    .and_then(|config| config.register_script(&mut loaded_scene).err())
+                    .map(|error| format!("Error loading the script: {error}"));
+                self.scene.lock().replace(loaded_scene);
+            }
+            Err(error) => println!("Error loading the simulation from {SNAPSHOT_FILE}:\n{error}"),
+        }
+    }
+
     fn start(&self, ctx: Context) {
         // Set the time interval for each simulatiom tick, if there is a config object set
-        self.config
-            .as_ref()
-            .map(|config| *self.tick_interval.lock().deref_mut() = config.interval);
+        if let Some(config) = self.config.as_ref() {
+            *self.tick_interval.lock().deref_mut() = config.interval;
+        }
 
         // Start the thread for simulation
         let scene = Arc::clone(&self.scene);
@@ -141,19 +242,35 @@ impl SimInterface {
     }
 
     pub fn get_shapes(&self, reference: Pos2) -> Vec<egui::Shape> {
-        let size = self
-            .config
-            .as_ref()
-            .map(|config| config.scene_params.body_size)
-            .unwrap_or_default();
         self.scene
             .lock()
             .as_ref()
             .map(|scene_sim| {
-                scene_sim
-                    .worms()
-                    .flat_map(|(behavior, body)| build_worm(body, behavior, size, reference))
-                    .chain(build_rewards(scene_sim.rewards(), size / 2., reference))
+                let size = scene_sim.params().body_size;
+                let area = scene_sim.area();
+                let scent = scene_sim.scent().filter(|_| self.show_scent);
+                build_scent(scent, reference)
+                    .chain(build_obstacles(
+                        &scene_sim.params().obstacles.obstacles,
+                        size,
+                        reference,
+                    ))
+                    .chain(scene_sim.worms().flat_map(|(behavior, body, species)| {
+                        build_worm(body, behavior, species, area, reference)
+                    }))
+                    .chain(build_rewards(
+                        scene_sim.rewards(),
+                        &scene_sim.params().reward_types,
+                        size / 2.,
+                        area,
+                        reference,
+                    ))
+                    .chain(build_predators(
+                        scene_sim.predators(),
+                        size * 2.,
+                        area,
+                        reference,
+                    ))
                     .collect()
             })
             .unwrap_or_default()
@@ -161,53 +278,166 @@ impl SimInterface {
 }
 
 fn tick_simulation(scene: &Mutex<Option<Scene>>, active_timer: &Mutex<u64>) -> Option<u64> {
-    scene
-        .lock()
-        .as_mut()
-        .map(|scene_sim| {
-            scene_sim.execute();
-            let timer = active_timer.lock().clone();
-            (timer != 0).then_some(timer)
-        })
-        .flatten()
+    scene.lock().as_mut().and_then(|scene_sim| {
+        scene_sim.execute();
+        let timer = *active_timer.lock();
+        (timer != 0).then_some(timer)
+    })
 }
 
 // Return an iterator over the shapes from the body of a
 fn build_worm<'a>(
-    body: &'a WormBody,
+    body: WormBody<'a>,
     behavior: &'a WormBehavior,
-    size: f32,
+    species: &Species,
+    area: Area,
     reference: Pos2,
 ) -> impl Iterator<Item = egui::Shape> + 'a {
+    let size = species.body_size;
     // get the color of the head and body
-    let (head_color, body_color) = match_color(behavior).unwrap_or((Color32::WHITE, Color32::RED));
+    let (head_color, body_color) =
+        match_color(behavior, species).unwrap_or((Color32::WHITE, Color32::RED));
     // create the head
     body.iter()
         .take(1)
-        .map(move |point| {
-            CircleShape::filled(reference + vec2(point.x, point.y), size, head_color).into()
-        })
+        .flat_map(move |point| build_circles(point, size, head_color, area, reference))
         // chain the rest of the body
-        .chain(body.iter().rev().take(body.size().saturating_sub(1)).map(move |point| {
-            CircleShape::filled(reference + vec2(point.x, point.y), size, body_color).into()
-        }))
+        .chain(
+            body.iter()
+                .rev()
+                .take(body.size().saturating_sub(1))
+                .flat_map(move |point| build_circles(point, size, body_color, area, reference)),
+        )
 }
 
-fn build_rewards(
-    points: &[Point],
+// Return an iterator over the shapes of the rewards, colored by kind
+fn build_rewards<'a>(
+    rewards: &'a [Reward],
+    reward_types: &'a RewardTypes,
     size: f32,
+    area: Area,
+    reference: Pos2,
+) -> impl Iterator<Item = egui::Shape> + 'a {
+    rewards.iter().flat_map(move |reward| {
+        let [r, g, b] = reward_types.get(reward.kind).color;
+        build_circles(
+            reward.position,
+            size,
+            Color32::from_rgb(r, g, b),
+            area,
+            reference,
+        )
+    })
+}
+
+// Return an iterator over the shapes of the predators: dark red discs with a white rim
+fn build_predators(
+    predators: &[Predator],
+    size: f32,
+    area: Area,
     reference: Pos2,
 ) -> impl Iterator<Item = egui::Shape> + '_ {
-    let reward_color = Color32::from_rgb(0xF8, 0xFF, 0xE5);
-    points.iter().map(move |point| {
-        CircleShape::filled(reference + vec2(point.x, point.y), size, reward_color).into()
+    let color = Color32::from_rgb(0x8B, 0x0A, 0x1A);
+    let rim = Stroke::new(size / 4., Color32::WHITE);
+    predators.iter().flat_map(move |predator| {
+        build_circles(predator.position, size, color, area, reference).chain(std::iter::once(
+            CircleShape::stroke(
+                reference + vec2(predator.position.x, predator.position.y),
+                size,
+                rim,
+            )
+            .into(),
+        ))
     })
 }
 
-fn match_color(behavior: &WormBehavior) -> Option<(Color32, Color32)> {
-    let moving_head_color = Color32::from_rgb(0x2E, 0xBF, 0xA5);
+// Return an iterator over the shapes of the scent field (if any): a heatmap with the
+// strongest scent in opaque orange
+fn build_scent(
+    scent: Option<&ScentField>,
+    reference: Pos2,
+) -> impl Iterator<Item = egui::Shape> + '_ {
+    let strongest = scent
+        .into_iter()
+        .flat_map(|field| field.cells())
+        .map(|(_, level)| level)
+        .fold(0., f32::max);
+    scent.into_iter().flat_map(move |field| {
+        let cell = vec2(field.cell_size(), field.cell_size());
+        field
+            .cells()
+            // the faintest scent is not drawn
+            .filter(move |&(_, level)| level > strongest / 100.)
+            .map(move |(corner, level)| {
+                let alpha = (255. * level / strongest) as u8;
+                egui::Shape::rect_filled(
+                    egui::Rect::from_min_size(reference + vec2(corner.x, corner.y), cell),
+                    0.,
+                    Color32::from_rgba_unmultiplied(0xFF, 0x8C, 0x00, alpha),
+                )
+            })
+    })
+}
+
+// Return an iterator over the shapes of the static obstacles (walls drawn a part wide)
+fn build_obstacles(
+    obstacles: &[Obstacle],
+    size: f32,
+    reference: Pos2,
+) -> impl Iterator<Item = egui::Shape> + '_ {
+    let color = Color32::from_rgb(0x5C, 0x5C, 0x6E);
+    let pos = move |point: Point| reference + vec2(point.x, point.y);
+    obstacles.iter().map(move |&obstacle| match obstacle {
+        Obstacle::Circle { center, radius } => {
+            CircleShape::filled(pos(center), radius, color).into()
+        }
+        Obstacle::Rect { min, max } => {
+            egui::Shape::rect_filled(egui::Rect::from_min_max(pos(min), pos(max)), 0., color)
+        }
+        Obstacle::Segment { from, to } => {
+            egui::Shape::line_segment([pos(from), pos(to)], Stroke::new(size, color))
+        }
+    })
+}
+
+// Return the circles drawing a point: on a torus, a circle crossing a border
+// is drawn again on the opposite side
+fn build_circles(
+    point: Point,
+    size: f32,
+    color: Color32,
+    area: Area,
+    reference: Pos2,
+) -> impl Iterator<Item = egui::Shape> {
+    let point = area.wrap(point);
+    let wraps = area.boundary == BoundaryMode::Torus;
+    let copies = move |value: f32, limit: f32| {
+        [
+            Some(0.),
+            (wraps && value < size).then_some(limit),
+            (wraps && value > limit - size).then_some(-limit),
+        ]
+        .into_iter()
+        .flatten()
+    };
+    let (width, height) = (area.width as f32, area.height as f32);
+    copies(point.x, width).flat_map(move |dx| {
+        copies(point.y, height).map(move |dy| {
+            CircleShape::filled(reference + vec2(point.x + dx, point.y + dy), size, color).into()
+        })
+    })
+}
+
+// Colors of the head and body of a worm: the ones of its species, when moving
+// (the body of a chasing worm turning blue)
+fn match_color(behavior: &WormBehavior, species: &Species) -> Option<(Color32, Color32)> {
+    let [r, g, b] = species.head_color;
+    let moving_head_color = Color32::from_rgb(r, g, b);
     match behavior {
-        WormBehavior::Alive(_) => Some((moving_head_color, Color32::from_rgb(0x7D, 0xDE, 0x92))),
+        WormBehavior::Alive(_) | WormBehavior::Custom(..) => {
+            let [r, g, b] = species.body_color;
+            Some((moving_head_color, Color32::from_rgb(r, g, b)))
+        }
         WormBehavior::Dead(_) => Some((Color32::GRAY, Color32::from_rgb(0x4E, 0x41, 0x87))),
         WormBehavior::Chasing => Some((moving_head_color, Color32::from_rgb(0x30, 0x83, 0xDC))),
         WormBehavior::Removed => None,
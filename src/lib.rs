@@ -3,4 +3,22 @@ pub mod composites;
 pub mod geometry;
 pub mod movement;
 pub mod config;
-pub mod gui;
\ No newline at end of file
+pub mod cli;
+pub mod gui;
+pub mod metrics;
+pub mod events;
+pub mod spatial;
+pub mod lineage;
+pub mod metabolism;
+pub mod spawning;
+pub mod obstacles;
+pub mod scenario;
+pub mod planning;
+pub mod predators;
+pub mod scent;
+pub mod species;
+pub mod neural;
+pub mod behaviors;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod bodies;
\ No newline at end of file
@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+/// Unique identifier of a worm (never reused, unlike the indexes in the scene)
+pub type WormId = u64;
+
+/// Entry of the lineage log
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LineageRecord {
+    pub id: WormId,
+    /// No parent for the worms created with the scene, one for an offspring of a split,
+    /// two for a merge (the chasing worm first, then the target)
+    pub parents: Vec<WormId>,
+    /// Tick the worm was created at
+    pub born: u64,
+    /// Tick the worm left the scene or was replaced by a merge (None while it lives)
+    pub ended: Option<u64>,
+}
+
+/// Log of all the worms created in a scene, along with their ancestry
+#[derive(Default, Serialize, Deserialize)]
+pub struct Lineage {
+    // the id of a worm is its position in the log
+    records: Vec<LineageRecord>,
+}
+
+impl Lineage {
+    /// Registers a new worm, returning its id
+    pub fn add(&mut self, parents: Vec<WormId>, tick: u64) -> WormId {
+        let id = self.records.len() as WormId;
+        self.records.push(LineageRecord {
+            id,
+            parents,
+            born: tick,
+            ended: None,
+        });
+        id
+    }
+
+    /// Marks the worm as gone (it keeps its place in the log)
+    pub fn end(&mut self, id: WormId, tick: u64) {
+        if let Some(record) = self.records.get_mut(id as usize) {
+            record.ended.get_or_insert(tick);
+        }
+    }
+
+    pub fn get(&self, id: WormId) -> Option<&LineageRecord> {
+        self.records.get(id as usize)
+    }
+
+    pub fn records(&self) -> &[LineageRecord] {
+        &self.records
+    }
+
+    /// Exports the lineage as a tree in the Newick format
+    /// Merged worms are placed under their first parent (the chasing worm), the worms
+    /// created with the scene under a common root, and branch lengths are the ticks
+    /// between the births of a worm and its parent
+    pub fn to_newick(&self) -> String {
+        let mut children = vec![Vec::new(); self.records.len()];
+        let mut roots = Vec::new();
+        for record in &self.records {
+            match record.parents.first() {
+                Some(&parent) => children[parent as usize].push(record.id),
+                None => roots.push(record.id),
+            }
+        }
+
+        // iterative traversal, as long runs give chains too deep for a recursion
+        enum Step {
+            Enter(WormId),
+            Separator,
+            Exit(WormId),
+        }
+        let mut newick = String::from("(");
+        let mut stack = Vec::new();
+        for (i, &root) in roots.iter().enumerate().rev() {
+            stack.push(Step::Enter(root));
+            if i > 0 {
+                stack.push(Step::Separator);
+            }
+        }
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Enter(id) if children[id as usize].is_empty() => {
+                    self.write_node(&mut newick, id)
+                }
+                Step::Enter(id) => {
+                    newick.push('(');
+                    stack.push(Step::Exit(id));
+                    for (i, &child) in children[id as usize].iter().enumerate().rev() {
+                        stack.push(Step::Enter(child));
+                        if i > 0 {
+                            stack.push(Step::Separator);
+                        }
+                    }
+                }
+                Step::Separator => newick.push(','),
+                Step::Exit(id) => {
+                    newick.push(')');
+                    self.write_node(&mut newick, id);
+                }
+            }
+        }
+        newick.push_str(");");
+        newick
+    }
+
+    // Writes the label and branch length of a worm in a Newick tree
+    fn write_node(&self, newick: &mut String, id: WormId) {
+        let record = &self.records[id as usize];
+        let parent_born = record
+            .parents
+            .first()
+            .map_or(record.born, |&parent| self.records[parent as usize].born);
+        write!(newick, "{id}:{}", record.born - parent_born).expect("writing to a String");
+    }
+
+    /// Exports the lineage as a GraphViz (DOT) directed graph
+    /// Each worm is a node labeled with its id and birth tick (dashed edges come from merges)
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph lineage {\n");
+        for record in &self.records {
+            writeln!(
+                dot,
+                "    {} [label=\"{} (t={})\"];",
+                record.id, record.id, record.born
+            )
+            .expect("writing to a String");
+            let style = if record.parents.len() > 1 {
+                " [style=dashed]"
+            } else {
+                ""
+            };
+            for parent in &record.parents {
+                writeln!(dot, "    {parent} -> {}{style};", record.id)
+                    .expect("writing to a String");
+            }
+        }
+        dot.push('}');
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Lineage;
+
+    #[test]
+    fn exports() {
+        let mut lineage = Lineage::default();
+        let first = lineage.add(Vec::new(), 0);
+        let second = lineage.add(Vec::new(), 0);
+        let offspring = lineage.add(vec![first], 10);
+        let merged = lineage.add(vec![second, offspring], 25);
+        lineage.end(second, 25);
+        lineage.end(second, 30);
+
+        assert_eq!(lineage.get(second).unwrap().ended, Some(25));
+        assert_eq!(lineage.get(merged).unwrap().ended, None);
+        assert_eq!(lineage.to_newick(), "((2:10)0:0,(3:25)1:0);");
+        assert_eq!(
+            lineage.to_dot(),
+            "digraph lineage {\n    \
+            0 [label=\"0 (t=0)\"];\n    \
+            1 [label=\"1 (t=0)\"];\n    \
+            2 [label=\"2 (t=10)\"];\n    \
+            0 -> 2;\n    \
+            3 [label=\"3 (t=25)\"];\n    \
+            1 -> 3 [style=dashed];\n    \
+            2 -> 3 [style=dashed];\n\
+            }"
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+use crate::composites::Genome;
+
+/// How the hunger of the worms is modeled
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Metabolism {
+    /// A worm starves after a number of ticks without eating
+    /// (the starvation of its genome divided by its size)
+    #[default]
+    Counter,
+    /// A worm has a reserve of energy, spent by moving and filled by eating:
+    /// it starves when the reserve is empty
+    Energy(EnergyParameters),
+}
+
+impl Metabolism {
+    /// Checks if a worm of the given size went hungry for too long
+    /// (counter: ticks since it last ate, energy: its reserve)
+    pub fn starving(&self, genome: &Genome, size: usize, energy: f32, counter: usize) -> bool {
+        match self {
+            Metabolism::Counter => counter >= genome.starvation / size.max(1),
+            Metabolism::Energy(_) => energy <= 0.,
+        }
+    }
+}
+
+/// Energy gains and costs of the worms, in the energy metabolism
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EnergyParameters {
+    /// Energy of the worms created with the scene
+    pub initial: f32,
+    /// Energy spent in a movement, per part of the body
+    pub per_part: f32,
+    /// Energy spent in a movement, multiplied by the speed of the worm
+    pub per_move: f32,
+    /// Energy delivered by a reward
+    pub per_reward: f32,
+    /// Energy needed to grow a part when eating (the worm does not grow without it)
+    pub growth_cost: f32,
+    /// Energy needed to split (the rest is shared with the offspring by number of parts)
+    pub split_cost: f32,
+}
+
+impl Default for EnergyParameters {
+    fn default() -> Self {
+        Self {
+            initial: 100.,
+            per_part: 0.05,
+            per_move: 0.5,
+            per_reward: 40.,
+            growth_cost: 10.,
+            split_cost: 20.,
+        }
+    }
+}
+
+impl EnergyParameters {
+    /// Energy spent by a worm of the given size and speed in a movement
+    pub fn movement_cost(&self, size: usize, speed: f32) -> f32 {
+        self.per_part * size as f32 + self.per_move * speed
+    }
+}
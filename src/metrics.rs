@@ -0,0 +1,177 @@
+use serde::Serialize;
+
+use crate::{composites::WormBehavior, events::SimEvent, scene::Scene};
+
+/// Population metrics of a scene, taken after a tick
+#[derive(Serialize)]
+pub struct SceneMetrics {
+    pub tick: u64,
+    pub alive: usize,
+    pub chasing: usize,
+    pub dead: usize,
+    pub removed: usize,
+    /// Mean size of the moving (alive or chasing) worms
+    pub mean_body_size: f32,
+    pub rewards_eaten: usize,
+    pub splits: usize,
+    pub merges: usize,
+    /// Number of rewards in the scene (varies with the seasons and the regrowth)
+    pub rewards: usize,
+}
+
+impl SceneMetrics {
+    pub const CSV_HEADER: &'static str =
+        "tick,alive,chasing,dead,removed,mean_body_size,rewards_eaten,splits,merges,rewards";
+
+    pub fn from(scene: &Scene) -> Self {
+        Self::of_worms(scene, |_| true)
+    }
+
+    /// Metrics of the worms of a species (the rewards count is the one of the whole scene)
+    pub fn of_species(scene: &Scene, species: usize) -> Self {
+        Self::of_worms(scene, |worm_id| scene.worm_species()[worm_id] == species)
+    }
+
+    // Metrics of the worms (and their events) accepted by the filter
+    fn of_worms(scene: &Scene, accept: impl Fn(usize) -> bool) -> Self {
+        let (mut alive, mut chasing, mut dead, mut removed) = (0, 0, 0, 0);
+        let mut moving_size = 0;
+        let worms = scene.worms().enumerate();
+        for (_, (behavior, body, _)) in worms.filter(|&(worm_id, _)| accept(worm_id)) {
+            match behavior {
+                // the worms with a registered behavior are alive too
+                WormBehavior::Alive(_) | WormBehavior::Custom(..) => alive += 1,
+                WormBehavior::Chasing => chasing += 1,
+                WormBehavior::Dead(_) => dead += 1,
+                WormBehavior::Removed => removed += 1,
+            }
+            if matches!(
+                behavior,
+                WormBehavior::Alive(_) | WormBehavior::Chasing | WormBehavior::Custom(..)
+            ) {
+                moving_size += body.size();
+            }
+        }
+        let count = |matching: fn(&SimEvent) -> bool| {
+            scene
+                .events()
+                .iter()
+                .filter(|event| matching(event) && accept(event.worm_id()))
+                .count()
+        };
+        Self {
+            tick: scene.tick(),
+            alive,
+            chasing,
+            dead,
+            removed,
+            mean_body_size: moving_size as f32 / (alive + chasing).max(1) as f32,
+            rewards_eaten: count(|event| matches!(event, SimEvent::RewardEaten { .. })),
+            splits: count(|event| matches!(event, SimEvent::Split { .. })),
+            merges: count(|event| matches!(event, SimEvent::Merged { .. })),
+            rewards: scene.rewards().len(),
+        }
+    }
+
+    /// Formats the metrics as a CSV line (following the order of CSV_HEADER)
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{:.3},{},{},{},{}",
+            self.tick,
+            self.alive,
+            self.chasing,
+            self.dead,
+            self.removed,
+            self.mean_body_size,
+            self.rewards_eaten,
+            self.splits,
+            self.merges,
+            self.rewards
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SceneMetrics;
+    use crate::{events::SimEvent, scenario::Scenario, scene::Scene};
+
+    fn metrics_scene() -> Scene {
+        Scene::from_scenario(
+            &Scenario::parse(
+                r#"{
+                    "config": {"width": 300, "height": 300, "part_size": 3.0, "worm_size": 2,
+                               "seed": 1, "species": [
+                               {"name": "red", "count": 0}, {"name": "blue", "count": 0}]},
+                    "worms": [
+                        {"head": {"x": 50, "y": 150}, "size": 3,
+                         "destination": {"x": 290, "y": 150}},
+                        {"head": {"x": 150, "y": 50}, "size": 5, "behavior": "Chasing",
+                         "species": "blue"},
+                        {"head": {"x": 250, "y": 250}, "behavior": {"Dead": 0}, "species": "blue"}
+                    ],
+                    "rewards": [
+                        {"position": {"x": 56, "y": 150}, "kind": "plant", "destination": {"x": 56, "y": 150}},
+                        {"position": {"x": 150, "y": 250}, "kind": "plant", "destination": {"x": 150, "y": 250}}
+                    ]
+                }"#,
+            )
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn scene_metrics() {
+        let mut scene = metrics_scene();
+        let metrics = SceneMetrics::from(&scene);
+        assert_eq!(metrics.tick, 0);
+        assert_eq!(
+            (
+                metrics.alive,
+                metrics.chasing,
+                metrics.dead,
+                metrics.removed
+            ),
+            (1, 1, 1, 0)
+        );
+        // the dead worm does not count in the mean size
+        assert_eq!(metrics.mean_body_size, 4.);
+        assert_eq!(
+            (metrics.rewards_eaten, metrics.splits, metrics.merges),
+            (0, 0, 0)
+        );
+        assert_eq!(metrics.rewards, 2);
+
+        let blue = SceneMetrics::of_species(&scene, 1);
+        assert_eq!((blue.alive, blue.chasing, blue.dead), (0, 1, 1));
+        assert_eq!(blue.mean_body_size, 5.);
+        assert_eq!(blue.rewards, 2);
+
+        // the alive worm eats the plant ahead: the events of the tick are counted
+        scene.execute();
+        let eaten = scene
+            .events()
+            .iter()
+            .filter(|event| matches!(event, SimEvent::RewardEaten { worm_id: 0, .. }))
+            .count();
+        assert_eq!(eaten, 1);
+        let metrics = SceneMetrics::from(&scene);
+        assert_eq!((metrics.tick, metrics.rewards_eaten), (1, 1));
+        assert_eq!(SceneMetrics::of_species(&scene, 0).rewards_eaten, 1);
+        assert_eq!(SceneMetrics::of_species(&scene, 1).rewards_eaten, 0);
+    }
+
+    #[test]
+    fn csv_format() {
+        let metrics = SceneMetrics::from(&metrics_scene());
+        assert_eq!(
+            SceneMetrics::CSV_HEADER,
+            "tick,alive,chasing,dead,removed,mean_body_size,rewards_eaten,splits,merges,rewards"
+        );
+        assert_eq!(metrics.to_csv(), "0,1,1,1,0,4.000,0,0,0,2");
+        assert_eq!(
+            metrics.to_csv().split(',').count(),
+            SceneMetrics::CSV_HEADER.split(',').count()
+        );
+    }
+}
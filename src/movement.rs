@@ -1,29 +1,147 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
 use crate::{
-    composites::{Reward, WormBehavior, WormBody, WormPart, WormStats},
-    geometry::{Point, Rotator},
+    bodies::{BodyStore, WormBody},
+    composites::{Reward, RewardTypes, WormBehavior, WormPart, WormStats},
+    geometry::{
+        angle_between, angle_serde, in_arc, Angle, Area, Direction, Point, Rotator, SimRng,
+    },
+    neural::{Brain, Sensors},
+    obstacles::ObstacleMap,
+    planning::PathPlanner,
+    predators::Predator,
+    scent::ScentField,
+    spatial::SpatialGrid,
+    species::Species,
 };
-use rayon::prelude::*;
+
+/// How the worms choose the heading of their next movement
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum MovementModel {
+    /// One of the n_directions fixed directions, the closest to the destination first
+    #[default]
+    Discrete,
+    /// Any heading, turning at most max_turn per tick from the current heading:
+    /// the closest to the destination is tried first, then n_samples random ones in the turn range
+    /// (the worm does not move when all of them are blocked)
+    Continuous {
+        #[serde(with = "angle_serde")]
+        max_turn: Angle,
+        n_samples: usize,
+    },
+}
+
+/// How an 'Alive' worm chooses among the rewards in sight
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetSelection {
+    /// The closest reward, whatever its kind
+    #[default]
+    Closest,
+    /// The reward with the best value for its distance (the harmful ones are avoided)
+    Value,
+}
 
 // Struct with the data needed to calculate the movement of a worm
 pub struct MovementDetails {
     pub origin: WormPart,
     pub chosen_destination: Point,
     pub stats: WormStats,
-    pub width: usize,
-    pub height: usize,
+    pub n_directions: u8,
+    pub area: Area,
+    pub model: MovementModel,
+    /// Direction of the last movement (None for a worm with a single part)
+    pub heading: Option<Angle>,
 }
 
 impl MovementDetails {
     /// Returns the current chosen destination if it is OUTSIDE vision range
     /// or a randon Point otherwise
-    fn choose_destination(&self) -> Point {
-        (self.origin.distance_to(self.chosen_destination) > self.stats.vision_distance)
-            .then_some(self.chosen_destination)
-            .unwrap_or(Point::rand(self.width, self.height))
+    pub fn choose_destination(&self, rng: &mut SimRng) -> Point {
+        if self.distance_to(self.chosen_destination) > self.stats.vision_distance {
+            self.chosen_destination
+        } else {
+            self.area.rand_point(rng)
+        }
     }
 
-    fn is_inside_area(&self, new_head: WormPart) -> bool {
-        new_head.x <= self.width as f32 && new_head.y <= self.height as f32
+    /// Destination when no target is in sight: up the scent gradient, if some scent
+    /// is detected ahead (in the vision range), or the one of choose_destination otherwise
+    fn wander(&self, scent: Option<&ScentField>, rng: &mut SimRng) -> Point {
+        scent
+            .and_then(|field| {
+                field.strongest(self.area, self.origin, |cell| {
+                    in_arc(
+                        self.facing(),
+                        self.area.angle(self.origin, cell),
+                        self.stats.vision_range,
+                    )
+                })
+            })
+            .unwrap_or_else(|| self.choose_destination(rng))
+    }
+
+    /// Checks if a given target is in range of worm
+    /// (according to its head, direction and stats, and to the obstacles if they block vision)
+    pub fn in_range(&self, target: Point, obstacles: &ObstacleMap) -> bool {
+        in_arc(
+            self.facing(),
+            self.area.angle(self.origin, target),
+            self.stats.vision_range,
+        ) && self.distance_to(target) < self.stats.vision_distance
+            && !obstacles.hides(self.area, self.origin, target)
+    }
+
+    /// Angle at the center of the vision: the way to the chosen destination
+    fn facing(&self) -> Angle {
+        match self.model {
+            MovementModel::Discrete => self.direction_to(self.chosen_destination).to_radians(),
+            MovementModel::Continuous { .. } => {
+                self.area.angle(self.origin, self.chosen_destination)
+            }
+        }
+    }
+
+    /// Direction (among the possible ones) from the origin to the destination
+    pub fn direction_to(&self, destination: Point) -> Direction {
+        Direction::quantize(self.area.angle(self.origin, destination), self.n_directions)
+    }
+
+    /// Distance from the origin to the target (across the borders, if they wrap around)
+    pub fn distance_to(&self, target: Point) -> f32 {
+        self.area.distance(self.origin, target)
+    }
+
+    /// Headings to try for the next movement towards the destination, the preferred ones first
+    pub fn candidate_headings(&self, destination: Point, rng: &mut SimRng) -> Vec<Angle> {
+        let desired = self.area.angle(self.origin, destination);
+        let MovementModel::Continuous {
+            max_turn,
+            n_samples,
+        } = self.model
+        else {
+            // all the possible directions (the ones closest to the destination first)
+            return Rotator::new(Direction::quantize(desired, self.n_directions), rng)
+                .map(|direction| direction.to_radians())
+                .collect();
+        };
+        // a worm without heading can turn to any side
+        let (heading, max_turn) = self
+            .heading
+            .map_or((desired, PI), |heading| (heading, max_turn.val()));
+        let turn = (desired - heading).wrap().val().clamp(-max_turn, max_turn);
+        // only headings within the maximum turn: a worm blocked in all of them cannot move
+        let mut headings = std::iter::once(heading + Angle::new(turn))
+            .chain(
+                (0..n_samples).map(|_| heading + Angle::new(rng.gen_range(-max_turn..=max_turn))),
+            )
+            .collect::<Vec<_>>();
+        headings.sort_by(|lhs, rhs| {
+            angle_between(desired, *lhs).total_cmp(&angle_between(desired, *rhs))
+        });
+        headings
     }
 }
 
@@ -42,84 +160,182 @@ pub enum MovementResult {
 pub trait Mover {
     /// Chooses a target to follow as movement destination
     /// Returns the index of the composite containing the target, if any, and the chosen target
-    fn select_target(&self) -> (Option<usize>, Point);
+    fn select_target(&self, rng: &mut SimRng) -> (Option<usize>, Point);
 
-    /// Checks if a given worm part does not collide (i.e is at least a given distance from all the obstacles)
-    fn collides(&self, part: WormPart, distance: f32) -> bool;
+    /// Checks if a worm part of the given radius collides with anything
+    /// (a part of another worm is kept at the sum of their radii)
+    fn collides(&self, part: WormPart, radius: f32) -> bool;
 
     fn origin(&self) -> WormPart;
 
     fn details(&self) -> &MovementDetails;
 
+    /// Planner routing the worm around what is in the way (None: straight to the destination)
+    fn planner(&self) -> Option<&PathPlanner> {
+        None
+    }
+
     /// Function to execute a movement: it gets a saved_movement and a Mover impl
+    /// The head (of the given radius) moves by the step, clear of what it collides with,
+    /// and hits the target it passes within its reach (twice its radius)
     /// Returns a MovementResult enum to indicate the action to be taken
-    fn execute_movement(&self, distance: f32) -> MovementResult {
+    fn execute_movement(&self, step: f32, radius: f32, rng: &mut SimRng) -> MovementResult {
         // select the id of the target and the desired point position to follow
-        let (target_id, destination) = self.select_target();
-
-        // iterate over the all possible directions (choosing the ones closest to the target first)
-        Rotator::new(self.origin().direction_to(destination))
-            // get a new head in a direction that do no collide with anything
-            .find_map(|direction| {
-                // create the new_head pointing in the iterated direction
-                let new_head = self.origin().copy(direction, distance);
+        let (target_id, destination) = self.select_target(rng);
+        // the point to head to: the next waypoint of the planned path, if any
+        let waypoint = self.planner().map_or(destination, |planner| {
+            planner.waypoint(self.origin(), destination)
+        });
+        // the parts of a slow worm are packed tighter: its new head can get as close to others
+        let clearance = radius.min(step / 2.);
+
+        // iterate over the candidate headings (the ones closest to the waypoint first)
+        self.details()
+            .candidate_headings(waypoint, rng)
+            .into_iter()
+            // get a new head in a heading that do no collide with anything
+            .find_map(|heading| {
+                // create the new_head pointing in the iterated heading (if the borders allow it)
+                let new_head = self
+                    .details()
+                    .area
+                    .place(self.origin().project(heading, step))?;
                 // return Some(new_head) if the head do not collide with any obstable
-                let is_valid = self.details().is_inside_area(new_head) && !self.collides(new_head, distance);
-                is_valid.then_some(new_head)
+                (!self.collides(new_head, clearance)).then_some(new_head)
             })
             .and_then(|valid_head| {
-                // If the destination is reached with the new head, some target is hit
-                (destination.distance_to(valid_head) < distance)
+                // If the destination is reached along the movement, some target is hit
+                let area = self.details().area;
+                if area.segment_distance(destination, self.origin(), valid_head) < 2. * radius {
                     // if the target is part of a composite (i.e has a target_id)
                     // return the id of the target hit and the new head created
-                    .then_some(target_id.map(|id| MovementResult::TargetHit(id, valid_head)))
+                    target_id.map(|id| MovementResult::TargetHit(id, valid_head))
+                } else {
                     // otherwise, destination not reached
-                    .unwrap_or(Some(MovementResult::TargetMiss(valid_head, destination)))
+                    Some(MovementResult::TargetMiss(valid_head, destination))
+                }
             })
             // No valid movement could be found
             .unwrap_or(MovementResult::None)
     }
 }
 
+/// Radii of the parts of the worms, which depend on their species
+#[derive(Clone, Copy)]
+pub struct PartSizes<'a> {
+    /// Species of each worm
+    pub species: &'a [usize],
+    /// Parameters of each species (with the radius of their parts)
+    pub params: &'a [Species],
+}
+
+impl PartSizes<'_> {
+    pub fn radius(&self, worm_id: usize) -> f32 {
+        self.params[self.species[worm_id]].body_size
+    }
+
+    /// Radius of the parts of the largest species
+    pub fn largest(&self) -> f32 {
+        self.params
+            .iter()
+            .map(|species| species.body_size)
+            .fold(0., f32::max)
+    }
+
+    /// Checks if a part of the given radius is closer to the parts of the worm than the sum
+    /// of their radii
+    fn overlap(
+        &self,
+        body: WormBody,
+        worm_id: usize,
+        part: WormPart,
+        radius: f32,
+        area: Area,
+    ) -> bool {
+        body.closer_than(part, radius + self.radius(worm_id) - 0.01, area)
+    }
+}
+
 /// Struct to represent a valid movement target. i.e a position contained in another composite
 /// it contains the index of the composite containing the target
-/// the distance to the target
+/// the cost of choosing the target (the distance to it, possibly weighed)
 /// and the target itself
 struct ValidTarget {
     target_id: usize,
     target: Point,
-    distance: f32,
+    cost: f32,
 }
 
 impl ValidTarget {
-    fn from(origin: WormPart, target_id: usize, target: Point) -> Self {
+    fn from(details: &MovementDetails, target_id: usize, target: Point) -> Self {
         Self {
             target_id,
             target,
-            distance: origin.distance_to(target),
+            cost: details.distance_to(target),
         }
     }
 }
 
 /// Mover for the 'Alive' worm
 /// holds the refereces to candidate targets: the rewards
-/// and the obstacles: the other worm bodies
+/// the obstacles: the other worm bodies and the static obstacles
+/// (along with the spatial indexes used to find the ones nearby)
+/// and the predators to flee from
 pub struct AliveWormMover<'a> {
     pub details: &'a MovementDetails,
     pub rewards: &'a Vec<Reward>,
-    pub bodies: &'a Vec<WormBody>,
+    pub reward_types: &'a RewardTypes,
+    pub selection: TargetSelection,
+    pub predators: &'a [Predator],
+    /// Distance under which a predator in sight is fled from
+    pub flee_distance: f32,
+    pub bodies: &'a BodyStore,
+    pub part_sizes: PartSizes<'a>,
+    pub obstacles: &'a ObstacleMap,
+    pub planner: Option<&'a PathPlanner>,
+    /// Scent followed when no target is in sight (None: a random destination)
+    pub scent: Option<&'a ScentField>,
+    pub reward_index: &'a SpatialGrid,
+    pub body_index: &'a SpatialGrid,
 }
 
 impl<'a> AliveWormMover<'a> {
-    // Converts a reward into a ValidTarget if it is in vision range
-    fn to_valid_target(&self, id: usize, reward: Reward) -> Option<ValidTarget> {
-        in_range(
-            self.details.origin,
-            self.details.chosen_destination,
-            reward,
-            &self.details.stats,
-        )
-        .then_some(ValidTarget::from(self.details.origin, id, reward))
+    // Converts a reward into a ValidTarget if it is in vision range (and worth it)
+    fn to_valid_target(&self, id: usize, reward: &Reward) -> Option<ValidTarget> {
+        let mut valid_target = self
+            .details
+            .in_range(reward.position, self.obstacles)
+            .then(|| ValidTarget::from(self.details, id, reward.position))?;
+        if let TargetSelection::Value = self.selection {
+            let value = self.reward_types.get(reward.kind).value;
+            if value <= 0. {
+                return None;
+            }
+            valid_target.cost /= value;
+        }
+        Some(valid_target)
+    }
+
+    // Point away from the closest predator in sight (if any is too close)
+    fn flee_destination(&self) -> Option<Point> {
+        let area = self.details.area;
+        let predator = self
+            .predators
+            .iter()
+            .map(|predator| predator.position)
+            .filter(|&position| {
+                self.details.distance_to(position) < self.flee_distance
+                    && self.details.in_range(position, self.obstacles)
+            })
+            .min_by(|lhs, rhs| {
+                self.details
+                    .distance_to(*lhs)
+                    .total_cmp(&self.details.distance_to(*rhs))
+            })?;
+        // beyond the vision distance, the worm keeps running that way (until a reward shows up)
+        let away = area.angle(predator, self.details.origin);
+        let distance = self.details.stats.vision_distance * 2.;
+        Some(self.details.origin.project(away, distance))
     }
 }
 
@@ -132,59 +348,92 @@ impl Mover for AliveWormMover<'_> {
         self.details
     }
 
-    /// Search for the closest reward in the visible range
+    fn planner(&self) -> Option<&PathPlanner> {
+        self.planner
+    }
+
+    /// Search for the closest (or most valuable) reward in the visible range
     /// Return the index of the reward in the table (if any) and its position
-    /// (or a randon one if no reward found)
-    fn select_target(&self) -> (Option<usize>, Point) {
+    /// (or one following the scent, or a randon one, if no reward found)
+    /// A predator in sight comes first: the worm runs away from it, ignoring the rewards
+    fn select_target(&self, rng: &mut SimRng) -> (Option<usize>, Point) {
+        if let Some(destination) = self.flee_destination() {
+            return (None, destination);
+        }
         match self
-            .rewards
-            .par_iter()
-            .enumerate()
+            .reward_index
+            .query(self.details.origin, self.details.stats.vision_distance)
+            .into_iter()
             // Filter the rewards in vision range, mapping them as a ValidTarget
-            .filter_map(|(rwd_id, &rwd)| self.to_valid_target(rwd_id, rwd))
-            // choose the closest ValidTarget
-            .min_by(|lhs, rhs| lhs.distance.total_cmp(&rhs.distance))
+            // (the index may hold stale ids of rewards removed during the tick)
+            .filter_map(|rwd_id| {
+                let reward = self.rewards.get(rwd_id)?;
+                self.to_valid_target(rwd_id, reward)
+            })
+            // choose the ValidTarget with the lowest cost
+            .min_by(|lhs, rhs| lhs.cost.total_cmp(&rhs.cost))
         {
             Some(closest_valid) => (Some(closest_valid.target_id), closest_valid.target),
-            // No valid target found, returns the destination according to the movement details
-            None => (None, self.details.choose_destination()),
+            // No valid target found, follows the scent (or the destination of the movement details)
+            None => (None, self.details.wander(self.scent, rng)),
         }
     }
 
-    fn collides(&self, part: WormPart, distance: f32) -> bool {
-        // iterates over all the parts of all worm bodies, checking for collision
-        self.bodies.par_iter().any(|body| {
-            body.iter()
-                .any(|point| point.distance_to(part) < distance - 0.01)
-        })
+    fn collides(&self, part: WormPart, radius: f32) -> bool {
+        // iterates over all the parts of the worm bodies nearby, checking for collision
+        self.body_index
+            .query(part, radius + self.part_sizes.largest())
+            .into_iter()
+            .any(|pos| {
+                let body = self.bodies.get(pos);
+                self.part_sizes
+                    .overlap(body, pos, part, radius, self.details.area)
+            })
+            // and with the static obstacles (keeping them a part radius away)
+            || self.obstacles.collides(self.details.area, self.origin(), part, radius)
     }
 }
 
 /// Mover for the 'Chasing' worm
 /// holds the refereces to candidate targets: other 'alive' snakes
-/// and the obstacles: other snakes not alive and rewards
+/// and the obstacles: other snakes not alive, rewards and the static obstacles
+/// (along with the spatial indexes used to find the ones nearby)
 pub struct ChasingWormMover<'a> {
     pub details: &'a MovementDetails,
     pub rewards: &'a Vec<Reward>,
-    pub bodies: &'a Vec<WormBody>,
+    pub bodies: &'a BodyStore,
     pub behaviors: &'a Vec<WormBehavior>,
+    pub part_sizes: PartSizes<'a>,
+    /// Only the worms of this species are valid targets (None: any alive worm)
+    pub target_species: Option<usize>,
+    pub obstacles: &'a ObstacleMap,
+    pub planner: Option<&'a PathPlanner>,
+    /// Scent followed when no target is in sight (None: a random destination)
+    pub scent: Option<&'a ScentField>,
+    pub reward_index: &'a SpatialGrid,
+    pub body_index: &'a SpatialGrid,
 }
 
 impl<'a> ChasingWormMover<'a> {
-    // Converts a tail part into a ValidTarget if it is in vision range
-    fn to_valid_target(&self, id: usize, target: &WormBody) -> Option<ValidTarget> {
-        // check if the target is alive
+    // Checks if the worm can be merged with (alive, and of the right species)
+    fn can_merge(&self, id: usize) -> bool {
         matches!(self.behaviors[id], WormBehavior::Alive(_))
+            && self
+                .target_species
+                .is_none_or(|species| self.part_sizes.species[id] == species)
+    }
+
+    // Converts a tail part into a ValidTarget if it is in vision range
+    fn to_valid_target(&self, id: usize, target: WormBody) -> Option<ValidTarget> {
+        let tail = target.tail()?;
+        // check if the target can be merged with
+        self.can_merge(id)
             .then(|| {
                 // check if the target tail is in vision range
-                in_range(
-                    self.details.origin,
-                    self.details.chosen_destination,
-                    *target.tail(),
-                    &self.details.stats,
-                )
-                // return the Validtarget if the case
-                .then(|| ValidTarget::from(self.details.origin, id, *target.tail()))
+                self.details
+                    .in_range(tail, self.obstacles)
+                    // return the Validtarget if the case
+                    .then(|| ValidTarget::from(self.details, id, tail))
             })
             .flatten()
     }
@@ -199,47 +448,256 @@ impl Mover for ChasingWormMover<'_> {
         self.details
     }
 
+    fn planner(&self) -> Option<&PathPlanner> {
+        self.planner
+    }
+
     /// Search for the closest worm tail in the visible range
     /// Return the index of the target worm in the table (if any) and its tail position
-    /// (or a randon one if no target found)
-    fn select_target(&self) -> (Option<usize>, Point) {
+    /// (or one following the scent, or a randon one, if no target found)
+    fn select_target(&self, rng: &mut SimRng) -> (Option<usize>, Point) {
         match self
-            .bodies
-            .par_iter()
-            .enumerate()
+            .body_index
+            .query(self.details.origin, self.details.stats.vision_distance)
+            .into_iter()
             // Filter the worms alive and in range, mapping their tail as a ValidTarget
-            .filter_map(|(target_id, target)| self.to_valid_target(target_id, target))
+            .filter_map(|target_id| self.to_valid_target(target_id, self.bodies.get(target_id)))
             // choose the closest one
-            .min_by(|lhs, rhs| lhs.distance.total_cmp(&rhs.distance))
+            .min_by(|lhs, rhs| lhs.cost.total_cmp(&rhs.cost))
         {
             Some(chosen_target) => (Some(chosen_target.target_id), chosen_target.target),
-            // No valid target found, returns the destination according to the movement details
-            None => (None, self.details.choose_destination()),
+            // No valid target found, follows the scent (or the destination of the movement details)
+            None => (None, self.details.wander(self.scent, rng)),
         }
     }
 
-    fn collides(&self, part: WormPart, distance: f32) -> bool {
-        self.bodies.par_iter().enumerate().any(|(pos, body)| {
-            // Skip the tail of alive worms as they ar valid targets
-            matches!(self.behaviors[pos], WormBehavior::Alive(_))
-                .then_some(body.iter().take(body.size() - 1))
-                .unwrap_or(body.iter().take(body.size()))
-                // check for collision with all parts
-                .any(|point| point.distance_to(part) < distance - 0.1)
-        }) 
-        // check for collision with rewards
+    fn collides(&self, part: WormPart, radius: f32) -> bool {
+        let reach = 2. * radius;
+        let area = self.details.area;
+        self.body_index
+            .query(part, radius + self.part_sizes.largest())
+            .into_iter()
+            .any(|pos| {
+                let body = self.bodies.get(pos);
+                // Skip the tail of the worms that are valid targets
+                let skipped = usize::from(self.can_merge(pos));
+                // check for collision with all (other) parts
+                let front = body.front(body.size().saturating_sub(skipped));
+                self.part_sizes.overlap(front, pos, part, radius, area)
+            })
+        // check for collision with rewards nearby
         || self
-            .rewards
-            .par_iter()
-            .any(|point| point.distance_to(part) < distance - 0.1)
+            .reward_index
+            .query(part, reach)
+            .into_iter()
+            .filter_map(|pos| self.rewards.get(pos))
+            .any(|reward| area.distance(reward.position, part) < reach - 0.1)
+        // check for collision with the static obstacles (keeping them a part radius away)
+        || self.obstacles.collides(area, self.origin(), part, radius)
     }
 }
 
-/// Checks if a given destination is in range of worm
-/// (according to its head, direction and stats)
-pub fn in_range(origin: Point, destination: Point, target: Point, stats: &WormStats) -> bool {
-    origin
-        .direction_to(destination)
-        .connect(origin, target, stats.vision_range)
-        && origin.distance_to(target) < stats.vision_distance
+/// Mover for an 'Alive' worm driven by its neural network (instead of the scripts)
+/// the network turns what the worm senses (the closest reward and body in sight, its hunger
+/// and size) into the heading of its movement, the worm eating the rewards it runs into
+/// The senses (and collisions) are the ones of the scripted mover
+pub struct NeuralMover<'a> {
+    pub senses: AliveWormMover<'a>,
+    pub brain: &'a Brain,
+    /// Index of the worm in the table (its own body is not sensed)
+    pub worm_id: usize,
+    /// How close the worm is to starve, from 0 (just ate) to 1
+    pub hunger: f32,
+    /// Size of the worm relative to its split size
+    pub size: f32,
+    /// Length of a movement
+    pub step: f32,
+}
+
+impl NeuralMover<'_> {
+    // Closest of the points in vision range (with the id of the composite holding it)
+    fn closest_in_range(
+        &self,
+        points: impl Iterator<Item = (usize, Point)>,
+    ) -> Option<(usize, Point)> {
+        let details = self.senses.details;
+        points
+            .filter(|&(_, point)| details.in_range(point, self.senses.obstacles))
+            .min_by(|(_, lhs), (_, rhs)| {
+                details
+                    .distance_to(*lhs)
+                    .total_cmp(&details.distance_to(*rhs))
+            })
+    }
+}
+
+impl Mover for NeuralMover<'_> {
+    fn origin(&self) -> WormPart {
+        self.senses.details.origin
+    }
+
+    fn details(&self) -> &MovementDetails {
+        self.senses.details
+    }
+
+    /// Feeds what the worm senses to its network, heading where it says
+    /// Return the index of the reward (and its position) if the movement reaches it
+    /// or a point in the chosen heading otherwise
+    fn select_target(&self, _rng: &mut SimRng) -> (Option<usize>, Point) {
+        let details = self.senses.details;
+        let vision_distance = details.stats.vision_distance;
+        let query = |index: &SpatialGrid| index.query(details.origin, vision_distance);
+        // the index may hold stale ids of rewards removed during the tick
+        let reward = self.closest_in_range(query(self.senses.reward_index).into_iter().filter_map(
+            |reward_id| {
+                let reward = self.senses.rewards.get(reward_id)?;
+                Some((reward_id, reward.position))
+            },
+        ));
+        let body = self.closest_in_range(
+            query(self.senses.body_index)
+                .into_iter()
+                .filter(|&worm_id| worm_id != self.worm_id)
+                .flat_map(|worm_id| {
+                    self.senses
+                        .bodies
+                        .get(worm_id)
+                        .iter()
+                        .map(move |part| (worm_id, part))
+                }),
+        );
+
+        // the angles are relative to the way the worm faces
+        let facing = details.facing();
+        let relative = |(_, point): (usize, Point)| {
+            let angle = details.area.angle(details.origin, point) - facing;
+            (angle, details.distance_to(point))
+        };
+        let sensors = Sensors {
+            reward: reward.map(relative),
+            body: body.map(relative),
+            hunger: self.hunger,
+            size: self.size,
+        };
+        let heading = facing + self.brain.heading(&sensors, vision_distance);
+
+        // the reward is eaten if the movement brings the head onto it
+        let next = details.origin.project(heading, self.step);
+        match reward.filter(|&(_, position)| details.area.distance(next, position) < self.step) {
+            Some((reward_id, position)) => (Some(reward_id), position),
+            None => (None, details.origin.project(heading, vision_distance)),
+        }
+    }
+
+    fn collides(&self, part: WormPart, radius: f32) -> bool {
+        self.senses.collides(part, radius)
+    }
+}
+
+/// Mover for a predator
+/// holds the references to candidate targets: the parts of the moving worms
+/// and the obstacles: the static obstacles (the predators cross the worms and rewards)
+pub struct PredatorMover<'a> {
+    pub details: &'a MovementDetails,
+    pub bodies: &'a BodyStore,
+    pub behaviors: &'a Vec<WormBehavior>,
+    pub obstacles: &'a ObstacleMap,
+    pub body_index: &'a SpatialGrid,
+}
+
+impl Mover for PredatorMover<'_> {
+    fn origin(&self) -> WormPart {
+        self.details.origin
+    }
+
+    fn details(&self) -> &MovementDetails {
+        self.details
+    }
+
+    /// Search for the closest part of a moving (alive, chasing or with a registered behavior)
+    /// worm in the visible range
+    /// Return the index of the worm in the table (if any) and the position of the part
+    /// (or a randon one if no worm found)
+    fn select_target(&self, rng: &mut SimRng) -> (Option<usize>, Point) {
+        match self
+            .body_index
+            .query(self.details.origin, self.details.stats.vision_distance)
+            .into_iter()
+            .filter(|&worm_id| {
+                matches!(
+                    self.behaviors[worm_id],
+                    WormBehavior::Alive(_) | WormBehavior::Chasing | WormBehavior::Custom(..)
+                )
+            })
+            // any part of the worm in range is a valid target
+            .flat_map(|worm_id| {
+                self.bodies
+                    .get(worm_id)
+                    .iter()
+                    .filter(|&part| self.details.in_range(part, self.obstacles))
+                    .map(move |part| ValidTarget::from(self.details, worm_id, part))
+            })
+            .min_by(|lhs, rhs| lhs.cost.total_cmp(&rhs.cost))
+        {
+            Some(closest) => (Some(closest.target_id), closest.target),
+            None => (None, self.details.choose_destination(rng)),
+        }
+    }
+
+    fn collides(&self, part: WormPart, radius: f32) -> bool {
+        self.obstacles
+            .collides(self.details.area, self.origin(), part, radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use std::f32::consts::PI;
+
+    use super::{MovementDetails, MovementModel};
+    use crate::{
+        composites::WormStats,
+        geometry::{angle_between, Angle, Area, BoundaryMode, Point, SimRng},
+    };
+
+    fn details(model: MovementModel, heading: Option<Angle>) -> MovementDetails {
+        MovementDetails {
+            origin: Point { x: 50., y: 50. },
+            chosen_destination: Point::default(),
+            stats: WormStats::default(),
+            n_directions: 8,
+            area: Area::new(100, 100, BoundaryMode::Bounded),
+            model,
+            heading,
+        }
+    }
+
+    #[test]
+    fn candidate_headings() {
+        let mut rng = SimRng::seed_from_u64(1);
+        let destination = Point { x: 50., y: 100. };
+        let continuous = MovementModel::Continuous {
+            max_turn: Angle::new(PI / 4.),
+            n_samples: 5,
+        };
+
+        // the desired heading (PI / 2) is beyond the maximum turn
+        let headings =
+            details(continuous, Some(Angle::new(0.))).candidate_headings(destination, &mut rng);
+        assert_eq!(headings.len(), 1 + 5);
+        assert!((headings[0].val() - PI / 4.).abs() < 1e-6);
+        assert!(headings
+            .iter()
+            .all(|&heading| angle_between(Angle::new(0.), heading) <= PI / 4. + 1e-6));
+
+        // without heading, any turn is possible
+        let headings = details(continuous, None).candidate_headings(destination, &mut rng);
+        assert!((headings[0].val() - PI / 2.).abs() < 1e-6);
+
+        let headings =
+            details(MovementModel::Discrete, None).candidate_headings(destination, &mut rng);
+        assert_eq!(headings.len(), 8);
+        assert!((headings[0].val() - PI / 2.).abs() < 1e-6);
+    }
 }
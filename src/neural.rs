@@ -0,0 +1,174 @@
+use rand::Rng;
+use rand_distr::Normal;
+use serde::{Deserialize, Serialize};
+
+use crate::geometry::{Angle, SimRng};
+
+/// Number of inputs of the network: the angle (sine and cosine, relative to the facing
+/// of the worm) and closeness of the closest reward and body in sight, the hunger
+/// and size of the worm, and a constant bias
+pub const N_INPUTS: usize = 9;
+/// Number of neurons of the hidden layer
+pub const N_HIDDEN: usize = 6;
+/// Number of outputs: the heading of the movement, as a vector relative to the facing
+pub const N_OUTPUTS: usize = 2;
+/// Number of weights of a network (the hidden layer has a bias neuron too)
+pub const N_WEIGHTS: usize = N_INPUTS * N_HIDDEN + (N_HIDDEN + 1) * N_OUTPUTS;
+
+/// What drives the movements of the 'Alive' worms
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Controller {
+    /// The hand-coded rules: the best reward in sight, fleeing the predators
+    #[default]
+    Scripted,
+    /// The neural network of the genome
+    Neural,
+}
+
+/// What a worm senses of its surroundings, as fed to its network
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sensors {
+    /// Angle (relative to the facing) and distance of the closest reward in sight
+    pub reward: Option<(Angle, f32)>,
+    /// Angle (relative to the facing) and distance of the closest part of another worm in sight
+    pub body: Option<(Angle, f32)>,
+    /// How close the worm is to starve, from 0 (just ate) to 1
+    pub hunger: f32,
+    /// Size of the worm relative to its split size, from 0 to 1
+    pub size: f32,
+}
+
+impl Sensors {
+    /// Inputs of the network (the distances relative to the vision distance)
+    fn inputs(&self, vision_distance: f32) -> [f32; N_INPUTS] {
+        // nothing in sight: no direction and no closeness
+        let sense = |seen: Option<(Angle, f32)>| {
+            seen.map_or([0.; 3], |(angle, distance)| {
+                let angle = angle.val();
+                let closeness = 1. - (distance / vision_distance).clamp(0., 1.);
+                [angle.sin(), angle.cos(), closeness]
+            })
+        };
+        let [reward_sin, reward_cos, reward_closeness] = sense(self.reward);
+        let [body_sin, body_cos, body_closeness] = sense(self.body);
+        [
+            reward_sin,
+            reward_cos,
+            reward_closeness,
+            body_sin,
+            body_cos,
+            body_closeness,
+            self.hunger.clamp(0., 1.),
+            self.size.clamp(0., 1.),
+            1.,
+        ]
+    }
+}
+
+/// Small feed-forward network (one hidden layer) choosing the heading of a worm
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Brain {
+    /// Weights of the hidden layer (input by input), then of the output layer
+    weights: Vec<f32>,
+}
+
+impl Brain {
+    /// Network with the given weights (None if there are not N_WEIGHTS of them)
+    pub fn new(weights: Vec<f32>) -> Option<Self> {
+        (weights.len() == N_WEIGHTS).then_some(Self { weights })
+    }
+
+    /// Network with random weights in [-1, 1]
+    pub fn rand(rng: &mut SimRng) -> Self {
+        Self {
+            weights: (0..N_WEIGHTS).map(|_| rng.gen_range(-1.0..=1.0)).collect(),
+        }
+    }
+
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    /// Heading of the movement, relative to the facing of the worm
+    pub fn heading(&self, sensors: &Sensors, vision_distance: f32) -> Angle {
+        let inputs = sensors.inputs(vision_distance);
+        let (hidden_weights, output_weights) = self.weights.split_at(N_INPUTS * N_HIDDEN);
+        let hidden = hidden_weights
+            .chunks(N_INPUTS)
+            .map(|weights| {
+                let sum = weights.iter().zip(&inputs).map(|(w, x)| w * x).sum::<f32>();
+                sum.tanh()
+            })
+            .chain(std::iter::once(1.))
+            .collect::<Vec<_>>();
+        let [x, y] = [0, 1].map(|output| {
+            output_weights[output * (N_HIDDEN + 1)..(output + 1) * (N_HIDDEN + 1)]
+                .iter()
+                .zip(&hidden)
+                .map(|(w, h)| w * h)
+                .sum::<f32>()
+        });
+        Angle::new(y.atan2(x))
+    }
+
+    /// Copy of the network with a random (gaussian) change of each weight
+    pub fn mutate(&self, rng: &mut SimRng, deviation: f32) -> Self {
+        let noise = Normal::new(0., deviation).expect("deviation is positive and finite");
+        Self {
+            weights: self.weights.iter().map(|w| w + rng.sample(noise)).collect(),
+        }
+    }
+
+    /// Average of two networks, weighted by the number of parts coming from each worm
+    pub fn combine(&self, weight: usize, other: &Self, other_weight: usize) -> Self {
+        let total = (weight + other_weight).max(1) as f32;
+        Self {
+            weights: self
+                .weights
+                .iter()
+                .zip(&other.weights)
+                .map(|(lhs, rhs)| (lhs * weight as f32 + rhs * other_weight as f32) / total)
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use std::f32::consts::PI;
+
+    use super::{Brain, Sensors, N_HIDDEN, N_INPUTS, N_WEIGHTS};
+    use crate::geometry::{Angle, SimRng};
+
+    #[test]
+    fn heading() {
+        // a hidden neuron copying the sine of the reward angle, another its cosine
+        let mut weights = vec![0.; N_WEIGHTS];
+        weights[0] = 1.;
+        weights[N_INPUTS + 1] = 1.;
+        // x from the cosine neuron, y from the sine one
+        let outputs = N_INPUTS * N_HIDDEN;
+        weights[outputs + 1] = 1.;
+        weights[outputs + N_HIDDEN + 1] = 1.;
+        let brain = Brain::new(weights).unwrap();
+
+        let sensors = Sensors {
+            reward: Some((Angle::new(PI / 4.), 50.)),
+            ..Default::default()
+        };
+        // tanh keeps the sign and the symmetry: the heading points to the reward
+        assert!((brain.heading(&sensors, 100.).val() - PI / 4.).abs() < 1e-5);
+        assert!(Brain::new(vec![0.; 3]).is_none());
+
+        let mut rng = SimRng::seed_from_u64(1);
+        let random = Brain::rand(&mut rng);
+        assert!(random.weights().iter().all(|w| (-1. ..=1.).contains(w)));
+        let mutated = random.mutate(&mut rng, 0.1);
+        assert_ne!(mutated, random);
+        let average = random.combine(1, &mutated, 3);
+        let expected = (random.weights()[0] + 3. * mutated.weights()[0]) / 4.;
+        assert!((average.weights()[0] - expected).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,216 @@
+use serde::{Deserialize, Serialize};
+
+use crate::geometry::{Area, Point};
+
+/// Static obstacle of the scene (worms can neither cross it nor stand on it)
+/// In a configuration or map file: {"shape": "circle", "center": {"x": 10, "y": 10}, "radius": 5}
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Obstacle {
+    Circle {
+        center: Point,
+        radius: f32,
+    },
+    /// Axis-aligned rectangle, between its minimum and maximum corners
+    Rect {
+        min: Point,
+        max: Point,
+    },
+    /// Wall without thickness, from one end to the other
+    Segment {
+        from: Point,
+        to: Point,
+    },
+}
+
+impl Obstacle {
+    /// Distance from the point to the obstacle (0 inside it)
+    pub fn distance(&self, point: Point) -> f32 {
+        match *self {
+            Obstacle::Circle { center, radius } => (point.distance_to(center) - radius).max(0.),
+            Obstacle::Rect { min, max } => {
+                let dx = (min.x - point.x).max(point.x - max.x).max(0.);
+                let dy = (min.y - point.y).max(point.y - max.y).max(0.);
+                dx.hypot(dy)
+            }
+            Obstacle::Segment { from, to } => segment_distance(point, from, to),
+        }
+    }
+
+    /// Checks if the straight path between the two points touches the obstacle
+    pub fn crosses(&self, from: Point, to: Point) -> bool {
+        match *self {
+            Obstacle::Circle { center, radius } => segment_distance(center, from, to) <= radius,
+            Obstacle::Rect { min, max } => {
+                let corners = [
+                    min,
+                    Point { x: max.x, y: min.y },
+                    max,
+                    Point { x: min.x, y: max.y },
+                ];
+                self.distance(from) == 0.
+                    || (0..4)
+                        .any(|i| segments_intersect(from, to, corners[i], corners[(i + 1) % 4]))
+            }
+            Obstacle::Segment {
+                from: start,
+                to: end,
+            } => segments_intersect(from, to, start, end),
+        }
+    }
+
+    /// Describes the values that make the obstacle invalid (if any)
+    pub fn invalid(&self) -> Option<&'static str> {
+        let finite = |point: Point| point.x.is_finite() && point.y.is_finite();
+        match *self {
+            Obstacle::Circle { center, radius } => {
+                (!finite(center) || !radius.is_finite() || radius <= 0.)
+                    .then_some("a circle needs a finite center and a positive radius")
+            }
+            Obstacle::Rect { min, max } => {
+                (!finite(min) || !finite(max) || min.x > max.x || min.y > max.y).then_some(
+                    "a rect needs finite corners, with min.x <= max.x and min.y <= max.y",
+                )
+            }
+            Obstacle::Segment { from, to } => {
+                (!finite(from) || !finite(to)).then_some("a segment needs finite ends")
+            }
+        }
+    }
+}
+
+/// Static obstacles of a scene
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ObstacleMap {
+    pub obstacles: Vec<Obstacle>,
+    /// If the obstacles hide the targets behind them
+    pub block_vision: bool,
+}
+
+impl ObstacleMap {
+    /// Checks if a part moving from the origin (with the given radius) hits an obstacle,
+    /// on its way or at its new position
+    /// (on a torus, the path is the shortest one, possibly across the borders)
+    pub fn collides(&self, area: Area, origin: Point, part: Point, radius: f32) -> bool {
+        let path_end = origin + area.offset(origin, part);
+        self.obstacles
+            .iter()
+            .any(|obstacle| obstacle.distance(part) < radius || obstacle.crosses(origin, path_end))
+    }
+
+    /// Checks if the target can not be seen from the origin (only if the obstacles block vision)
+    pub fn hides(&self, area: Area, origin: Point, target: Point) -> bool {
+        let target = origin + area.offset(origin, target);
+        self.block_vision
+            && self
+                .obstacles
+                .iter()
+                .any(|obstacle| obstacle.crosses(origin, target))
+    }
+
+    /// Checks if the point is inside an obstacle
+    pub fn covers(&self, point: Point) -> bool {
+        self.obstacles
+            .iter()
+            .any(|obstacle| obstacle.distance(point) == 0.)
+    }
+}
+
+// Distance from the point to the segment between start and end
+fn segment_distance(point: Point, start: Point, end: Point) -> f32 {
+    let (segment, relative) = (end - start, point - start);
+    let length = segment.x * segment.x + segment.y * segment.y;
+    let progress = if length > 0. {
+        ((relative.x * segment.x + relative.y * segment.y) / length).clamp(0., 1.)
+    } else {
+        0.
+    };
+    point.distance_to(start + segment.scale(progress))
+}
+
+// Checks if the segments (a1, a2) and (b1, b2) have a point in common
+fn segments_intersect(a1: Point, a2: Point, b1: Point, b2: Point) -> bool {
+    // sign of the turn from (o, p) to (o, q)
+    let cross =
+        |o: Point, p: Point, q: Point| (p.x - o.x) * (q.y - o.y) - (p.y - o.y) * (q.x - o.x);
+    let (d1, d2) = (cross(b1, b2, a1), cross(b1, b2, a2));
+    let (d3, d4) = (cross(a1, a2, b1), cross(a1, a2, b2));
+    if ((d1 > 0. && d2 < 0.) || (d1 < 0. && d2 > 0.))
+        && ((d3 > 0. && d4 < 0.) || (d3 < 0. && d4 > 0.))
+    {
+        return true;
+    }
+    // collinear or touching cases: an end on the other segment
+    let on_segment = |p: Point, start: Point, end: Point| segment_distance(p, start, end) < 1e-4;
+    on_segment(a1, b1, b2)
+        || on_segment(a2, b1, b2)
+        || on_segment(b1, a1, a2)
+        || on_segment(b2, a1, a2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Obstacle, ObstacleMap};
+    use crate::geometry::{Area, BoundaryMode, Point};
+
+    #[test]
+    fn shapes() {
+        let point = |x, y| Point { x, y };
+        let circle = Obstacle::Circle {
+            center: point(10., 10.),
+            radius: 5.,
+        };
+        let rect = Obstacle::Rect {
+            min: point(20., 0.),
+            max: point(30., 10.),
+        };
+        let wall = Obstacle::Segment {
+            from: point(0., 50.),
+            to: point(100., 50.),
+        };
+
+        assert_eq!(circle.distance(point(10., 12.)), 0.);
+        assert_eq!(circle.distance(point(10., 20.)), 5.);
+        assert_eq!(rect.distance(point(33., 14.)), 5.);
+        assert_eq!(wall.distance(point(40., 47.)), 3.);
+
+        assert!(circle.crosses(point(0., 10.), point(20., 10.)));
+        assert!(!circle.crosses(point(0., 0.), point(20., 0.)));
+        assert!(rect.crosses(point(25., -5.), point(25., 15.)));
+        assert!(rect.crosses(point(25., 5.), point(26., 6.)));
+        assert!(!rect.crosses(point(0., 20.), point(40., 20.)));
+        assert!(wall.crosses(point(40., 40.), point(40., 60.)));
+        assert!(!wall.crosses(point(40., 40.), point(40., 49.)));
+
+        assert!(circle.invalid().is_none());
+        assert!(Obstacle::Rect {
+            min: point(1., 1.),
+            max: point(0., 0.)
+        }
+        .invalid()
+        .is_some());
+    }
+
+    #[test]
+    fn map() {
+        let area = Area::new(100, 100, BoundaryMode::Torus);
+        let mut map = ObstacleMap {
+            obstacles: vec![Obstacle::Segment {
+                from: Point { x: 50., y: 0. },
+                to: Point { x: 50., y: 100. },
+            }],
+            block_vision: false,
+        };
+        let (left, right) = (Point { x: 45., y: 50. }, Point { x: 55., y: 50. });
+        // a step over the wall, or too close to it
+        assert!(map.collides(area, left, right, 1.));
+        assert!(map.collides(area, Point { x: 40., y: 50. }, left, 6.));
+        // across the border of the torus, far from the wall
+        assert!(!map.collides(area, Point { x: 98., y: 50. }, Point { x: 2., y: 50. }, 1.));
+
+        assert!(!map.hides(area, left, right));
+        map.block_vision = true;
+        assert!(map.hides(area, left, right));
+        assert!(!map.hides(area, left, Point { x: 40., y: 80. }));
+    }
+}
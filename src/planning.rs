@@ -0,0 +1,393 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashMap},
+    f32::consts::SQRT_2,
+};
+
+use crate::{
+    geometry::{Area, BoundaryMode, Point},
+    obstacles::Obstacle,
+};
+
+/// How a worm finds its way to the destination chosen by its mover
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Planner {
+    /// Straight to the destination (the closest heading that does not collide)
+    #[default]
+    Greedy,
+    /// Along the shortest path found by A* in a coarse grid of the bodies and obstacles
+    AStar,
+}
+
+/// Path planning of the worms, for each behavior
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlanningParameters {
+    /// Planner of the 'Alive' worms, heading to rewards
+    pub alive: Planner,
+    /// Planner of the 'Chasing' worms, heading to the tails of other worms
+    pub chasing: Planner,
+    /// Size of the cells of the planning grid
+    pub cell_size: f32,
+    /// Maximum number of cells explored for a path (the best partial path is used beyond it)
+    pub budget: usize,
+}
+
+impl Default for PlanningParameters {
+    fn default() -> Self {
+        Self {
+            alive: Planner::Greedy,
+            chasing: Planner::Greedy,
+            cell_size: 14.,
+            budget: 400,
+        }
+    }
+}
+
+impl PlanningParameters {
+    /// Checks if a behavior uses the A* planner
+    pub fn enabled(&self) -> bool {
+        self.alive == Planner::AStar || self.chasing == Planner::AStar
+    }
+}
+
+// Entry of the A* frontier: the cheapest estimated cost first (then the lowest cell)
+#[derive(PartialEq)]
+struct Frontier {
+    estimate: f32,
+    cell: usize,
+}
+
+impl Eq for Frontier {}
+
+impl PartialOrd for Frontier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Frontier {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.estimate
+            .total_cmp(&other.estimate)
+            .then(self.cell.cmp(&other.cell))
+    }
+}
+
+/// Coarse occupancy grid of the scene, finding paths around the blocked cells
+/// The static obstacles are kept apart, so that the grid can be cleared each tick
+/// and filled again with the bodies
+#[derive(Default)]
+pub struct PathPlanner {
+    area: Area,
+    cell_size: f32,
+    budget: usize,
+    columns: usize,
+    rows: usize,
+    // cells blocked by the static obstacles
+    walls: Vec<bool>,
+    blocked: Vec<bool>,
+}
+
+impl PathPlanner {
+    /// Creates the grid of the area, blocking the cells too close to the obstacles
+    /// (closer than the margin)
+    pub fn new(
+        area: Area,
+        params: &PlanningParameters,
+        obstacles: &[Obstacle],
+        margin: f32,
+    ) -> Self {
+        let cell_size = params.cell_size;
+        let columns = 1 + (area.width as f32 / cell_size) as usize;
+        let rows = 1 + (area.height as f32 / cell_size) as usize;
+        let mut planner = Self {
+            area,
+            cell_size,
+            budget: params.budget,
+            columns,
+            rows,
+            walls: vec![false; columns * rows],
+            blocked: vec![false; columns * rows],
+        };
+        // a cell is blocked if an obstacle may reach the margin around any of its points
+        let reach = margin + cell_size * SQRT_2 / 2.;
+        for cell in 0..columns * rows {
+            let center = planner.center(cell);
+            planner.walls[cell] = obstacles
+                .iter()
+                .any(|obstacle| obstacle.distance(center) < reach);
+        }
+        planner.clear();
+        planner
+    }
+
+    /// Unblocks all the cells but the ones of the static obstacles
+    pub fn clear(&mut self) {
+        self.blocked.copy_from_slice(&self.walls);
+    }
+
+    /// Blocks the cells touched by the disc around the point
+    /// (on a torus, the disc wraps around the borders like the paths)
+    pub fn block(&mut self, point: Point, radius: f32) {
+        let point = self.area.wrap(point);
+        let center = self.index(point);
+        // range of the cells touched, relative to the one of the point
+        let span = |value: f32, cell: usize| {
+            let to_cell = |value: f32| (value / self.cell_size).floor() as isize - cell as isize;
+            to_cell(value - radius)..=to_cell(value + radius)
+        };
+        for dy in span(point.y, center / self.columns) {
+            for dx in span(point.x, center % self.columns) {
+                if let Some(cell) = self.offset(center, dx, dy) {
+                    self.blocked[cell] = true;
+                }
+            }
+        }
+    }
+
+    /// Point to head to, in order to reach the destination along the planned path:
+    /// the furthest point of the path in a straight line from the origin
+    /// (the destination itself if nothing is in the way)
+    /// The cells around the origin are considered free, as they hold the worm itself
+    pub fn waypoint(&self, origin: Point, destination: Point) -> Point {
+        let (start, goal) = (self.index(origin), self.index(destination));
+        if start == goal || self.in_sight(origin, destination) {
+            return destination;
+        }
+        let path = self.search(start, goal);
+        // the path goes from the goal (or the closest cell found) back to the start
+        path.iter()
+            .map(|&cell| match cell == goal {
+                true => destination,
+                false => self.center(cell),
+            })
+            .find(|&point| self.in_sight(origin, point))
+            .unwrap_or(destination)
+    }
+
+    // Checks if a path can go through the cell: either not blocked,
+    // or next to the start (where the head and neck of the worm are) or the goal
+    fn free(&self, cell: usize, start: usize, goal: usize) -> bool {
+        let (dx, dy) = self.cell_delta(cell, start);
+        !self.blocked[cell] || cell == goal || (dx <= 1 && dy <= 1)
+    }
+
+    // A* search from the start to the goal, returning the path from the goal to the start
+    // (from the cell closest to the goal, if the goal can not be reached within the budget)
+    // Only the cells reached are recorded, keeping the search cheap in a fine grid
+    fn search(&self, start: usize, goal: usize) -> Vec<usize> {
+        // cells reached: cost from the start, previous cell in the path and if explored
+        let mut reached = HashMap::from([(start, (0., start, false))]);
+        let mut frontier = BinaryHeap::new();
+        frontier.push(Reverse(Frontier {
+            estimate: self.heuristic(start, goal),
+            cell: start,
+        }));
+        let (mut closest, mut explored) = (start, 0);
+        while let Some(Reverse(Frontier { cell, .. })) = frontier.pop() {
+            let (cell_cost, _, explored_cell) = reached.get_mut(&cell).unwrap();
+            if *explored_cell {
+                // outdated entry: the cell was already reached more cheaply
+                continue;
+            }
+            *explored_cell = true;
+            let cell_cost = *cell_cost;
+            if self.heuristic(cell, goal) < self.heuristic(closest, goal) {
+                closest = cell;
+            }
+            explored += 1;
+            if cell == goal || explored > self.budget {
+                break;
+            }
+            for (neighbor, step) in self.neighbors(cell, start, goal) {
+                let new_cost = cell_cost + step;
+                if reached
+                    .get(&neighbor)
+                    .is_none_or(|&(cost, _, _)| new_cost < cost)
+                {
+                    reached.insert(neighbor, (new_cost, cell, false));
+                    frontier.push(Reverse(Frontier {
+                        estimate: new_cost + self.heuristic(neighbor, goal),
+                        cell: neighbor,
+                    }));
+                }
+            }
+        }
+        let mut path = vec![closest];
+        while let Some(&cell) = path.last().filter(|&&cell| cell != start) {
+            path.push(reached[&cell].1);
+        }
+        path
+    }
+
+    // Free cells around the cell, with the cost of the step
+    // Diagonal steps are only allowed when both adjacent cells are free
+    fn neighbors(&self, cell: usize, start: usize, goal: usize) -> Vec<(usize, f32)> {
+        let free = |cell: Option<usize>| cell.filter(|&cell| self.free(cell, start, goal));
+        let mut neighbors = Vec::with_capacity(8);
+        for (dx, dy) in [
+            (-1, -1),
+            (0, -1),
+            (1, -1),
+            (-1, 0),
+            (1, 0),
+            (-1, 1),
+            (0, 1),
+            (1, 1),
+        ] {
+            let Some(neighbor) = free(self.offset(cell, dx, dy)) else {
+                continue;
+            };
+            if dx == 0 || dy == 0 {
+                neighbors.push((neighbor, 1.));
+            } else if free(self.offset(cell, dx, 0)).is_some()
+                && free(self.offset(cell, 0, dy)).is_some()
+            {
+                neighbors.push((neighbor, SQRT_2));
+            }
+        }
+        neighbors
+    }
+
+    // Octile distance between two cells (in cells)
+    fn heuristic(&self, from: usize, to: usize) -> f32 {
+        let (dx, dy) = self.cell_delta(from, to);
+        let (dx, dy) = (dx as f32, dy as f32);
+        dx.max(dy) + (SQRT_2 - 1.) * dx.min(dy)
+    }
+
+    // Number of columns and rows between two cells (across the borders of a torus)
+    fn cell_delta(&self, from: usize, to: usize) -> (usize, usize) {
+        let delta = |from: usize, to: usize, limit: usize| {
+            let delta = from.abs_diff(to);
+            match self.area.boundary {
+                BoundaryMode::Torus => delta.min(limit - delta),
+                _ => delta,
+            }
+        };
+        (
+            delta(from % self.columns, to % self.columns, self.columns),
+            delta(from / self.columns, to / self.columns, self.rows),
+        )
+    }
+
+    // Checks if the straight line between the points only crosses free cells
+    fn in_sight(&self, from: Point, to: Point) -> bool {
+        let offset = self.area.offset(from, to);
+        let (start, goal) = (self.index(from), self.index(to));
+        let length = offset.x.hypot(offset.y);
+        let n_samples = (2. * length / self.cell_size).ceil() as usize;
+        (1..n_samples).all(|i| {
+            let cell = self.index(from + offset.scale(i as f32 / n_samples as f32));
+            self.free(cell, start, goal)
+        })
+    }
+
+    // Cell next to the given one (None beyond the borders, unless they wrap around)
+    fn offset(&self, cell: usize, dx: isize, dy: isize) -> Option<usize> {
+        let shift = |value: usize, delta: isize, limit: usize| {
+            let shifted = value as isize + delta;
+            match self.area.boundary {
+                BoundaryMode::Torus => Some(shifted.rem_euclid(limit as isize) as usize),
+                _ => (0..limit as isize)
+                    .contains(&shifted)
+                    .then_some(shifted as usize),
+            }
+        };
+        let column = shift(cell % self.columns, dx, self.columns)?;
+        let row = shift(cell / self.columns, dy, self.rows)?;
+        Some(row * self.columns + column)
+    }
+
+    fn index(&self, point: Point) -> usize {
+        let (column, row) = self.cell_of(self.area.wrap(point));
+        row * self.columns + column
+    }
+
+    // Cell containing the point (points outside the area are clamped to the border cells)
+    fn cell_of(&self, point: Point) -> (usize, usize) {
+        let to_cell = |value: f32, limit: usize| {
+            ((value / self.cell_size).floor().max(0.) as usize).min(limit - 1)
+        };
+        (to_cell(point.x, self.columns), to_cell(point.y, self.rows))
+    }
+
+    fn center(&self, cell: usize) -> Point {
+        Point {
+            x: ((cell % self.columns) as f32 + 0.5) * self.cell_size,
+            y: ((cell / self.columns) as f32 + 0.5) * self.cell_size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PathPlanner, PlanningParameters};
+    use crate::{
+        geometry::{Area, BoundaryMode, Point},
+        obstacles::Obstacle,
+    };
+
+    #[test]
+    fn waypoints() {
+        let area = Area::new(100, 100, BoundaryMode::Bounded);
+        let params = PlanningParameters {
+            cell_size: 5.,
+            ..Default::default()
+        };
+        let wall = Obstacle::Segment {
+            from: Point { x: 50., y: 0. },
+            to: Point { x: 50., y: 80. },
+        };
+        let (origin, destination) = (Point { x: 30., y: 40. }, Point { x: 70., y: 40. });
+
+        // nothing in the way: straight to the destination
+        let mut planner = PathPlanner::new(area, &params, &[], 2.);
+        assert_eq!(planner.waypoint(origin, destination), destination);
+        // around the bodies in the way
+        planner.block(Point { x: 50., y: 40. }, 3.);
+        let waypoint = planner.waypoint(origin, destination);
+        assert_ne!(waypoint, destination);
+        planner.clear();
+        assert_eq!(planner.waypoint(origin, destination), destination);
+
+        // around the end of the wall
+        let planner = PathPlanner::new(area, &params, &[wall], 2.);
+        let waypoint = planner.waypoint(origin, destination);
+        assert!(waypoint.y > 70. && waypoint.x < 50., "{waypoint}");
+
+        // with no budget, the way is only partially known (but still around the wall)
+        let params = PlanningParameters {
+            budget: 0,
+            ..params
+        };
+        let planner = PathPlanner::new(area, &params, &[wall], 2.);
+        assert!(planner.waypoint(origin, destination).x < 50.);
+    }
+
+    #[test]
+    fn blocking_across_borders() {
+        let params = PlanningParameters {
+            cell_size: 5.,
+            ..Default::default()
+        };
+        let body = Point { x: 1., y: 49. };
+        let across = Point { x: 97., y: 49. };
+        // on a torus, the disc around a body near a border also covers the cells across it
+        let mut planner =
+            PathPlanner::new(Area::new(98, 98, BoundaryMode::Torus), &params, &[], 2.);
+        planner.block(body, 3.);
+        assert!(planner.blocked[planner.index(body)]);
+        assert!(planner.blocked[planner.index(across)]);
+        assert!(!planner.in_sight(Point { x: 80., y: 49. }, Point { x: 10., y: 49. }));
+        // not in a bounded area
+        let mut planner =
+            PathPlanner::new(Area::new(98, 98, BoundaryMode::Bounded), &params, &[], 2.);
+        planner.block(body, 3.);
+        assert!(planner.blocked[planner.index(body)]);
+        assert!(!planner.blocked[planner.index(across)]);
+    }
+}
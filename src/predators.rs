@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+use crate::{
+    composites::WormStats,
+    geometry::{Angle, Area, Point, SimRng},
+    obstacles::ObstacleMap,
+    spawning::MAX_ATTEMPTS,
+};
+
+/// Predators hunting the worms: they bite parts off the worms they reach,
+/// starve when they go too long without biting and reproduce after enough bites
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PredatorParameters {
+    /// Number of predators created with the scene (no predators if 0)
+    pub count: usize,
+    /// Distance covered by a predator in a tick
+    pub speed: f32,
+    pub stats: WormStats,
+    /// Number of ticks a predator survives without biting
+    pub starvation: usize,
+    /// Number of parts eaten in a bite (from the tail of the victim)
+    pub bite: usize,
+    /// Number of parts to eat before a predator reproduces (never if 0)
+    pub reproduction: usize,
+    /// Worms flee from the predators in sight closer than this distance
+    pub flee_distance: f32,
+}
+
+impl Default for PredatorParameters {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            speed: 14.,
+            stats: WormStats {
+                vision_range: Angle::new(2. * PI / 3.),
+                vision_distance: 150.,
+            },
+            starvation: 500,
+            bite: 2,
+            reproduction: 20,
+            flee_distance: 100.,
+        }
+    }
+}
+
+/// A predator of the scene
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Predator {
+    pub position: Point,
+    /// Point the predator heads to when no worm is in sight
+    pub destination: Point,
+    /// Number of ticks since the last bite
+    pub hunger: usize,
+    /// Number of parts eaten since the predator was born (or last reproduced)
+    pub eaten: usize,
+}
+
+impl Predator {
+    pub fn new(position: Point, destination: Point) -> Self {
+        Self {
+            position,
+            destination,
+            hunger: 0,
+            eaten: 0,
+        }
+    }
+
+    /// Predator at a random position, out of the obstacles (if possible)
+    pub fn rand(rng: &mut SimRng, area: Area, obstacles: &ObstacleMap) -> Self {
+        let mut position = area.rand_point(rng);
+        for _ in 1..MAX_ATTEMPTS {
+            if !obstacles.covers(position) {
+                break;
+            }
+            position = area.rand_point(rng);
+        }
+        Self::new(position, area.rand_point(rng))
+    }
+
+    /// Checks if the predator went hungry for too long
+    pub fn starving(&self, params: &PredatorParameters) -> bool {
+        self.hunger > params.starvation
+    }
+
+    /// Checks if the predator ate enough to reproduce
+    pub fn can_reproduce(&self, params: &PredatorParameters) -> bool {
+        params.reproduction > 0 && self.eaten >= params.reproduction
+    }
+}
@@ -0,0 +1,245 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+use crate::{
+    bodies::BodyStore,
+    composites::{RewardKind, WormBehavior},
+    config::{ConfigError, SimConfig},
+    geometry::{Angle, Area, Point},
+};
+
+/// Hand-authored initial state of a scene, as read from a JSON file:
+/// the configuration of the simulation (with the dimensions and obstacles of the scene)
+/// and the exact worms, rewards and predators to start with
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scenario {
+    /// Same keys as a configuration file (n_worms, n_rewards and n_predators are ignored)
+    pub config: SimConfig,
+    pub worms: Vec<ScenarioWorm>,
+    pub rewards: Vec<ScenarioReward>,
+    pub predators: Vec<ScenarioPredator>,
+}
+
+/// Worm of a scenario: its head and either the rest of its parts
+/// or a size and a heading (the body lying straight behind the head)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioWorm {
+    pub head: Point,
+    /// Direction the head faces, in degrees (0 towards +x, 90 towards +y)
+    #[serde(default)]
+    pub heading: f32,
+    /// Number of parts (worm_size of the configuration if not set)
+    pub size: Option<usize>,
+    /// Parts after the head, from the neck to the tail (replaces heading and size)
+    pub parts: Option<Vec<Point>>,
+    /// Alive(0) if not set
+    pub behavior: Option<WormBehavior>,
+    /// Destination the worm heads to when nothing is in sight (a random one if not set)
+    pub destination: Option<Point>,
+    /// Name of the species of the worm, defined in the configuration (the first one if not set)
+    pub species: Option<String>,
+}
+
+/// Reward of a scenario (the missing destination and kind are chosen randomly)
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioReward {
+    pub position: Point,
+    pub destination: Option<Point>,
+    pub kind: Option<RewardKind>,
+}
+
+/// Predator of a scenario (heading to a random destination if not set)
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioPredator {
+    pub position: Point,
+    pub destination: Option<Point>,
+}
+
+impl ScenarioWorm {
+    /// Positions of all the parts, from the head to the tail
+    pub fn positions(&self, default_size: usize, part_size: f32) -> Vec<Point> {
+        match &self.parts {
+            Some(parts) => std::iter::once(self.head).chain(parts.clone()).collect(),
+            None => {
+                let behind = Angle::new(self.heading.to_radians() + PI);
+                (0..self.size.unwrap_or(default_size))
+                    .map(|i| self.head.project(behind, i as f32 * part_size * 2.))
+                    .collect()
+            }
+        }
+    }
+
+    /// Adds the body of the worm to the store (up to its maximum size), returning its index
+    pub fn push_body(&self, bodies: &mut BodyStore, default_size: usize, part_size: f32) -> usize {
+        let target = self.destination.unwrap_or(self.head);
+        bodies.push(&self.positions(default_size, part_size), target)
+    }
+}
+
+impl Scenario {
+    pub fn from_json(file_path: &str) -> Result<Self, ConfigError> {
+        Self::parse(&std::fs::read_to_string(file_path)?)
+    }
+
+    /// Parses and validates a scenario in JSON (reading the map file of its configuration)
+    pub fn parse(content: &str) -> Result<Self, ConfigError> {
+        let deserializer = &mut serde_json::Deserializer::from_str(content);
+        let mut scenario: Self = serde_path_to_error::deserialize(deserializer)?;
+        scenario.config.load_map()?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    /// Checks the configuration, and that the worms, rewards and predators fit in the scene
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.config.validate()?;
+        let config = &self.config;
+        let area = Area::new(config.width, config.height, config.boundary);
+        let invalid = |field, reason| Err(ConfigError::Invalid { field, reason });
+        let params = config.scene_params();
+        let (species, obstacles) = (params.species, params.obstacles);
+        for (i, worm) in self.worms.iter().enumerate() {
+            let index = match &worm.species {
+                Some(name) => match species.iter().position(|species| &species.name == name) {
+                    Some(index) => index,
+                    None => return invalid("worms", format!("worm {i}: unknown species {name}")),
+                },
+                None => 0,
+            };
+            // the sizes of the species (or of the configuration, without species)
+            let (worm_size, body_size) = species
+                .get(index)
+                .map_or((config.worm_size, config.body_size), |species| {
+                    (species.worm_size, species.body_size)
+                });
+            let positions = worm.positions(worm_size, body_size);
+            if positions.is_empty() || positions.len() > config.max_size {
+                return invalid(
+                    "worms",
+                    format!("worm {i}: size must be between 1 and {}", config.max_size),
+                );
+            }
+            if !positions.iter().all(|&part| area.contains(part)) {
+                return invalid("worms", format!("worm {i}: parts must be inside the scene"));
+            }
+            if positions.iter().any(|&part| obstacles.covers(part)) {
+                return invalid(
+                    "worms",
+                    format!("worm {i}: parts must be out of the obstacles"),
+                );
+            }
+        }
+        for (i, reward) in self.rewards.iter().enumerate() {
+            if !area.contains(reward.position) {
+                return invalid(
+                    "rewards",
+                    format!("reward {i}: position must be inside the scene"),
+                );
+            }
+            if obstacles.covers(reward.position) {
+                return invalid(
+                    "rewards",
+                    format!("reward {i}: position must be out of the obstacles"),
+                );
+            }
+        }
+        for (i, predator) in self.predators.iter().enumerate() {
+            if !area.contains(predator.position) {
+                return invalid(
+                    "predators",
+                    format!("predator {i}: position must be inside the scene"),
+                );
+            }
+            if obstacles.covers(predator.position) {
+                return invalid(
+                    "predators",
+                    format!("predator {i}: position must be out of the obstacles"),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scenario;
+    use crate::{
+        bodies::BodyStore,
+        composites::{RewardKind, WormBehavior},
+        config::ConfigError,
+        geometry::Point,
+    };
+
+    #[test]
+    fn parse() {
+        let scenario = Scenario::parse(
+            r#"{
+                "config": {"width": 100, "height": 50, "part_size": 2.0, "worm_size": 3},
+                "worms": [
+                    {"head": {"x": 20, "y": 10}, "heading": 180, "behavior": "Chasing"},
+                    {"head": {"x": 50, "y": 10}, "parts": [{"x": 50, "y": 14}]}
+                ],
+                "rewards": [{"position": {"x": 5, "y": 5}, "kind": "super_food"}]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(scenario.config.width, 100);
+        assert!(matches!(
+            scenario.worms[0].behavior,
+            Some(WormBehavior::Chasing)
+        ));
+        assert_eq!(scenario.rewards[0].kind, Some(RewardKind::SuperFood));
+
+        // facing -x: the body lies towards +x
+        let mut bodies = BodyStore::new(8);
+        let worm = scenario.worms[0].push_body(&mut bodies, 3, 2.0);
+        let body = bodies.get(worm);
+        assert_eq!(body.size(), 3);
+        assert_eq!(body.head().unwrap(), Point { x: 20., y: 10. });
+        assert!((body.tail().unwrap().x - 28.).abs() < 1e-4);
+        let worm = scenario.worms[1].push_body(&mut bodies, 3, 2.0);
+        let body = bodies.get(worm);
+        assert_eq!(body.size(), 2);
+        assert_eq!(body.tail().unwrap(), Point { x: 50., y: 14. });
+
+        let error = Scenario::parse(
+            r#"{"config": {"width": 100}, "worms": [{"head": {"x": 200, "y": 10}}]}"#,
+        )
+        .unwrap_err();
+        assert!(matches!(error, ConfigError::Invalid { field: "worms", .. }));
+        let error = Scenario::parse(r#"{"worms": [{"position": {"x": 2, "y": 1}}]}"#).unwrap_err();
+        assert!(matches!(error, ConfigError::Parse { .. }));
+
+        // nothing inside the obstacles
+        let inside_obstacle = |entities: &str| {
+            Scenario::parse(&format!(
+                r#"{{"config": {{"obstacles": [{{"shape": "rect", "min": {{"x": 0, "y": 0}},
+                                                   "max": {{"x": 20, "y": 20}}}}]}},
+                     {entities}}}"#
+            ))
+            .unwrap_err()
+        };
+        let error = inside_obstacle(r#""worms": [{"head": {"x": 30, "y": 10}, "heading": 0}]"#);
+        assert!(matches!(error, ConfigError::Invalid { field: "worms", .. }));
+        let error = inside_obstacle(r#""rewards": [{"position": {"x": 10, "y": 10}}]"#);
+        assert!(matches!(
+            error,
+            ConfigError::Invalid {
+                field: "rewards",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn example() {
+        let scenario = Scenario::from_json("conf/scenarios/chase.json").unwrap();
+        assert_eq!(scenario.worms.len(), 4);
+        assert_eq!(scenario.config.obstacles.len(), 1);
+    }
+}
@@ -1,134 +1,797 @@
+use crate::behaviors::{Behavior, BehaviorId, MoverContext, Transition, WormView};
+use crate::bodies::{BodyStore, WormBody};
 use crate::composites::*;
-use crate::geometry::Point;
+use crate::events::{Observer, SimEvent};
+use crate::geometry::{Area, BoundaryMode, Direction, Point, SimRng, N_DIRECTIONS};
+use crate::lineage::{Lineage, WormId};
+use crate::metabolism::Metabolism;
 use crate::movement::*;
+use crate::neural::Controller;
+use crate::obstacles::ObstacleMap;
+use crate::planning::{PathPlanner, Planner, PlanningParameters};
+use crate::predators::{Predator, PredatorParameters};
+use crate::scenario::Scenario;
+use crate::scent::{ScentField, ScentParameters};
+use crate::spatial::SpatialGrid;
+use crate::spawning::{SpawnParameters, Spawner};
+use crate::species::{MergeRule, Species};
+use rand::{seq::SliceRandom, Rng, SeedableRng};
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, fs, io, path::Path, sync::Arc};
 
-#[derive(Clone)]
+/// Version of the snapshot file format written by Scene::save
+/// (2: the kinds of the rewards, 3: the parts of each body from the head to the tail,
+/// instead of its whole ring buffer)
+const SNAPSHOT_VERSION: u64 = 3;
+/// Oldest version of the snapshot file format Scene::load still reads
+const OLDEST_SNAPSHOT_VERSION: u64 = 1;
+
+/// How the worms are updated in a tick
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateMode {
+    /// One worm after the other (in the order of the table), each one seeing the moves
+    /// of the previous ones
+    #[default]
+    Sequential,
+    /// All the worms plan their moves at once (in parallel) against the scene as it was at the
+    /// start of the tick, then the moves are applied in a random order, resolving the conflicts
+    Parallel,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SceneParameters {
     pub worm_size: usize,
     pub body_size: f32,
     pub starvation: usize,
     pub expiration: usize,
+    /// Seed of the random number generator (a random one is used if not set)
+    pub seed: Option<u64>,
+    pub stats: WormStats,
+    /// Maximum number of parts of a worm (it splits when reached)
+    pub max_size: usize,
+    /// Number of possible movement directions
+    pub n_directions: u8,
+    /// Distance covered by a reward in a tick
+    pub reward_speed: f32,
+    /// Behavior of the borders of the scene
+    #[serde(default)]
+    pub boundary: BoundaryMode,
+    /// How the worms (and rewards) choose the heading of their movements
+    #[serde(default)]
+    pub movement: MovementModel,
+    /// Standard deviation of the mutations of the genome of an offspring,
+    /// relative to the value of each trait (no mutation if 0)
+    #[serde(default)]
+    pub mutation: f32,
+    /// How the hunger of the worms is modeled
+    #[serde(default)]
+    pub metabolism: Metabolism,
+    /// Spawn rates, movement and effects of each kind of reward
+    #[serde(default)]
+    pub reward_types: RewardTypes,
+    /// How the worms choose among the rewards in sight
+    #[serde(default)]
+    pub target_selection: TargetSelection,
+    /// Where, how many and how fast the rewards are spawned
+    #[serde(default)]
+    pub spawning: SpawnParameters,
+    /// Static obstacles (walls) of the scene
+    #[serde(default)]
+    pub obstacles: ObstacleMap,
+    /// How the worms find their way around the bodies and obstacles
+    #[serde(default)]
+    pub planning: PlanningParameters,
+    /// Predators hunting the worms (none by default)
+    #[serde(default)]
+    pub predators: PredatorParameters,
+    /// Scent left and followed by the worms (no scent if not set)
+    #[serde(default)]
+    pub scent: Option<ScentParameters>,
+    /// Species of the worms, each with its own number of worms (the number of worms given
+    /// to the scene is ignored), sizes, hunger and vision
+    /// If empty, all the worms belong to a single species made of the parameters above
+    #[serde(default)]
+    pub species: Vec<Species>,
+    /// Which worms a chasing worm may merge with
+    #[serde(default)]
+    pub merge_rule: MergeRule,
+    /// What drives the movements of the alive worms (when no species is defined)
+    #[serde(default)]
+    pub controller: Controller,
+    /// How the worms are updated in a tick
+    #[serde(default)]
+    pub update: UpdateMode,
+}
+
+impl Default for SceneParameters {
+    fn default() -> Self {
+        Self {
+            worm_size: 8,
+            body_size: 7.0,
+            starvation: 2000,
+            expiration: 25,
+            seed: None,
+            stats: WormStats::default(),
+            max_size: MAX_SIZE,
+            n_directions: N_DIRECTIONS,
+            reward_speed: 7.0 / 4.,
+            boundary: BoundaryMode::Bounded,
+            movement: MovementModel::Discrete,
+            mutation: 0.,
+            metabolism: Metabolism::Counter,
+            reward_types: RewardTypes::default(),
+            target_selection: TargetSelection::Closest,
+            spawning: SpawnParameters::default(),
+            obstacles: ObstacleMap::default(),
+            planning: PlanningParameters::default(),
+            predators: PredatorParameters::default(),
+            scent: None,
+            species: Vec::new(),
+            merge_rule: MergeRule::Any,
+            controller: Controller::Scripted,
+            update: UpdateMode::Sequential,
+        }
+    }
+}
+
+impl SceneParameters {
+    /// Genome of the worms created with the scene
+    pub fn base_genome(&self) -> Genome {
+        Genome {
+            stats: self.stats,
+            speed: 1.,
+            starvation: self.starvation,
+            split_size: self.max_size,
+            brain: None,
+        }
+    }
+
+    /// Energy of the worms created with the scene (unused by the counter metabolism)
+    pub fn initial_energy(&self) -> f32 {
+        match self.metabolism {
+            Metabolism::Counter => 0.,
+            Metabolism::Energy(energy) => energy.initial,
+        }
+    }
+
+    /// Defines the single species made of the parameters, with n_worms worms,
+    /// if no species is defined
+    fn fill_species(&mut self, n_worms: usize) {
+        if self.species.is_empty() {
+            self.species.push(Species {
+                name: "worms".to_string(),
+                count: n_worms,
+                worm_size: self.worm_size,
+                body_size: self.body_size,
+                starvation: self.starvation,
+                stats: self.stats,
+                controller: self.controller,
+                head_color: Species::HEAD_COLOR,
+                body_color: Species::BODY_COLOR,
+            });
+        }
+    }
+
+    /// Index of the species with the name (None if there is no such species)
+    pub fn species_index(&self, name: &str) -> Option<usize> {
+        self.species.iter().position(|species| species.name == name)
+    }
 }
 
+#[derive(Serialize, Deserialize)]
 struct SceneContent {
     behaviors: Vec<WormBehavior>,
-    bodies: Vec<WormBody>,
+    bodies: BodyStore,
+    #[serde(default)]
+    genomes: Vec<Genome>,
+    /// Unique ids of the worms (a reused slot gets a new id)
+    #[serde(default)]
+    ids: Vec<WormId>,
+    /// Energy reserves of the worms (only used by the energy metabolism)
+    #[serde(default)]
+    energy: Vec<f32>,
     rewards: Vec<Reward>,
-    reward_destination: Vec<Point>,
+    #[serde(default)]
+    predators: Vec<Predator>,
+    /// Index of the species of each worm
+    #[serde(default)]
+    species: Vec<usize>,
 }
 
 impl SceneContent {
     fn rand(
-        n_worms: usize,
+        rng: &mut SimRng,
         n_rewards: usize,
-        worm_size: usize,
-        body_size: f32,
-        width: usize,
-        height: usize,
+        params: &SceneParameters,
+        area: Area,
+        spawner: &Spawner,
     ) -> Self {
+        // the worms of each species, one species after the other
+        let species = params
+            .species
+            .iter()
+            .enumerate()
+            .flat_map(|(index, species)| std::iter::repeat_n(index, species.count))
+            .collect::<Vec<_>>();
+        let n_worms = species.len();
         let behaviors = vec![WormBehavior::Alive(0); n_worms];
-        let bodies = (0..n_worms)
-            .into_iter()
-            .map(|_| WormBody::rand(worm_size, body_size, width, height))
+        let mut bodies = BodyStore::new(params.max_size);
+        for &index in &species {
+            let species = &params.species[index];
+            bodies.push_rand(
+                rng,
+                species.worm_size,
+                species.body_size,
+                params.n_directions,
+                area,
+                &params.obstacles,
+            );
+        }
+        let genomes = species
+            .iter()
+            .map(|&index| params.species[index].genome(params.max_size, rng))
+            .collect();
+        let energy = vec![params.initial_energy(); n_worms];
+        let positions = (0..n_rewards)
+            .map(|_| spawner.position(&params.spawning, &params.obstacles, area, rng))
             .collect::<Vec<_>>();
-        let rewards = (0..n_rewards)
-            .into_iter()
-            .map(|_| Reward::rand(width, height))
+        let destinations = (0..n_rewards)
+            .map(|_| area.rand_point(rng))
             .collect::<Vec<_>>();
-        let reward_destination = (0..n_rewards)
+        let rewards = positions
             .into_iter()
-            .map(|_| Point::rand(width, height))
+            .zip(destinations)
+            .map(|(position, destination)| Reward {
+                kind: params.reward_types.sample_kind(rng),
+                position,
+                destination,
+            })
+            .collect::<Vec<_>>();
+        let predators = (0..params.predators.count)
+            .map(|_| Predator::rand(rng, area, &params.obstacles))
+            .collect();
+
+        Self {
+            behaviors,
+            bodies,
+            genomes,
+            ids: Vec::new(),
+            energy,
+            rewards,
+            predators,
+            species,
+        }
+    }
+
+    fn from_scenario(
+        rng: &mut SimRng,
+        scenario: &Scenario,
+        params: &SceneParameters,
+        area: Area,
+    ) -> Self {
+        let n_worms = scenario.worms.len();
+        let behaviors = scenario
+            .worms
+            .iter()
+            .map(|worm| worm.behavior.unwrap_or(WormBehavior::Alive(0)))
+            .collect();
+        // the scenario was validated: the species exist (the first one if not set)
+        let species = scenario
+            .worms
+            .iter()
+            .map(|worm| {
+                worm.species
+                    .as_deref()
+                    .and_then(|name| params.species_index(name))
+                    .unwrap_or(0)
+            })
             .collect::<Vec<_>>();
+        let mut bodies = BodyStore::new(params.max_size);
+        for (worm, &index) in scenario.worms.iter().zip(&species) {
+            let species = &params.species[index];
+            worm.push_body(&mut bodies, species.worm_size, species.body_size);
+        }
+        let genomes = species
+            .iter()
+            .map(|&index| params.species[index].genome(params.max_size, rng))
+            .collect();
+        let rewards = scenario
+            .rewards
+            .iter()
+            .map(|reward| Reward {
+                kind: reward
+                    .kind
+                    .unwrap_or_else(|| params.reward_types.sample_kind(rng)),
+                position: reward.position,
+                destination: reward.destination.unwrap_or_else(|| area.rand_point(rng)),
+            })
+            .collect();
+        let predators = scenario
+            .predators
+            .iter()
+            .map(|predator| {
+                let destination = predator.destination.unwrap_or_else(|| area.rand_point(rng));
+                Predator::new(predator.position, destination)
+            })
+            .collect();
 
         Self {
             behaviors,
             bodies,
+            genomes,
+            ids: Vec::new(),
+            energy: vec![params.initial_energy(); n_worms],
             rewards,
-            reward_destination,
+            predators,
+            species,
         }
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Scene {
     params: SceneParameters,
     width: usize,
     height: usize,
-    stats: WormStats,
     content: SceneContent,
+    rng: SimRng,
+    /// Number of ticks executed so far
+    tick: u64,
+    #[serde(default)]
+    lineage: Lineage,
+    #[serde(default)]
+    spawner: Spawner,
+    #[serde(default)]
+    scent: ScentField,
+    /// Events of the last tick
+    #[serde(skip)]
+    events: Vec<SimEvent>,
+    #[serde(skip)]
+    observers: Vec<Observer>,
+    /// Behaviors added to the built-in ones (a snapshot must be loaded with the same ones)
+    #[serde(skip)]
+    registry: Vec<Arc<dyn Behavior>>,
+    /// Registered behaviors whose error was already reported
+    #[serde(skip)]
+    failed_behaviors: Vec<BehaviorId>,
+    // the spatial indexes are derived from the content (not saved)
+    #[serde(skip)]
+    body_index: SpatialGrid,
+    #[serde(skip)]
+    reward_index: SpatialGrid,
+    #[serde(skip)]
+    planner: PathPlanner,
+    /// Rewards eaten during a parallel update, removed once all the moves are applied
+    /// (keeping valid the reward indexes the moves were planned with)
+    #[serde(skip)]
+    eaten_rewards: Vec<usize>,
+}
+
+/// Errors when saving or loading a scene snapshot
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    Format(serde_json::Error),
+    /// The file was written with an unsupported version of the format
+    Version(u64),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(error) => write!(f, "{error}"),
+            SnapshotError::Format(error) => write!(f, "invalid snapshot: {error}"),
+            SnapshotError::Version(version) => write!(
+                f,
+                "unsupported snapshot version {version} \
+                (expected {OLDEST_SNAPSHOT_VERSION} to {SNAPSHOT_VERSION})"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(error: io::Error) -> Self {
+        SnapshotError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(error: serde_json::Error) -> Self {
+        SnapshotError::Format(error)
+    }
+}
+
+/// Layout of a snapshot file: the format version and the scene itself
+#[derive(Serialize, Deserialize)]
+struct SnapshotFile<S> {
+    version: u64,
+    scene: S,
 }
 
 impl Scene {
+    /// Creates a scene with n_worms random worms (or the number of worms of each species,
+    /// if the parameters define species) and n_rewards random rewards
     pub fn new(
         width: usize,
         height: usize,
-        params: SceneParameters,
+        mut params: SceneParameters,
         n_worms: usize,
         n_rewards: usize,
     ) -> Self {
-        Self {
+        params.fill_species(n_worms);
+        let mut rng = Self::seeded_rng(&params);
+        let area = Area::new(width, height, params.boundary);
+        let spawner = Spawner::new(&params.spawning, n_rewards, area, &mut rng);
+        let content = SceneContent::rand(&mut rng, n_rewards, &params, area, &spawner);
+        Self::with_content(width, height, params, rng, spawner, content)
+    }
+
+    /// Creates a scene starting with the worms and rewards of the scenario
+    /// (the random generator only chooses the reward destinations and kinds not given)
+    pub fn from_scenario(scenario: &Scenario) -> Self {
+        let (width, height) = (scenario.config.width, scenario.config.height);
+        let mut params = scenario.config.scene_params();
+        params.fill_species(scenario.worms.len());
+        let mut rng = Self::seeded_rng(&params);
+        let area = Area::new(width, height, params.boundary);
+        let spawner = Spawner::new(&params.spawning, scenario.rewards.len(), area, &mut rng);
+        let content = SceneContent::from_scenario(&mut rng, scenario, &params, area);
+        Self::with_content(width, height, params, rng, spawner, content)
+    }
+
+    // Random generator seeded as in the parameters (or randomly, if they have no seed)
+    fn seeded_rng(params: &SceneParameters) -> SimRng {
+        params
+            .seed
+            .map(SimRng::seed_from_u64)
+            .unwrap_or_else(SimRng::from_entropy)
+    }
+
+    fn with_content(
+        width: usize,
+        height: usize,
+        params: SceneParameters,
+        rng: SimRng,
+        spawner: Spawner,
+        content: SceneContent,
+    ) -> Self {
+        let mut scene = Self {
             width,
             height,
-            stats: WormStats::default(),
-            content: SceneContent::rand(
-                n_worms,
-                n_rewards,
-                params.worm_size,
-                params.body_size,
-                width,
-                height,
-            ),
+            body_index: SpatialGrid::default(),
+            reward_index: SpatialGrid::default(),
+            planner: PathPlanner::default(),
+            content,
             params,
+            rng,
+            tick: 0,
+            lineage: Lineage::default(),
+            spawner,
+            scent: ScentField::default(),
+            events: Vec::new(),
+            observers: Vec::new(),
+            registry: Vec::new(),
+            failed_behaviors: Vec::new(),
+            eaten_rewards: Vec::new(),
+        };
+        scene.register_founders();
+        scene.build_indexes();
+        scene
+    }
+
+    /// Saves the whole state of the scene (including the random generator) as a JSON file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        let snapshot = SnapshotFile {
+            version: SNAPSHOT_VERSION,
+            scene: self,
+        };
+        let writer = io::BufWriter::new(fs::File::create(path)?);
+        serde_json::to_writer(writer, &snapshot)?;
+        Ok(())
+    }
+
+    /// Loads a scene saved with Scene::save, resuming exactly where it was
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        let file_content = fs::read_to_string(path)?;
+        // check the version before interpreting the scene
+        let snapshot: SnapshotFile<serde::de::IgnoredAny> = serde_json::from_str(&file_content)?;
+        if !(OLDEST_SNAPSHOT_VERSION..=SNAPSHOT_VERSION).contains(&snapshot.version) {
+            return Err(SnapshotError::Version(snapshot.version));
+        }
+        let mut scene = serde_json::from_str::<SnapshotFile<Scene>>(&file_content)?.scene;
+        scene.content.bodies.set_max_size(scene.params.max_size);
+        // snapshots taken before the species existed: all the worms are of a single one
+        let n_worms = scene.content.bodies.len();
+        scene.params.fill_species(n_worms);
+        scene.content.species.resize(n_worms, 0);
+        // snapshots taken before the genomes existed: all the worms have the base one
+        let base_genome = scene.params.base_genome();
+        scene
+            .content
+            .genomes
+            .resize(scene.content.bodies.len(), base_genome);
+        let initial_energy = scene.params.initial_energy();
+        scene
+            .content
+            .energy
+            .resize(scene.content.bodies.len(), initial_energy);
+        // and no lineage: they are the founders
+        scene.register_founders();
+        // nor spawner: the number of rewards was constant
+        if scene.spawner.capacity() == 0 {
+            let area = scene.area();
+            scene.spawner = Spawner::new(
+                &scene.params.spawning,
+                scene.content.rewards.len(),
+                area,
+                &mut scene.rng,
+            );
+        }
+        scene.build_indexes();
+        Ok(scene)
+    }
+
+    /// Gives a new id (without parents) to the worms that do not have one
+    fn register_founders(&mut self) {
+        while self.content.ids.len() < self.content.bodies.len() {
+            let id = self.lineage.add(Vec::new(), self.tick);
+            self.content.ids.push(id);
+        }
+    }
+
+    /// Creates the (empty) spatial indexes, sized according to the scene
+    fn build_indexes(&mut self) {
+        // cells big enough to keep the vision queries cheap, but never smaller than a movement
+        // (of any species)
+        let vision_distance = self
+            .params
+            .species
+            .iter()
+            .map(|species| species.stats.vision_distance)
+            .fold(self.params.stats.vision_distance, f32::max);
+        let body_cell = (self.largest_body_size() * 2.).max(vision_distance / 8.);
+        self.body_index = SpatialGrid::new(self.area(), body_cell);
+        self.reward_index = SpatialGrid::new(self.area(), vision_distance / 2.);
+        self.build_planner();
+        self.build_scent();
+    }
+
+    /// Creates an empty scent field, unless the current one already covers the scene
+    fn build_scent(&mut self) {
+        if let Some(params) = self.params.scent {
+            if !self.scent.covers(self.area(), &params) {
+                self.scent = ScentField::new(self.area(), &params);
+            }
+        }
+    }
+
+    /// Creates the planning grid with the static obstacles (only if a behavior uses it)
+    fn build_planner(&mut self) {
+        if self.params.planning.enabled() {
+            self.planner = PathPlanner::new(
+                self.area(),
+                &self.params.planning,
+                &self.params.obstacles.obstacles,
+                self.largest_body_size(),
+            );
         }
     }
 
-    pub fn worms(&self) -> impl Iterator<Item = (&WormBehavior, &WormBody)> {
+    /// Radius of the parts of the largest species
+    fn largest_body_size(&self) -> f32 {
+        self.params
+            .species
+            .iter()
+            .map(|species| species.body_size)
+            .fold(self.params.body_size, f32::max)
+    }
+
+    pub fn params(&self) -> &SceneParameters {
+        &self.params
+    }
+
+    /// Area covered by the scene, with the behavior of its borders
+    pub fn area(&self) -> Area {
+        Area::new(self.width, self.height, self.params.boundary)
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Events that happened during the last tick
+    pub fn events(&self) -> &[SimEvent] {
+        &self.events
+    }
+
+    /// Registers a callback receiving every event, at the end of the tick it happened in
+    pub fn add_observer(&mut self, observer: impl FnMut(&SimEvent) + Send + 'static) {
+        self.observers.push(Box::new(observer));
+    }
+
+    /// Adds a behavior the worms can take up (see Behavior), returning its id
+    /// The ids follow the order of registration: the worms saved with a behavior keep its id
+    pub fn register_behavior(&mut self, behavior: impl Behavior + 'static) -> BehaviorId {
+        self.registry.push(Arc::new(behavior));
+        self.registry.len() - 1
+    }
+
+    /// Registered behavior with the id, if any
+    pub fn behavior(&self, id: BehaviorId) -> Option<&dyn Behavior> {
+        self.registry.get(id).map(|behavior| behavior.as_ref())
+    }
+
+    /// Errors the registered behaviors ran into so far, with their names
+    pub fn behavior_errors(&self) -> impl Iterator<Item = (&str, String)> {
+        self.registry
+            .iter()
+            .filter_map(|behavior| Some((behavior.name(), behavior.error()?)))
+    }
+
+    /// Worms of the scene, with their species
+    pub fn worms(&self) -> impl Iterator<Item = (&WormBehavior, WormBody<'_>, &Species)> {
         self.content
             .behaviors
             .iter()
             .zip(self.content.bodies.iter())
+            .zip(self.content.species.iter())
+            .map(|((behavior, body), &species)| (behavior, body, &self.params.species[species]))
+    }
+
+    /// Species of the worms (the index of a species is its position in the list)
+    pub fn species(&self) -> &[Species] {
+        &self.params.species
+    }
+
+    /// Index of the species of each worm (in the same order as Scene::worms)
+    pub fn worm_species(&self) -> &[usize] {
+        &self.content.species
+    }
+
+    /// Genomes of the worms (in the same order as Scene::worms)
+    pub fn genomes(&self) -> &[Genome] {
+        &self.content.genomes
+    }
+
+    /// Unique ids of the worms (in the same order as Scene::worms)
+    pub fn worm_ids(&self) -> &[WormId] {
+        &self.content.ids
+    }
+
+    /// Log of all the worms created so far, with their ancestry
+    pub fn lineage(&self) -> &Lineage {
+        &self.lineage
+    }
+
+    /// Energy reserves of the worms (in the same order as Scene::worms)
+    pub fn energy(&self) -> &[f32] {
+        &self.content.energy
     }
 
     pub fn rewards(&self) -> &[Reward] {
         &self.content.rewards
     }
 
+    pub fn predators(&self) -> &[Predator] {
+        &self.content.predators
+    }
+
     pub fn resize(&mut self, width: usize, height: usize) {
+        if (width, height) == (self.width, self.height) {
+            return;
+        }
         self.width = width;
         self.height = height;
+        self.build_planner();
+        self.build_scent();
+    }
+
+    /// Scent field of the scene (None if the worms leave no scent)
+    pub fn scent(&self) -> Option<&ScentField> {
+        self.params.scent.map(|_| &self.scent)
     }
 
     pub fn execute(&mut self) {
+        self.tick += 1;
+        self.events.clear();
+        self.index_content();
         self.update_worms();
+        self.update_predators();
         self.update_rewards();
+        self.spawn_rewards();
+        self.update_scent();
+        for observer in self.observers.iter_mut() {
+            for event in &self.events {
+                observer(event);
+            }
+        }
+    }
+
+    /// Head of a worm in the scene (only asked for the worms with parts, never the removed ones)
+    fn head(&self, worm_id: usize) -> Point {
+        self.content
+            .bodies
+            .get(worm_id)
+            .head()
+            .expect("a worm in the scene has parts")
+    }
+
+    /// Rebuilds the spatial indexes of bodies and rewards from scratch
+    /// During the tick, they are only updated with the new positions, so they may hold
+    /// stale entries (harmless, as the movers check the exact positions)
+    fn index_content(&mut self) {
+        self.body_index.reset(self.area());
+        for (worm_id, body) in self.content.bodies.iter().enumerate() {
+            body.iter()
+                .for_each(|part| self.body_index.insert(worm_id, part));
+        }
+        self.reward_index.reset(self.area());
+        for (reward_id, reward) in self.content.rewards.iter().enumerate() {
+            self.reward_index.insert(reward_id, reward.position);
+        }
+        if self.params.planning.enabled() {
+            self.planner.clear();
+            for (body, &species) in self.content.bodies.iter().zip(&self.content.species) {
+                let body_size = self.params.species[species].body_size;
+                body.iter()
+                    .for_each(|part| self.planner.block(part, body_size));
+            }
+        }
+    }
+
+    /// Registers all the parts of a worm in the spatial index
+    fn index_body(&mut self, worm_id: usize) {
+        for part in self.content.bodies.get(worm_id).iter() {
+            self.body_index.insert(worm_id, part);
+        }
     }
 
     fn update_worms(&mut self) {
+        match self.params.update {
+            UpdateMode::Sequential => self.update_worms_sequentially(),
+            UpdateMode::Parallel => self.update_worms_in_parallel(),
+        }
+    }
+
+    fn update_worms_sequentially(&mut self) {
         for worm_id in 0..self.content.behaviors.len() {
             match self.content.behaviors[worm_id] {
                 WormBehavior::Alive(counter) => {
-                    if self.content.bodies[worm_id].full() {
+                    let split_size = self.content.genomes[worm_id].split_size;
+                    if self.content.bodies.get(worm_id).size() >= split_size
+                        && self.view().can_split(worm_id)
+                    {
                         self.content.behaviors[worm_id] = self.split_worm(worm_id);
+                    } else if let Some(id) = self.view().entered_behavior(worm_id) {
+                        self.content.behaviors[worm_id] = self.execute_custom(worm_id, id, 0);
                     } else {
                         self.content.behaviors[worm_id] = self.execute_alive(worm_id, counter)
                     }
                 }
 
                 WormBehavior::Dead(counter) => {
-                    if counter < self.params.expiration {
-                        self.content.behaviors[worm_id] = WormBehavior::Dead(counter + 1)
+                    self.content.behaviors[worm_id] = self.decay(worm_id, counter);
+                }
+
+                WormBehavior::Chasing => {
+                    if let Some(id) = self.view().entered_behavior(worm_id) {
+                        self.content.behaviors[worm_id] = self.execute_custom(worm_id, id, 0);
                     } else {
-                        self.content.behaviors[worm_id] = WormBehavior::Removed;
-                        self.content.bodies[worm_id].set_size(0)
+                        let result = self.execute_chasing(worm_id);
+                        self.content.behaviors[worm_id] = match result {
+                            WormBehavior::Chasing => self.execute_chasing(worm_id),
+                            _ => result,
+                        };
                     }
                 }
 
-                WormBehavior::Chasing => {
-                    let result = self.execute_chasing(worm_id);
-                    self.content.behaviors[worm_id] = match result {
-                        WormBehavior::Chasing => self.execute_chasing(worm_id),
-                        _ => result,
-                    };
+                WormBehavior::Custom(id, counter) => {
+                    self.content.behaviors[worm_id] = self.execute_custom(worm_id, id, counter);
                 }
 
                 WormBehavior::Removed => (),
@@ -136,152 +799,2410 @@ impl Scene {
         }
     }
 
-    /// Move the rewards in the scene
-    fn update_rewards(&mut self) {
-        for i in 0..self.content.reward_destination.len() {
-            let direction =
-                self.content.rewards[i].direction_to(self.content.reward_destination[i]);
+    /// Plans the moves of all the worms at once (in parallel) against the scene as it is,
+    /// then applies them in a random order (drawn from the generator of the scene)
+    fn update_worms_in_parallel(&mut self) {
+        // each worm draws from a generator of its own, whatever the thread planning its move
+        let seed = self.rng.gen::<u64>();
+        let mut order = (0..self.content.behaviors.len()).collect::<Vec<_>>();
+        order.shuffle(&mut self.rng);
+        let view = self.view();
+        let intents = (0..order.len())
+            .into_par_iter()
+            .map(|worm_id| view.plan(worm_id, &mut SimRng::seed_from_u64(seed ^ worm_id as u64)))
+            .collect::<Vec<_>>();
+        self.resolve(intents, &order);
+    }
 
-            let new_reward = self.content.rewards[i].copy(direction, self.params.body_size / 4.);
+    /// Applies the planned moves in the given order, holding back the worms losing a conflict
+    /// (they stay in place, one more tick in their behavior): a reward, or a worm to merge with,
+    /// goes to the closest of the worms heading to it, a new head cannot be put too close
+    /// to a head moved before, and a worm that moved cannot be merged with (its tail is no
+    /// longer where the chaser planned to reach it)
+    /// The splits come last, so that the slots they take have no pending move
+    fn resolve(&mut self, mut intents: Vec<Intent>, order: &[usize]) {
+        let claims = self.claims(&intents, order);
+        // the worms merged with: their planned moves no longer fit their bodies
+        let mut merged = vec![false; intents.len()];
+        // the worms whose planned moves were applied
+        let mut moved = vec![false; intents.len()];
+        let mut splits = Vec::new();
+        for &worm_id in order {
+            if merged[worm_id] {
+                // what is left of the worm stays in place
+                if let WormBehavior::Alive(counter) = self.content.behaviors[worm_id] {
+                    self.content.behaviors[worm_id] = self.hold_alive(worm_id, counter);
+                }
+                continue;
+            }
+            let turn = Turn {
+                claims: &claims,
+                merged: &merged,
+                moved: &moved,
+            };
+            let behavior = match std::mem::replace(&mut intents[worm_id], Intent::Idle) {
+                Intent::Idle => continue,
+                Intent::Split => {
+                    splits.push(worm_id);
+                    continue;
+                }
+                Intent::Decay(counter) => self.decay(worm_id, counter),
+                Intent::Alive(counter, result) => {
+                    if self.held_back(worm_id, &result, Hit::Meal, &turn) {
+                        self.hold_alive(worm_id, counter)
+                    } else {
+                        moved[worm_id] = true;
+                        self.apply_alive(worm_id, counter, result)
+                    }
+                }
+                Intent::Chasing(first, second) => {
+                    let mut behavior = WormBehavior::Chasing;
+                    for result in std::iter::once(first).chain(second) {
+                        let turn = Turn {
+                            claims: &claims,
+                            merged: &merged,
+                            moved: &moved,
+                        };
+                        if self.held_back(worm_id, &result, Hit::Merge, &turn) {
+                            break;
+                        }
+                        if let MovementResult::TargetHit(target_id, _) = result {
+                            merged[target_id] = true;
+                        }
+                        moved[worm_id] = true;
+                        behavior = self.apply_chasing(worm_id, result);
+                        if !matches!(behavior, WormBehavior::Chasing) {
+                            break;
+                        }
+                    }
+                    behavior
+                }
+                Intent::Custom(id, counter, result) => {
+                    let hit = match self.registry.get(id) {
+                        Some(behavior) if behavior.eats() => Hit::Meal,
+                        _ => Hit::Move,
+                    };
+                    if self.held_back(worm_id, &result, hit, &turn) {
+                        WormBehavior::Custom(id, counter + 1)
+                    } else {
+                        moved[worm_id] = true;
+                        self.apply_custom(worm_id, id, counter, result)
+                    }
+                }
+            };
+            self.content.behaviors[worm_id] = behavior;
+        }
+        splits.sort_unstable();
+        for worm_id in splits.into_iter().filter(|&worm_id| !merged[worm_id]) {
+            self.content.behaviors[worm_id] = self.split_worm(worm_id);
+        }
+        // from the last index down, as each removal moves the last reward
+        self.eaten_rewards.sort_unstable_by(|lhs, rhs| rhs.cmp(lhs));
+        self.eaten_rewards.dedup();
+        for reward_id in std::mem::take(&mut self.eaten_rewards) {
+            self.remove_reward(reward_id);
+        }
+    }
 
-            let is_valid = new_reward.x <= self.width as f32
-                && new_reward.y <= self.height as f32
-                && self.content.reward_destination[i].distance_to(new_reward)
-                    >= self.params.body_size;
+    /// Winners of the rewards and worms hit by the planned moves: the closest worms
+    /// (the first ones in the order, if tied)
+    fn claims(&self, intents: &[Intent], order: &[usize]) -> Claims {
+        let area = self.area();
+        let mut claims = Claims::default();
+        for &worm_id in order {
+            // (the removed worms have no head)
+            let head = || self.head(worm_id);
+            match &intents[worm_id] {
+                Intent::Alive(_, MovementResult::TargetHit(reward_id, _)) => {
+                    let distance = area.distance(head(), self.content.rewards[*reward_id].position);
+                    Claims::claim(&mut claims.meals, *reward_id, worm_id, distance);
+                }
+                Intent::Custom(id, _, MovementResult::TargetHit(reward_id, _))
+                    if self
+                        .registry
+                        .get(*id)
+                        .is_some_and(|behavior| behavior.eats()) =>
+                {
+                    let distance = area.distance(head(), self.content.rewards[*reward_id].position);
+                    Claims::claim(&mut claims.meals, *reward_id, worm_id, distance);
+                }
+                Intent::Chasing(MovementResult::TargetHit(target_id, _), _)
+                | Intent::Chasing(_, Some(MovementResult::TargetHit(target_id, _))) => {
+                    let distance = area.distance(head(), self.head(*target_id));
+                    Claims::claim(&mut claims.merges, *target_id, worm_id, distance);
+                }
+                _ => (),
+            }
+        }
+        claims
+    }
 
-            self.content.rewards[i] = is_valid
-                .then_some(new_reward)
-                .unwrap_or(Point::rand(self.width, self.height));
+    /// Checks if the planned movement of the worm conflicts with the ones applied before
+    fn held_back(&self, worm_id: usize, result: &MovementResult, hit: Hit, turn: &Turn) -> bool {
+        match (result, hit) {
+            (MovementResult::None, _) => false,
+            (&MovementResult::TargetHit(reward_id, new_head), Hit::Meal) => {
+                turn.claims.meals.get(&reward_id).map(|&(winner, _)| winner) != Some(worm_id)
+                    || self.head_taken(worm_id, new_head)
+            }
+            // the target may have died, merged, or moved since the move was planned
+            (&MovementResult::TargetHit(target_id, _), Hit::Merge) => {
+                turn.claims
+                    .merges
+                    .get(&target_id)
+                    .map(|&(winner, _)| winner)
+                    != Some(worm_id)
+                    || turn.merged[target_id]
+                    || turn.moved[target_id]
+                    || !matches!(self.content.behaviors[target_id], WormBehavior::Alive(_))
+            }
+            (&MovementResult::TargetHit(_, new_head), Hit::Move)
+            | (&MovementResult::TargetMiss(new_head, _), _) => self.head_taken(worm_id, new_head),
         }
     }
 
-    fn execute_alive(&mut self, worm_id: usize, counter: usize) -> WormBehavior {
-        let mover = AliveWormMover {
-            details: &self.get_movement_details(worm_id),
-            rewards: &self.content.rewards,
-            bodies: &self.content.bodies,
+    /// Checks if a new head of the worm is too close to the head of another worm
+    /// (only the heads can have moved: the rest of the bodies was avoided when planning)
+    fn head_taken(&self, worm_id: usize, new_head: Point) -> bool {
+        let area = self.area();
+        let part_sizes = self.view().part_sizes();
+        let radius = part_sizes.radius(worm_id);
+        self.body_index
+            .query(new_head, radius + part_sizes.largest())
+            .into_iter()
+            .filter(|&other| other != worm_id)
+            // (the removed worms have no head)
+            .filter_map(|other| Some((other, self.content.bodies.get(other).head()?)))
+            .any(|(other, head)| {
+                area.distance(head, new_head) < radius + part_sizes.radius(other) - 0.01
+            })
+    }
+
+    /// One more tick for a dead worm, taken out of the scene once expired
+    fn decay(&mut self, worm_id: usize, counter: usize) -> WormBehavior {
+        if counter < self.params.expiration {
+            WormBehavior::Dead(counter + 1)
+        } else {
+            self.remove_worm(worm_id)
+        }
+    }
+
+    /// Takes the worm out of the scene
+    fn remove_worm(&mut self, worm_id: usize) -> WormBehavior {
+        self.events.push(SimEvent::Removed {
+            tick: self.tick,
+            worm_id,
+            worm: self.content.ids[worm_id],
+            position: self.head(worm_id),
+        });
+        self.content.bodies.set_size(worm_id, 0);
+        self.lineage.end(self.content.ids[worm_id], self.tick);
+        WormBehavior::Removed
+    }
+
+    /// Read-only view of the scene, used to plan the movements
+    fn view(&self) -> SceneView<'_> {
+        SceneView {
+            params: &self.params,
+            content: &self.content,
+            area: self.area(),
+            registry: &self.registry,
+            scent: &self.scent,
+            planner: &self.planner,
+            body_index: &self.body_index,
+            reward_index: &self.reward_index,
+        }
+    }
+
+    /// Same as view, along with the random generator (borrowed apart)
+    fn view_with_rng(&mut self) -> (SceneView<'_>, &mut SimRng) {
+        let area = self.area();
+        let Self {
+            params,
+            content,
+            registry,
+            scent,
+            planner,
+            body_index,
+            reward_index,
+            rng,
+            ..
+        } = self;
+        let view = SceneView {
+            params,
+            content,
+            area,
+            registry,
+            scent,
+            planner,
+            body_index,
+            reward_index,
         };
+        (view, rng)
+    }
 
-        match mover.execute_movement(self.params.body_size * 2.) {
-            MovementResult::TargetHit(target_index, new_head) => {
-                self.content.rewards[target_index] = Reward::rand(self.width, self.height);
-                self.content.bodies[worm_id].grow(new_head);
-                WormBehavior::Alive(0)
+    /// Moves the worm with the mover of its registered behavior, then applies its transition
+    fn execute_custom(&mut self, worm_id: usize, id: BehaviorId, counter: usize) -> WormBehavior {
+        let (view, rng) = self.view_with_rng();
+        let result = view.custom_movement(worm_id, id, rng);
+        self.apply_custom(worm_id, id, counter, result)
+    }
+
+    /// The body of the worm follows the new head of its registered behavior (eating the reward
+    /// hit, if the behavior eats), then the behavior transitions
+    /// (a worm with an unknown behavior, saved with other registered ones, gets back to life)
+    fn apply_custom(
+        &mut self,
+        worm_id: usize,
+        id: BehaviorId,
+        counter: usize,
+        result: MovementResult,
+    ) -> WormBehavior {
+        let Some(behavior) = self.registry.get(id).cloned() else {
+            return WormBehavior::Alive(0);
+        };
+        let moved = match result {
+            MovementResult::TargetHit(target_index, new_head) if behavior.eats() => {
+                self.eat_reward(worm_id, target_index, new_head);
+                None
             }
-            MovementResult::TargetMiss(new_head, destination) => {
-                self.content.bodies[worm_id].roll(new_head, destination);
-                if counter < self.params.starvation / self.content.bodies[worm_id].size() {
-                    return WormBehavior::Alive(counter + 1);
-                }
-                WormBehavior::Chasing
+            MovementResult::TargetHit(_, new_head) => Some((new_head, new_head)),
+            MovementResult::TargetMiss(new_head, destination) => Some((new_head, destination)),
+            MovementResult::None => None,
+        };
+        if let Some((new_head, destination)) = moved {
+            self.content.bodies.roll(worm_id, new_head, destination);
+            self.body_index.insert(worm_id, new_head);
+            self.leave_scent(new_head, false);
+            self.spend_energy(worm_id);
+        }
+        let transition = behavior.transition(&self.view().worm_view(worm_id), counter, &result);
+        if behavior.error().is_some() && !self.failed_behaviors.contains(&id) {
+            self.failed_behaviors.push(id);
+            self.events.push(SimEvent::BehaviorFailed {
+                tick: self.tick,
+                worm_id,
+                worm: self.content.ids[worm_id],
+                behavior: id,
+                position: self.head(worm_id),
+            });
+        }
+        match transition {
+            Transition::Stay => WormBehavior::Custom(id, counter + 1),
+            Transition::Switch(WormBehavior::Dead(_)) => self.kill_worm(worm_id),
+            Transition::Switch(WormBehavior::Removed) => self.remove_worm(worm_id),
+            Transition::Switch(next) => next,
+        }
+    }
+
+    /// Moves the predators, biting the worms they reach
+    /// The starving ones leave the scene, the well fed ones reproduce
+    fn update_predators(&mut self) {
+        let params = self.params.predators;
+        let n_predators = self.content.predators.len();
+        for predator_id in 0..n_predators {
+            self.execute_predator(predator_id);
+            let predator = &mut self.content.predators[predator_id];
+            if predator.can_reproduce(&params) {
+                predator.eaten = 0;
+                let position = predator.position;
+                let destination = self.area().rand_point(&mut self.rng);
+                self.content
+                    .predators
+                    .push(Predator::new(position, destination));
             }
-            MovementResult::None => WormBehavior::Dead(0),
         }
+        self.content
+            .predators
+            .retain(|predator| !predator.starving(&params));
     }
 
-    fn execute_chasing(&mut self, worm_id: usize) -> WormBehavior {
-        let mover = ChasingWormMover {
-            details: &self.get_movement_details(worm_id),
-            rewards: &self.content.rewards,
+    fn execute_predator(&mut self, predator_id: usize) {
+        let predator = self.content.predators[predator_id];
+        let area = self.area();
+        let mover = PredatorMover {
+            details: &MovementDetails {
+                origin: predator.position,
+                chosen_destination: predator.destination,
+                stats: self.params.predators.stats,
+                n_directions: self.params.n_directions,
+                area,
+                model: self.params.movement,
+                heading: None,
+            },
             bodies: &self.content.bodies,
             behaviors: &self.content.behaviors,
+            obstacles: &self.params.obstacles,
+            body_index: &self.body_index,
         };
 
-        match mover.execute_movement(self.params.body_size * 2.) {
-            MovementResult::TargetHit(target_index, _) => {
-                self.merge_worms(worm_id, target_index);
-                WormBehavior::Alive(0)
+        // a predator is as wide as a worm part
+        let radius = self.params.body_size;
+        let result = mover.execute_movement(self.params.predators.speed, radius, &mut self.rng);
+        let predator = &mut self.content.predators[predator_id];
+        predator.hunger += 1;
+        match result {
+            MovementResult::TargetHit(worm_id, position) => {
+                predator.position = position;
+                predator.hunger = 0;
+                self.bite_worm(predator_id, worm_id);
             }
-            MovementResult::TargetMiss(new_head, destination) => {
-                self.content.bodies[worm_id].roll(new_head, destination);
-                WormBehavior::Chasing
+            MovementResult::TargetMiss(position, destination) => {
+                predator.position = position;
+                predator.destination = destination;
             }
-            MovementResult::None => WormBehavior::Dead(0),
+            // stuck against an obstacle: try another way
+            MovementResult::None => predator.destination = area.rand_point(&mut self.rng),
         }
     }
 
-    fn get_movement_details(&self, worm_id: usize) -> MovementDetails {
-        MovementDetails {
-            origin: *self.content.bodies[worm_id].head(),
-            chosen_destination: self.content.bodies[worm_id].target,
-            stats: self.stats,
-            width: self.width,
-            height: self.height,
+    /// The predator eats parts from the tail of the worm (which leaves the scene if none is left)
+    fn bite_worm(&mut self, predator_id: usize, worm_id: usize) {
+        let size = self.content.bodies.get(worm_id).size();
+        let parts = self.params.predators.bite.min(size);
+        self.content.bodies.set_size(worm_id, size - parts);
+        // the worm loses the share of its energy held by the parts
+        if let Metabolism::Energy(_) = self.params.metabolism {
+            self.content.energy[worm_id] *= (size - parts) as f32 / size.max(1) as f32;
+        }
+        let predator = &mut self.content.predators[predator_id];
+        predator.eaten += parts;
+        self.events.push(SimEvent::Bitten {
+            tick: self.tick,
+            worm_id,
+            worm: self.content.ids[worm_id],
+            predator_id,
+            parts,
+            position: predator.position,
+        });
+        if parts == size {
+            self.events.push(SimEvent::Removed {
+                tick: self.tick,
+                worm_id,
+                worm: self.content.ids[worm_id],
+                position: predator.position,
+            });
+            self.content.behaviors[worm_id] = WormBehavior::Removed;
+            self.lineage.end(self.content.ids[worm_id], self.tick);
         }
     }
 
-    /// Return the index of the first worm having the Removed behavior
-    /// Creates a new worm if none is found
-    fn next_removed_index(&mut self) -> usize {
-        self.content
-            .behaviors
-            .par_iter()
-            .position_any(|behavior| matches!(behavior, WormBehavior::Removed))
-            .unwrap_or_else(|| {
-                self.content.bodies.push(WormBody::default());
-                self.content.behaviors.push(WormBehavior::Removed);
-                self.content.bodies.len() - 1
-            })
+    /// Spreads and evaporates the scent
+    fn update_scent(&mut self) {
+        if let Some(params) = self.params.scent {
+            let area = self.area();
+            self.scent.update(area, &params);
+        }
     }
 
-    fn split_worm(&mut self, worm_id: usize) -> WormBehavior {
-        // While the worm has a size that can be split
-        while self.content.bodies[worm_id].size() >= self.params.worm_size * 2 {
-            // Calculate the new size after the split
-            let size_after_split = self.content.bodies[worm_id].size() - self.params.worm_size;
-            // Get the first index of a content table entry that is free (i.e has a removed worm)
-            let free_index = self.next_removed_index();
-            // activate the worm at the found free_index
-            self.content.behaviors[free_index] = WormBehavior::Alive(0);
-            // Copy all the desired parts to the body in the free_index
-            self.content.bodies[worm_id]
-                .iter()
-                .rev()
-                .take(self.params.worm_size)
-                .cloned()
-                .collect::<Vec<_>>()
-                .iter()
-                .fold(&mut self.content.bodies[free_index], |acc, &part| {
-                    acc.grow(part);
-                    acc
+    /// Leaves scent where the worm moved (more if it just ate)
+    fn leave_scent(&mut self, position: Point, meal: bool) {
+        if let Some(params) = self.params.scent {
+            let amount = params.deposit + if meal { params.meal_deposit } else { 0. };
+            let area = self.area();
+            self.scent.deposit(area, position, amount);
+        }
+    }
+
+    /// Move the rewards in the scene
+    fn update_rewards(&mut self) {
+        let area = self.area();
+        for i in 0..self.content.rewards.len() {
+            let Reward {
+                kind,
+                position,
+                destination,
+            } = self.content.rewards[i];
+            let speed = self.params.reward_types.get(kind).speed;
+            // static rewards stay where they spawned
+            if speed <= 0. {
+                continue;
+            }
+            let angle = area.angle(position, destination);
+            let heading = match self.params.movement {
+                MovementModel::Discrete => {
+                    Direction::quantize(angle, self.params.n_directions).to_radians()
+                }
+                MovementModel::Continuous { .. } => angle,
+            };
+
+            // a reward reaching its destination (or leaving a bounded area, or running into
+            // an obstacle) is replaced
+            let new_position = area
+                .place(position.project(heading, self.params.reward_speed * speed))
+                .filter(|&new_position| {
+                    area.distance(destination, new_position) >= self.params.body_size
+                        && !self.params.obstacles.covers(new_position)
                 });
+
+            match new_position {
+                Some(new_position) => self.content.rewards[i].position = new_position,
+                None => self.respawn_reward(i),
+            }
+        }
+    }
+
+    /// Spawns the missing rewards (or withers the ones in excess), following the seasons
+    /// and the regrowth rate
+    fn spawn_rewards(&mut self) {
+        let n_rewards = self.content.rewards.len();
+        let adjustment = self
+            .spawner
+            .adjustment(&self.params.spawning, n_rewards, self.tick);
+        if adjustment < 0 {
+            self.content
+                .rewards
+                .truncate(n_rewards.saturating_sub(adjustment.unsigned_abs()));
+        }
+        for _ in 0..adjustment.max(0) {
+            let area = self.area();
+            let position = self.spawner.position(
+                &self.params.spawning,
+                &self.params.obstacles,
+                area,
+                &mut self.rng,
+            );
+            let destination = area.rand_point(&mut self.rng);
+            let kind = self.params.reward_types.sample_kind(&mut self.rng);
+            self.content.rewards.push(Reward {
+                kind,
+                position,
+                destination,
+            });
+        }
+    }
+
+    /// Removes an eaten reward, until it regrows
+    fn remove_reward(&mut self, reward_id: usize) {
+        self.content.rewards.swap_remove(reward_id);
+        // the last reward took its place in the table
+        if let Some(moved) = self.content.rewards.get(reward_id) {
+            self.reward_index.insert(reward_id, moved.position);
+        }
+    }
+
+    /// Replaces the reward by a new one (of a random kind) at a spawn position,
+    /// keeping its destination
+    fn respawn_reward(&mut self, reward_id: usize) {
+        let position = self.spawner.position(
+            &self.params.spawning,
+            &self.params.obstacles,
+            self.area(),
+            &mut self.rng,
+        );
+        let kind = self.params.reward_types.sample_kind(&mut self.rng);
+        let reward = &mut self.content.rewards[reward_id];
+        reward.position = position;
+        reward.kind = kind;
+    }
+
+    fn execute_alive(&mut self, worm_id: usize, counter: usize) -> WormBehavior {
+        let (view, rng) = self.view_with_rng();
+        let result = view.alive_movement(worm_id, counter, rng);
+        self.apply_alive(worm_id, counter, result)
+    }
+
+    /// The worm eats the reward hit, or its body follows the new head
+    /// (it starts chasing when starving, and dies when it cannot move)
+    fn apply_alive(
+        &mut self,
+        worm_id: usize,
+        counter: usize,
+        result: MovementResult,
+    ) -> WormBehavior {
+        match result {
+            // only a reward the worm grows with feeds it (a harmful one leaves it as hungry)
+            MovementResult::TargetHit(target_index, new_head) => {
+                if self.eat_reward(worm_id, target_index, new_head) > 0 {
+                    WormBehavior::Alive(0)
+                } else {
+                    self.go_hungry(worm_id, counter)
+                }
+            }
+            MovementResult::TargetMiss(new_head, destination) => {
+                self.content.bodies.roll(worm_id, new_head, destination);
+                self.body_index.insert(worm_id, new_head);
+                self.leave_scent(new_head, false);
+                self.spend_energy(worm_id);
+                self.go_hungry(worm_id, counter)
+            }
+            MovementResult::None => self.kill_worm(worm_id),
+        }
+    }
+
+    /// The alive worm stays in place (held back in a parallel update), spending the energy
+    /// of a movement all the same
+    fn hold_alive(&mut self, worm_id: usize, counter: usize) -> WormBehavior {
+        self.spend_energy(worm_id);
+        self.go_hungry(worm_id, counter)
+    }
+
+    /// One more tick without eating: the worm starts chasing when starving
+    fn go_hungry(&mut self, worm_id: usize, counter: usize) -> WormBehavior {
+        if !self.starving(worm_id, counter) {
+            return WormBehavior::Alive(counter + 1);
+        }
+        self.events.push(SimEvent::Starved {
+            tick: self.tick,
+            worm_id,
+            worm: self.content.ids[worm_id],
+            position: self.head(worm_id),
+        });
+        WormBehavior::Chasing
+    }
+
+    /// The worm moves onto the reward and eats it, growing (or shrinking) accordingly
+    /// Return the number of parts grown (negative for the parts lost)
+    fn eat_reward(&mut self, worm_id: usize, target_index: usize, new_head: Point) -> i32 {
+        let kind = self.content.rewards[target_index].kind;
+        let n_rewards = self.content.rewards.len() - self.eaten_rewards.len();
+        if self
+            .spawner
+            .respawns(&self.params.spawning, n_rewards, self.tick)
+        {
+            self.respawn_reward(target_index);
+            self.reward_index
+                .insert(target_index, self.content.rewards[target_index].position);
+        } else if self.params.update == UpdateMode::Parallel {
+            self.eaten_rewards.push(target_index);
+        } else {
+            self.remove_reward(target_index);
+        }
+        self.events.push(SimEvent::RewardEaten {
+            tick: self.tick,
+            worm_id,
+            worm: self.content.ids[worm_id],
+            reward_id: target_index,
+            kind,
+            position: new_head,
+        });
+        self.spend_energy(worm_id);
+        let growth = self.feed(worm_id, kind);
+        let bodies = &mut self.content.bodies;
+        if growth > 0 {
+            // the extra parts are stacked on the new head: they only spread out as the worm
+            // moves on, its tail staying in place until they are all unstacked
+            (0..growth).for_each(|_| bodies.grow(worm_id, new_head));
+        } else {
+            bodies.roll(worm_id, new_head, new_head);
+            // a harmful reward takes parts from the tail (never the head)
+            let lost = growth.unsigned_abs() as usize;
+            let size = bodies.get(worm_id).size().saturating_sub(lost).max(1);
+            bodies.set_size(worm_id, size);
+        }
+        self.body_index.insert(worm_id, new_head);
+        self.leave_scent(new_head, true);
+        growth
+    }
+
+    fn execute_chasing(&mut self, worm_id: usize) -> WormBehavior {
+        let (view, rng) = self.view_with_rng();
+        let details = view.movement_details(worm_id);
+        let result = view.chasing_movement(worm_id, &details, rng);
+        self.apply_chasing(worm_id, result)
+    }
+
+    /// The worm merges with the worm hit, or its body follows the new head
+    /// (it dies when it cannot move)
+    fn apply_chasing(&mut self, worm_id: usize, result: MovementResult) -> WormBehavior {
+        match result {
+            MovementResult::TargetHit(target_index, _) => {
+                self.merge_worms(worm_id, target_index);
+                WormBehavior::Alive(0)
+            }
+            MovementResult::TargetMiss(new_head, destination) => {
+                self.content.bodies.roll(worm_id, new_head, destination);
+                self.body_index.insert(worm_id, new_head);
+                self.leave_scent(new_head, false);
+                self.spend_energy(worm_id);
+                WormBehavior::Chasing
+            }
+            MovementResult::None => self.kill_worm(worm_id),
+        }
+    }
+
+    /// Checks if the worm went hungry for too long (counter: ticks since it last ate)
+    fn starving(&self, worm_id: usize, counter: usize) -> bool {
+        self.params.metabolism.starving(
+            &self.content.genomes[worm_id],
+            self.content.bodies.get(worm_id).size(),
+            self.content.energy[worm_id],
+            counter,
+        )
+    }
+
+    /// Charges the energy of a movement to the worm (an empty reserve stays empty: a starving
+    /// worm owes nothing once it merges back)
+    fn spend_energy(&mut self, worm_id: usize) {
+        if let Metabolism::Energy(energy) = self.params.metabolism {
+            let cost = energy.movement_cost(
+                self.content.bodies.get(worm_id).size(),
+                self.content.genomes[worm_id].speed,
+            );
+            let reserve = &mut self.content.energy[worm_id];
+            *reserve = (*reserve - cost).max(0.);
+        }
+    }
+
+    /// Gives the energy of a reward to the worm, returning the number of parts it grows
+    /// (negative for the parts it loses)
+    fn feed(&mut self, worm_id: usize, kind: RewardKind) -> i32 {
+        let reward_type = self.params.reward_types.get(kind);
+        match self.params.metabolism {
+            Metabolism::Counter => reward_type.growth,
+            Metabolism::Energy(energy) => {
+                let reserve = &mut self.content.energy[worm_id];
+                *reserve = (*reserve + energy.per_reward * reward_type.value).max(0.);
+                if reward_type.growth <= 0 {
+                    return reward_type.growth;
+                }
+                // each part grown has its cost
+                let affordable = (*reserve / energy.growth_cost) as i32;
+                let growth = reward_type.growth.min(affordable);
+                *reserve -= energy.growth_cost * growth as f32;
+                growth
+            }
+        }
+    }
+
+    fn species_of(&self, worm_id: usize) -> &Species {
+        &self.params.species[self.content.species[worm_id]]
+    }
+
+    fn kill_worm(&mut self, worm_id: usize) -> WormBehavior {
+        self.events.push(SimEvent::Died {
+            tick: self.tick,
+            worm_id,
+            worm: self.content.ids[worm_id],
+            position: self.head(worm_id),
+        });
+        WormBehavior::Dead(0)
+    }
+
+    /// Return the index of the first worm having the Removed behavior
+    /// Creates a new worm if none is found
+    fn next_removed_index(&mut self) -> usize {
+        self.content
+            .behaviors
+            .par_iter()
+            .position_first(|behavior| matches!(behavior, WormBehavior::Removed))
+            .unwrap_or_else(|| {
+                self.content.bodies.push_empty();
+                self.content.genomes.push(self.params.base_genome());
+                self.content.energy.push(0.);
+                self.content.species.push(0);
+                // placeholder id, replaced when the worm is activated
+                self.content.ids.push(WormId::MAX);
+                self.content.behaviors.push(WormBehavior::Removed);
+                self.content.bodies.len() - 1
+            })
+    }
+
+    fn split_worm(&mut self, worm_id: usize) -> WormBehavior {
+        let worm_size = self.species_of(worm_id).worm_size;
+        // While the worm has a size that can be split
+        while self.content.bodies.get(worm_id).size() >= worm_size * 2
+            && self.view().can_split(worm_id)
+        {
+            // Calculate the new size after the split
+            let size_after_split = self.content.bodies.get(worm_id).size() - worm_size;
+            // Get the first index of a content table entry that is free (i.e has a removed worm)
+            let free_index = self.next_removed_index();
+            // activate the worm at the found free_index, inheriting the species
+            // and the (mutated) genome
+            self.content.behaviors[free_index] = WormBehavior::Alive(0);
+            self.content.species[free_index] = self.content.species[worm_id];
+            self.content.genomes[free_index] = self.content.genomes[worm_id].mutate(
+                &mut self.rng,
+                self.params.mutation,
+                worm_size * 2..=self.params.max_size,
+            );
+            self.content.ids[free_index] =
+                self.lineage.add(vec![self.content.ids[worm_id]], self.tick);
+            // the energy left after the split is shared according to the number of parts
+            if let Metabolism::Energy(energy) = self.params.metabolism {
+                let reserve = self.content.energy[worm_id] - energy.split_cost;
+                let size = self.content.bodies.get(worm_id).size() as f32;
+                let offspring_share = reserve * worm_size as f32 / size;
+                self.content.energy[free_index] = offspring_share;
+                self.content.energy[worm_id] = reserve - offspring_share;
+            }
+            // Copy all the desired parts to the body in the free_index
+            let parts = self
+                .content
+                .bodies
+                .get(worm_id)
+                .iter()
+                .rev()
+                .take(worm_size)
+                .collect::<Vec<_>>();
+            for part in parts {
+                self.content.bodies.grow(free_index, part);
+            }
+            self.index_body(free_index);
+            self.events.push(SimEvent::Split {
+                tick: self.tick,
+                worm_id,
+                worm: self.content.ids[worm_id],
+                offspring_id: free_index,
+                offspring: self.content.ids[free_index],
+                position: self.head(free_index),
+            });
             // Reduce the size of the worm after the split
-            self.content.bodies[worm_id].set_size(size_after_split);
+            self.content.bodies.set_size(worm_id, size_after_split);
         }
         WormBehavior::Alive(0)
     }
 
     fn merge_worms(&mut self, worm_id: usize, target_id: usize) {
         // Remove the head of the worm
-        self.content.bodies[worm_id].shrink(1);
+        self.content.bodies.shrink(worm_id, 1);
         // Calculate the gap between the head of the worm and the tail of the target worm
-        let diff = *self.content.bodies[target_id].tail() - *self.content.bodies[worm_id].head();
-        // Align the rest of worm body to the 'target' worm body
-        self.content.bodies[worm_id].shift(diff);
+        // (a worm of a single part has nothing left to align)
+        let area = self.area();
+        let (head, tail) = (
+            self.content.bodies.get(worm_id).head(),
+            self.content.bodies.get(target_id).tail(),
+        );
+        if let (Some(head), Some(tail)) = (head, tail) {
+            let diff = area.offset(head, tail);
+            // Align the rest of worm body to the 'target' worm body (keeping it inside a torus)
+            self.content
+                .bodies
+                .transform(worm_id, |part| area.wrap(part + diff));
+        }
         // Store the original size of the worm
-        let original_worm_size = self.content.bodies[worm_id].size();
+        let original_worm_size = self.content.bodies.get(worm_id).size();
 
         // Copy all the parts that fit to the original worm
-        self.content.bodies[target_id]
+        let parts = self
+            .content
+            .bodies
+            .get(target_id)
             .iter()
             .rev()
-            .take(self.content.bodies[worm_id].available_space())
-            .cloned()
-            .collect::<Vec<_>>()
-            .iter()
-            .fold(&mut self.content.bodies[worm_id], |acc, &part| {
-                acc.grow(part);
-                acc
-            });
+            .take(self.content.bodies.available_space(worm_id))
+            .collect::<Vec<_>>();
+        for part in parts {
+            self.content.bodies.grow(worm_id, part);
+        }
+
+        // The whole body was shifted: index it again
+        self.index_body(worm_id);
 
         // Get the new size of the target worm (subtracting the transfered parts)
-        let removed = self.content.bodies[worm_id].size() - original_worm_size;
-        let target_worm_size = self.content.bodies[target_id].size() - removed;
+        let removed = self.content.bodies.get(worm_id).size() - original_worm_size;
+        let target_worm_size = self.content.bodies.get(target_id).size() - removed;
 
         // Remove the copied parts from the 'target' by reducing its size
-        self.content.bodies[target_id].set_size(target_worm_size);
+        // (a target left without parts no longer has a head: where it was is kept for the event)
+        let target_head = self.head(target_id);
+        self.content.bodies.set_size(target_id, target_worm_size);
+        // along with their share of its energy
+        if let Metabolism::Energy(_) = self.params.metabolism {
+            let transfered = self.content.energy[target_id] * removed as f32
+                / (target_worm_size + removed).max(1) as f32;
+            self.content.energy[target_id] -= transfered;
+            self.content.energy[worm_id] += transfered;
+        }
+        // The genome of the worm takes after the parts it is made of
+        self.content.genomes[worm_id] = self.content.genomes[worm_id].combine(
+            original_worm_size,
+            &self.content.genomes[target_id],
+            removed,
+        );
+        // The merged worm is a new one, descending from both
+        let (worm_uid, target_uid) = (self.content.ids[worm_id], self.content.ids[target_id]);
+        self.lineage.end(worm_uid, self.tick);
+        self.content.ids[worm_id] = self.lineage.add(vec![worm_uid, target_uid], self.tick);
+        self.events.push(SimEvent::Merged {
+            tick: self.tick,
+            worm_id,
+            worm: self.content.ids[worm_id],
+            target_id,
+            target: target_uid,
+            position: self.head(worm_id),
+        });
         if target_worm_size == 0 {
-            self.content.behaviors[target_id] = WormBehavior::Removed
+            self.events.push(SimEvent::Removed {
+                tick: self.tick,
+                worm_id: target_id,
+                worm: target_uid,
+                position: target_head,
+            });
+            self.content.behaviors[target_id] = WormBehavior::Removed;
+            self.lineage.end(target_uid, self.tick);
+        }
+    }
+}
+
+/// What a worm does in a parallel update, planned against the scene as it was at the start
+/// of the tick
+enum Intent {
+    /// Nothing (a removed worm)
+    Idle,
+    /// One more tick dead (counter: ticks dead so far)
+    Decay(usize),
+    /// The worm splits instead of moving
+    Split,
+    /// Movement of an 'Alive' worm (counter: ticks since it last ate)
+    Alive(usize, MovementResult),
+    /// The two movements of a 'Chasing' worm (the second one, planned from the end of the
+    /// first, only if the first missed its target)
+    Chasing(MovementResult, Option<MovementResult>),
+    /// Movement of a worm with a registered behavior (its id and counter)
+    Custom(BehaviorId, usize, MovementResult),
+}
+
+/// What the target hit by a planned movement is, for the conflicts over it
+#[derive(Clone, Copy)]
+enum Hit {
+    /// A reward, eaten by a single worm
+    Meal,
+    /// The tail of a worm, merged with by a single worm
+    Merge,
+    /// A point the worm just moves onto
+    Move,
+}
+
+/// What the planned movement of a worm is checked against, when its turn comes
+struct Turn<'a> {
+    claims: &'a Claims,
+    /// The worms merged with (so far)
+    merged: &'a [bool],
+    /// The worms whose planned moves were applied (so far)
+    moved: &'a [bool],
+}
+
+/// Worm getting each reward (meals) and each worm to merge with (merges) hit by the planned
+/// movements, with its distance to the target
+#[derive(Default)]
+struct Claims {
+    meals: HashMap<usize, (usize, f32)>,
+    merges: HashMap<usize, (usize, f32)>,
+}
+
+impl Claims {
+    /// Gives the target to the worm if it is closer than the one holding it
+    fn claim(
+        claims: &mut HashMap<usize, (usize, f32)>,
+        target: usize,
+        worm_id: usize,
+        distance: f32,
+    ) {
+        let holder = claims.entry(target).or_insert((worm_id, distance));
+        if distance < holder.1 {
+            *holder = (worm_id, distance);
+        }
+    }
+}
+
+/// Read-only view of the scene: what the worms plan their movements with
+/// (shared by the threads of a parallel update, leaving out the observers)
+struct SceneView<'a> {
+    params: &'a SceneParameters,
+    content: &'a SceneContent,
+    area: Area,
+    registry: &'a [Arc<dyn Behavior>],
+    scent: &'a ScentField,
+    planner: &'a PathPlanner,
+    body_index: &'a SpatialGrid,
+    reward_index: &'a SpatialGrid,
+}
+
+impl<'a> SceneView<'a> {
+    /// Plans what the worm does in a parallel update (with a random generator of its own)
+    fn plan(&self, worm_id: usize, rng: &mut SimRng) -> Intent {
+        match self.content.behaviors[worm_id] {
+            WormBehavior::Alive(counter) => {
+                let split_size = self.content.genomes[worm_id].split_size;
+                if self.content.bodies.get(worm_id).size() >= split_size && self.can_split(worm_id)
+                {
+                    Intent::Split
+                } else if let Some(id) = self.entered_behavior(worm_id) {
+                    Intent::Custom(id, 0, self.custom_movement(worm_id, id, rng))
+                } else {
+                    Intent::Alive(counter, self.alive_movement(worm_id, counter, rng))
+                }
+            }
+            WormBehavior::Dead(counter) => Intent::Decay(counter),
+            WormBehavior::Chasing => {
+                if let Some(id) = self.entered_behavior(worm_id) {
+                    Intent::Custom(id, 0, self.custom_movement(worm_id, id, rng))
+                } else {
+                    let details = self.movement_details(worm_id);
+                    let first = self.chasing_movement(worm_id, &details, rng);
+                    let second = match first {
+                        MovementResult::TargetMiss(new_head, destination) => {
+                            let details = self.moved_details(&details, new_head, destination);
+                            Some(self.chasing_movement(worm_id, &details, rng))
+                        }
+                        _ => None,
+                    };
+                    Intent::Chasing(first, second)
+                }
+            }
+            WormBehavior::Custom(id, counter) => {
+                Intent::Custom(id, counter, self.custom_movement(worm_id, id, rng))
+            }
+            WormBehavior::Removed => Intent::Idle,
+        }
+    }
+
+    fn alive_movement(&self, worm_id: usize, counter: usize, rng: &mut SimRng) -> MovementResult {
+        let step = self.step(worm_id);
+        let hunger = self.hunger(worm_id, counter);
+        let size = self.content.bodies.get(worm_id).size() as f32
+            / self.content.genomes[worm_id].split_size.max(1) as f32;
+        let mover = AliveWormMover {
+            details: &self.movement_details(worm_id),
+            rewards: &self.content.rewards,
+            reward_types: &self.params.reward_types,
+            selection: self.params.target_selection,
+            predators: &self.content.predators,
+            flee_distance: self.params.predators.flee_distance,
+            bodies: &self.content.bodies,
+            part_sizes: self.part_sizes(),
+            obstacles: &self.params.obstacles,
+            planner: (self.params.planning.alive == Planner::AStar).then_some(self.planner),
+            scent: self.scent(),
+            reward_index: self.reward_index,
+            body_index: self.body_index,
+        };
+        // the worms with a brain follow it instead of the scripts
+        match &self.content.genomes[worm_id].brain {
+            Some(brain) => NeuralMover {
+                senses: mover,
+                brain,
+                worm_id,
+                hunger,
+                size,
+                step,
+            }
+            .execute_movement(step, self.radius(worm_id), rng),
+            None => mover.execute_movement(step, self.radius(worm_id), rng),
+        }
+    }
+
+    /// Movement of a 'Chasing' worm, starting with the given details
+    fn chasing_movement(
+        &self,
+        worm_id: usize,
+        details: &MovementDetails,
+        rng: &mut SimRng,
+    ) -> MovementResult {
+        let mover = ChasingWormMover {
+            details,
+            rewards: &self.content.rewards,
+            bodies: &self.content.bodies,
+            behaviors: &self.content.behaviors,
+            part_sizes: self.part_sizes(),
+            target_species: (self.params.merge_rule == MergeRule::Same)
+                .then_some(self.content.species[worm_id]),
+            obstacles: &self.params.obstacles,
+            planner: (self.params.planning.chasing == Planner::AStar).then_some(self.planner),
+            scent: self.scent(),
+            reward_index: self.reward_index,
+            body_index: self.body_index,
+        };
+        mover.execute_movement(self.step(worm_id), self.radius(worm_id), rng)
+    }
+
+    /// Movement given by the mover of a registered behavior (none if the behavior is unknown)
+    fn custom_movement(&self, worm_id: usize, id: BehaviorId, rng: &mut SimRng) -> MovementResult {
+        let Some(behavior) = self.registry.get(id) else {
+            return MovementResult::None;
+        };
+        let details = self.movement_details(worm_id);
+        let context = MoverContext {
+            worm: self.worm_view(worm_id),
+            details: &details,
+            params: self.params,
+            rewards: &self.content.rewards,
+            bodies: &self.content.bodies,
+            part_sizes: self.part_sizes(),
+            behaviors: &self.content.behaviors,
+            predators: &self.content.predators,
+            scent: self.scent(),
+            planner: self.planner,
+            reward_index: self.reward_index,
+            body_index: self.body_index,
+        };
+        let result =
+            behavior
+                .mover(context)
+                .execute_movement(self.step(worm_id), self.radius(worm_id), rng);
+        result
+    }
+
+    /// First registered behavior taken up by the 'Alive' or 'Chasing' worm, if any
+    fn entered_behavior(&self, worm_id: usize) -> Option<BehaviorId> {
+        let worm = self.worm_view(worm_id);
+        let behavior = self.content.behaviors[worm_id];
+        self.registry
+            .iter()
+            .position(|registered| registered.enters(&worm, behavior))
+    }
+
+    fn worm_view(&self, worm_id: usize) -> WormView<'a> {
+        WormView {
+            id: worm_id,
+            body: self.content.bodies.get(worm_id),
+            genome: &self.content.genomes[worm_id],
+            species: &self.params.species[self.content.species[worm_id]],
+            energy: self.content.energy[worm_id],
+            metabolism: self.params.metabolism,
+        }
+    }
+
+    /// Scent field of the scene (None if the worms leave no scent)
+    fn scent(&self) -> Option<&'a ScentField> {
+        self.params.scent.map(|_| self.scent)
+    }
+
+    /// How close the worm is to starve, from 0 (just ate) to 1 (counter: ticks since it last ate)
+    fn hunger(&self, worm_id: usize, counter: usize) -> f32 {
+        let hunger = match self.params.metabolism {
+            Metabolism::Counter => {
+                let starvation = self.content.genomes[worm_id].starvation;
+                let limit = (starvation / self.content.bodies.get(worm_id).size()).max(1);
+                counter as f32 / limit as f32
+            }
+            Metabolism::Energy(energy) => 1. - self.content.energy[worm_id] / energy.initial,
+        };
+        hunger.clamp(0., 1.)
+    }
+
+    fn can_split(&self, worm_id: usize) -> bool {
+        match self.params.metabolism {
+            Metabolism::Counter => true,
+            Metabolism::Energy(energy) => self.content.energy[worm_id] >= energy.split_cost,
+        }
+    }
+
+    /// Length of a movement of the worm
+    fn step(&self, worm_id: usize) -> f32 {
+        let species = &self.params.species[self.content.species[worm_id]];
+        species.body_size * 2. * self.content.genomes[worm_id].speed
+    }
+
+    /// Radius of the parts of the worm (half the spacing of its parts at the default speed)
+    fn radius(&self, worm_id: usize) -> f32 {
+        self.part_sizes().radius(worm_id)
+    }
+
+    fn part_sizes(&self) -> PartSizes<'a> {
+        PartSizes {
+            species: &self.content.species,
+            params: &self.params.species,
+        }
+    }
+
+    fn movement_details(&self, worm_id: usize) -> MovementDetails {
+        let body = self.content.bodies.get(worm_id);
+        let mut parts = body.iter();
+        let head = parts.next().expect("a moving worm has parts");
+        let area = self.area;
+        // the heading is only needed by the continuous model
+        // (from the first part behind the head: the parts grown by eating are stacked on it)
+        let heading = match self.params.movement {
+            MovementModel::Discrete => None,
+            MovementModel::Continuous { .. } => parts
+                .find(|&part| part != head)
+                .map(|neck| area.angle(neck, head)),
+        };
+        MovementDetails {
+            origin: head,
+            chosen_destination: body.target,
+            stats: self.content.genomes[worm_id].stats,
+            n_directions: self.params.n_directions,
+            area,
+            model: self.params.movement,
+            heading,
+        }
+    }
+
+    /// Movement details of the worm once its body followed the new head
+    fn moved_details(
+        &self,
+        details: &MovementDetails,
+        new_head: Point,
+        destination: Point,
+    ) -> MovementDetails {
+        MovementDetails {
+            origin: new_head,
+            chosen_destination: destination,
+            stats: details.stats,
+            n_directions: details.n_directions,
+            area: details.area,
+            model: details.model,
+            heading: details
+                .heading
+                .map(|_| details.area.angle(details.origin, new_head)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use std::sync::{Arc, Mutex};
+
+    use super::{Scene, SceneParameters, UpdateMode};
+    use crate::{
+        behaviors::{Behavior, MoverContext, Transition, WormView},
+        bodies::WormBody,
+        composites::{RewardKind, RewardType, RewardTypes, WormBehavior},
+        events::SimEvent,
+        geometry::Point,
+        geometry::{angle_between, Angle, BoundaryMode, SimRng},
+        metabolism::{EnergyParameters, Metabolism},
+        movement::{MovementDetails, MovementModel, MovementResult, Mover, TargetSelection},
+        obstacles::{Obstacle, ObstacleMap},
+        predators::PredatorParameters,
+        scenario::Scenario,
+        spawning::{Seasons, SpawnParameters, SpawnPattern},
+    };
+
+    fn seeded_scene(seed: u64) -> Scene {
+        bounded_scene(seed, BoundaryMode::Bounded)
+    }
+
+    fn bounded_scene(seed: u64, boundary: BoundaryMode) -> Scene {
+        Scene::new(
+            300,
+            300,
+            SceneParameters {
+                worm_size: 4,
+                body_size: 3.0,
+                starvation: 200,
+                expiration: 20,
+                seed: Some(seed),
+                boundary,
+                ..Default::default()
+            },
+            40,
+            10,
+        )
+    }
+
+    fn snapshot(scene: &Scene) -> String {
+        scene
+            .worms()
+            .map(|(behavior, body, _)| format!("{behavior:?} {body}"))
+            .chain(
+                scene
+                    .rewards()
+                    .iter()
+                    .map(|reward| format!("{:?}{}", reward.kind, reward.position)),
+            )
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn same_seed_same_trajectory() {
+        let mut scene1 = seeded_scene(7);
+        let mut scene2 = seeded_scene(7);
+        assert_eq!(snapshot(&scene1), snapshot(&scene2));
+        for _ in 0..300 {
+            scene1.execute();
+            scene2.execute();
+            assert_eq!(snapshot(&scene1), snapshot(&scene2));
+        }
+        assert_ne!(snapshot(&scene1), snapshot(&seeded_scene(8)));
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join(format!("worms_snapshot_{}.json", std::process::id()));
+        let mut scene = seeded_scene(11);
+        for _ in 0..100 {
+            scene.execute();
+        }
+        scene.save(&path).unwrap();
+        let mut loaded = Scene::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(snapshot(&scene), snapshot(&loaded));
+        // the random generator is restored as well: both continue identically
+        for _ in 0..100 {
+            scene.execute();
+            loaded.execute();
+        }
+        assert_eq!(snapshot(&scene), snapshot(&loaded));
+    }
+
+    #[test]
+    fn load_older_snapshots() {
+        let path = std::env::temp_dir().join(format!("worms_v1_{}.json", std::process::id()));
+        let mut scene = seeded_scene(11);
+        for _ in 0..20 {
+            scene.execute();
+        }
+        scene.save(&path).unwrap();
+        // the first version of the format: no genomes, ids, energy, nor kinds of reward
+        let mut json = std::fs::read_to_string(&path).unwrap();
+        json = json.replacen(r#""version":3"#, r#""version":1"#, 1);
+        let (start, end) = (json.find(r#""genomes":"#), json.find(r#""rewards":"#));
+        json.replace_range(start.unwrap()..end.unwrap(), "");
+        for kind in RewardKind::ALL {
+            json = json.replace(&format!(r#""kind":"{}","#, kind.name()), "");
+        }
+        assert!(!json.contains(r#""genomes""#) && !json.contains(r#""kind""#));
+        std::fs::write(&path, json).unwrap();
+        let mut loaded = Scene::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.worm_ids().len(), scene.worm_ids().len());
+        assert!(loaded
+            .rewards()
+            .iter()
+            .all(|reward| reward.kind == RewardKind::Prey));
+        for _ in 0..20 {
+            loaded.execute();
+        }
+    }
+
+    #[test]
+    fn observed_events() {
+        let observed = Arc::new(Mutex::new(Vec::new()));
+        let mut scene = seeded_scene(3);
+        let sink = Arc::clone(&observed);
+        scene.add_observer(move |event| sink.lock().unwrap().push(*event));
+
+        let mut returned = Vec::new();
+        for _ in 0..400 {
+            scene.execute();
+            assert!(scene
+                .events()
+                .iter()
+                .all(|event| event.tick() == scene.tick()));
+            returned.extend_from_slice(scene.events());
+        }
+        let observed = observed.lock().unwrap();
+        assert_eq!(format!("{observed:?}"), format!("{returned:?}"));
+        assert!(observed
+            .iter()
+            .any(|event| matches!(event, SimEvent::RewardEaten { .. })));
+        assert!(observed
+            .iter()
+            .any(|event| matches!(event, SimEvent::Starved { .. })));
+    }
+
+    #[test]
+    fn boundary_modes() {
+        for boundary in [
+            BoundaryMode::Bounded,
+            BoundaryMode::Torus,
+            BoundaryMode::Reflect,
+        ] {
+            let mut scene = bounded_scene(5, boundary);
+            for _ in 0..300 {
+                scene.execute();
+                let area = scene.area();
+                // the heads are the only parts placed by the movements
+                assert!(scene
+                    .worms()
+                    .filter(|(_, body, _)| body.size() > 0)
+                    .all(|(_, body, _)| area.contains(body.head().unwrap())));
+                assert!(scene
+                    .rewards()
+                    .iter()
+                    .all(|reward| area.contains(reward.position)));
+            }
+        }
+    }
+
+    #[test]
+    fn continuous_movement() {
+        let max_turn = Angle::new(std::f32::consts::PI / 6.);
+        let mut scene = Scene::new(
+            300,
+            300,
+            SceneParameters {
+                worm_size: 4,
+                body_size: 3.0,
+                seed: Some(9),
+                boundary: BoundaryMode::Torus,
+                movement: MovementModel::Continuous {
+                    max_turn,
+                    n_samples: 8,
+                },
+                ..Default::default()
+            },
+            20,
+            10,
+        );
+        let area = scene.area();
+        let heading = move |body: WormBody| {
+            let mut parts = body.iter();
+            let head = parts.next()?;
+            let neck = parts.find(|&part| part != head)?;
+            Some(area.angle(neck, head))
+        };
+        let (mut turns, mut violations) = (0, 0);
+        for _ in 0..100 {
+            let before = scene
+                .worms()
+                .map(|(behavior, body, _)| (*behavior, heading(body)))
+                .collect::<Vec<_>>();
+            scene.execute();
+            for ((_, body, _), (behavior, previous)) in scene.worms().zip(before) {
+                // the 'Chasing' worms move twice in a tick
+                let moves = match behavior {
+                    WormBehavior::Chasing => 2.,
+                    _ => 1.,
+                };
+                if let (Some(previous), Some(current)) = (previous, heading(body)) {
+                    turns += 1;
+                    if angle_between(previous, current) > moves * max_turn.val() + 1e-4 {
+                        violations += 1;
+                    }
+                }
+            }
+        }
+        // the worms never turn beyond the maximum turn
+        assert!(turns > 0);
+        assert_eq!(violations, 0);
+    }
+
+    #[test]
+    fn fast_worms() {
+        let mut scene = Scene::from_scenario(
+            &Scenario::parse(
+                r#"{
+                    "config": {"width": 300, "height": 300, "part_size": 3.0, "worm_size": 2,
+                               "seed": 1},
+                    "worms": [
+                        {"head": {"x": 50, "y": 150}, "destination": {"x": 290, "y": 150}},
+                        {"head": {"x": 100, "y": 160}, "size": 8, "destination": {"x": 290, "y": 160}}
+                    ],
+                    "rewards": [
+                        {"position": {"x": 62, "y": 150}, "kind": "plant", "destination": {"x": 62, "y": 150}}
+                    ]
+                }"#,
+            )
+            .unwrap(),
+        );
+        // a step of 18, past the reward and along the other worm (10 away)
+        scene.content.genomes[0].speed = 3.;
+        scene.execute();
+        assert!(matches!(
+            scene.events(),
+            [SimEvent::RewardEaten { worm_id: 0, .. }, ..]
+        ));
+        let head = scene.worms().next().unwrap().1.head().unwrap();
+        assert!(head.distance_to(Point { x: 68., y: 150. }) < 1e-3);
+    }
+
+    #[test]
+    fn inherited_genomes() {
+        let mut scene = Scene::new(
+            300,
+            300,
+            SceneParameters {
+                worm_size: 2,
+                body_size: 3.0,
+                max_size: 6,
+                seed: Some(4),
+                mutation: 0.2,
+                ..Default::default()
+            },
+            40,
+            20,
+        );
+        let base_genome = scene.params().base_genome();
+        let mut splits = 0;
+        for _ in 0..300 {
+            scene.execute();
+            for event in scene.events() {
+                if let SimEvent::Split { offspring_id, .. } = *event {
+                    splits += 1;
+                    let offspring = &scene.genomes()[offspring_id];
+                    assert!((4..=6).contains(&offspring.split_size));
+                    assert_ne!(offspring.speed, base_genome.speed);
+                }
+            }
+        }
+        assert!(splits > 0);
+        assert_eq!(scene.genomes().len(), scene.worms().count());
+    }
+
+    #[test]
+    fn lineage() {
+        let mut scene = Scene::new(
+            300,
+            300,
+            SceneParameters {
+                worm_size: 2,
+                body_size: 3.0,
+                max_size: 6,
+                starvation: 100,
+                seed: Some(4),
+                ..Default::default()
+            },
+            40,
+            20,
+        );
+        assert_eq!(scene.worm_ids(), (0..40).collect::<Vec<_>>());
+        let (mut splits, mut merges) = (0, 0);
+        for _ in 0..400 {
+            scene.execute();
+            for event in scene.events() {
+                match *event {
+                    SimEvent::Split {
+                        worm, offspring, ..
+                    } => {
+                        splits += 1;
+                        let record = scene.lineage().get(offspring);
+                        assert_eq!(record.unwrap().born, scene.tick());
+                        assert!(record.unwrap().parents == [worm]);
+                    }
+                    SimEvent::Merged { worm, target, .. } => {
+                        merges += 1;
+                        let record = scene.lineage().get(worm);
+                        assert_eq!(record.unwrap().parents.len(), 2);
+                        assert!(record.unwrap().parents.contains(&target));
+                    }
+                    _ => (),
+                }
+            }
+        }
+        assert!(splits > 0 && merges > 0);
+        // ids are unique among the worms in the scene
+        let mut ids = scene
+            .worms()
+            .zip(scene.worm_ids())
+            .filter(|((behavior, _, _), _)| !matches!(behavior, WormBehavior::Removed))
+            .map(|(_, &id)| id)
+            .collect::<Vec<_>>();
+        let n_worms = ids.len();
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), n_worms);
+        assert_eq!(scene.lineage().records().len(), 40 + splits + merges);
+        assert!(scene.lineage().to_newick().ends_with(");"));
+    }
+
+    #[test]
+    fn reused_slot() {
+        // the expired worm leaves its slot, taken in the same tick by the offspring of the other
+        let mut scene = Scene::from_scenario(
+            &Scenario::parse(
+                r#"{
+                    "config": {"width": 300, "height": 300, "part_size": 3.0, "worm_size": 4,
+                               "max_size": 8, "expiration": 0, "seed": 1},
+                    "worms": [{"head": {"x": 50, "y": 50}, "behavior": {"Dead": 0}},
+                              {"head": {"x": 150, "y": 150}, "size": 8}]
+                }"#,
+            )
+            .unwrap(),
+        );
+        let founders = scene.worm_ids().to_vec();
+        scene.execute();
+        let events = scene.events();
+        let [SimEvent::Removed {
+            worm_id: 0, worm, ..
+        }, SimEvent::Split {
+            offspring_id: 0,
+            offspring,
+            ..
+        }, ..] = events
+        else {
+            panic!("{events:?}");
+        };
+        // the events still tell the two worms apart
+        assert_eq!(*worm, founders[0]);
+        assert_ne!(*offspring, founders[0]);
+        assert_eq!(scene.worm_ids()[0], *offspring);
+        assert_eq!(
+            scene.lineage().get(*offspring).unwrap().parents,
+            [founders[1]]
+        );
+        assert!(events
+            .iter()
+            .skip(1)
+            .all(|event| event.worm() != founders[0]));
+    }
+
+    #[test]
+    fn energy_metabolism() {
+        let energy = EnergyParameters {
+            initial: 5.,
+            per_part: 0.,
+            per_move: 1.,
+            ..Default::default()
+        };
+        let mut scene = Scene::new(
+            300,
+            300,
+            SceneParameters {
+                worm_size: 4,
+                body_size: 3.0,
+                seed: Some(2),
+                metabolism: Metabolism::Energy(energy),
+                ..Default::default()
+            },
+            20,
+            0,
+        );
+        let mut starved = 0;
+        for tick in 1..=10 {
+            scene.execute();
+            for event in scene.events() {
+                if let SimEvent::Starved { worm_id, .. } = *event {
+                    starved += 1;
+                    assert_eq!(tick, 5);
+                    assert!(scene.energy()[worm_id] <= 0.);
+                }
+            }
+            if tick < 5 {
+                // no rewards: one unit of energy spent in each movement
+                assert!(scene
+                    .worms()
+                    .zip(scene.energy())
+                    .filter(|((behavior, _, _), _)| matches!(behavior, WormBehavior::Alive(_)))
+                    .all(|(_, &energy)| energy == 5. - tick as f32));
+            }
+        }
+        assert!(starved > 0);
+    }
+
+    #[test]
+    fn energy_after_merge() {
+        let mut scene = chase_scene(4, r#"{"Alive": 0}"#, 32);
+        scene.params.metabolism = Metabolism::Energy(EnergyParameters {
+            per_part: 0.,
+            per_move: 1.,
+            ..Default::default()
+        });
+        scene.content.energy = vec![0., 5.];
+        // a long chase does not run the reserve of the chaser below empty
+        for _ in 0..10 {
+            scene.spend_energy(0);
+        }
+        assert_eq!(scene.energy()[0], 0.);
+        // merging, it takes the energy of the target: enough to live on
+        scene.execute();
+        assert!(matches!(
+            scene.worms().next().unwrap().0,
+            WormBehavior::Alive(0)
+        ));
+        assert_eq!(scene.energy()[0], 5.);
+        scene.execute();
+        assert!(matches!(
+            scene.worms().next().unwrap().0,
+            WormBehavior::Alive(1)
+        ));
+    }
+
+    #[test]
+    fn reward_kinds() {
+        let defaults = RewardTypes::default();
+        let reward_types = RewardTypes {
+            prey: RewardType {
+                spawn_rate: 0.,
+                ..defaults.prey
+            },
+            poison: RewardType {
+                spawn_rate: 1.,
+                ..defaults.poison
+            },
+            super_food: RewardType {
+                spawn_rate: 1.,
+                ..defaults.super_food
+            },
+            ..defaults
+        };
+        let kind_scene = |target_selection| {
+            Scene::new(
+                300,
+                300,
+                SceneParameters {
+                    worm_size: 4,
+                    body_size: 3.0,
+                    starvation: 5000,
+                    max_size: 64,
+                    seed: Some(3),
+                    reward_types,
+                    target_selection,
+                    ..Default::default()
+                },
+                20,
+                100,
+            )
+        };
+
+        let mut scene = kind_scene(TargetSelection::Closest);
+        let mut eaten = [0; 4];
+        for _ in 0..300 {
+            let sizes = scene
+                .worms()
+                .map(|(_, body, _)| body.size())
+                .collect::<Vec<_>>();
+            scene.execute();
+            assert!(scene
+                .rewards()
+                .iter()
+                .all(|reward| matches!(reward.kind, RewardKind::Poison | RewardKind::SuperFood)));
+            for event in scene.events() {
+                let SimEvent::RewardEaten { worm_id, kind, .. } = *event else {
+                    continue;
+                };
+                eaten[kind as usize] += 1;
+                let size = scene.worms().nth(worm_id).unwrap().1.size();
+                match kind {
+                    RewardKind::SuperFood => assert_eq!(size, (sizes[worm_id] + 3).min(64)),
+                    _ => assert_eq!(size, sizes[worm_id].saturating_sub(2).max(1)),
+                }
+            }
+        }
+        assert!(eaten[RewardKind::Poison as usize] > 0);
+        assert!(eaten[RewardKind::SuperFood as usize] > 0);
+
+        // weighing the targets by their value, the poison is avoided
+        let mut scene = kind_scene(TargetSelection::Value);
+        for _ in 0..300 {
+            scene.execute();
+            assert!(scene.events().iter().all(|event| !matches!(
+                event,
+                SimEvent::RewardEaten {
+                    kind: RewardKind::Poison,
+                    ..
+                }
+            )));
+        }
+    }
+
+    #[test]
+    fn harmful_rewards() {
+        // a single part worm, with a poison right ahead
+        let poison_scene = || {
+            Scene::from_scenario(
+                &Scenario::parse(
+                    r#"{
+                        "config": {"width": 300, "height": 300, "part_size": 3.0,
+                                   "worm_size": 1, "starvation": 4, "seed": 1},
+                        "worms": [{"head": {"x": 100, "y": 100}}],
+                        "rewards": [{"position": {"x": 106, "y": 100}, "kind": "poison"}]
+                    }"#,
+                )
+                .unwrap(),
+            )
+        };
+        let hit = || MovementResult::TargetHit(0, Point { x: 106., y: 100. });
+        // eating it does not delay the starvation
+        let mut scene = poison_scene();
+        assert!(matches!(
+            scene.apply_alive(0, 1, hit()),
+            WormBehavior::Alive(2)
+        ));
+        let mut scene = poison_scene();
+        assert!(matches!(
+            scene.apply_alive(0, 4, hit()),
+            WormBehavior::Chasing
+        ));
+        assert!(matches!(
+            scene.events(),
+            [SimEvent::RewardEaten { .. }, SimEvent::Starved { .. }]
+        ));
+    }
+
+    #[test]
+    fn reward_spawning() {
+        let spawning = SpawnParameters {
+            pattern: SpawnPattern::Patches {
+                count: 2,
+                radius: 30.,
+            },
+            seasons: Some(Seasons {
+                period: 60,
+                amplitude: 0.5,
+            }),
+            regrowth: Some(0.5),
+        };
+        let mut scene = Scene::new(
+            300,
+            300,
+            SceneParameters {
+                worm_size: 4,
+                body_size: 3.0,
+                seed: Some(4),
+                spawning,
+                ..Default::default()
+            },
+            30,
+            40,
+        );
+        let (mut eaten, mut fewest) = (0, 40);
+        for tick in 1..=120 {
+            let before = scene.rewards().len();
+            scene.execute();
+            let n_rewards = scene.rewards().len();
+            // never more rewards than the season allows, nor more than regrown
+            assert!(n_rewards as f32 <= 40. * spawning.abundance(tick) + 0.5);
+            assert!(n_rewards <= before + 1);
+            eaten += scene
+                .events()
+                .iter()
+                .filter(|event| matches!(event, SimEvent::RewardEaten { .. }))
+                .count();
+            fewest = fewest.min(n_rewards);
+        }
+        assert!(eaten > 0);
+        assert!(fewest <= 20);
+    }
+
+    #[test]
+    fn obstacles() {
+        let wall = Obstacle::Segment {
+            from: Point { x: 150., y: -10. },
+            to: Point { x: 150., y: 310. },
+        };
+        let mut scene = Scene::new(
+            300,
+            300,
+            SceneParameters {
+                worm_size: 4,
+                body_size: 3.0,
+                starvation: 5000,
+                seed: Some(5),
+                obstacles: ObstacleMap {
+                    obstacles: vec![wall],
+                    block_vision: true,
+                },
+                ..Default::default()
+            },
+            30,
+            30,
+        );
+        let left_side = |scene: &Scene| {
+            scene
+                .worms()
+                .map(|(_, body, _)| body.head().unwrap().x < 150.)
+                .collect::<Vec<_>>()
+        };
+        let initial = left_side(&scene);
+        for _ in 0..200 {
+            scene.execute();
+            // the worms never cross the wall (nor stand on it)
+            for (worm_id, (behavior, body, _)) in scene.worms().enumerate().take(initial.len()) {
+                if let WormBehavior::Alive(_) = behavior {
+                    assert_eq!(body.head().unwrap().x < 150., initial[worm_id]);
+                    assert!(wall.distance(body.head().unwrap()) >= 3. - 1e-3);
+                }
+            }
+            assert!(scene
+                .rewards()
+                .iter()
+                .all(|reward| wall.distance(reward.position) > 0.));
+        }
+    }
+
+    #[test]
+    fn initial_placement() {
+        // the worms (and predators) are placed out of the obstacles,
+        // even when they cover most of the scene
+        let block = Obstacle::Rect {
+            min: Point { x: -10., y: -10. },
+            max: Point { x: 310., y: 240. },
+        };
+        let scene = Scene::new(
+            300,
+            300,
+            SceneParameters {
+                worm_size: 4,
+                body_size: 3.0,
+                seed: Some(3),
+                obstacles: ObstacleMap {
+                    obstacles: vec![block],
+                    block_vision: false,
+                },
+                predators: PredatorParameters {
+                    count: 20,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            30,
+            30,
+        );
+        for (_, body, _) in scene.worms() {
+            assert!(body.iter().all(|part| block.distance(part) >= 3.));
+        }
+        assert!(scene
+            .rewards()
+            .iter()
+            .all(|reward| block.distance(reward.position) > 0.));
+        assert!(scene
+            .predators()
+            .iter()
+            .all(|predator| block.distance(predator.position) > 0.));
+    }
+
+    #[test]
+    fn path_planning() {
+        // a worm inside a U-shaped wall, with a reward right behind its bottom
+        let ticks_to_eat = |planner: &str| {
+            let mut scene = Scene::from_scenario(
+                &Scenario::parse(&format!(
+                    r#"{{
+                        "config": {{"width": 300, "height": 300, "part_size": 3.0, "worm_size": 4,
+                                    "seed": 2, "reward_types": {{"plant": {{"speed": 0}}}},
+                                    "alive_planner": "{planner}",
+                                    "obstacles": [
+                                        {{"shape": "segment", "from": {{"x": 90, "y": 110}},
+                                          "to": {{"x": 130, "y": 110}}}},
+                                        {{"shape": "segment", "from": {{"x": 130, "y": 110}},
+                                          "to": {{"x": 130, "y": 190}}}},
+                                        {{"shape": "segment", "from": {{"x": 130, "y": 190}},
+                                          "to": {{"x": 90, "y": 190}}}}
+                                    ]}},
+                        "worms": [{{"head": {{"x": 120, "y": 150}},
+                                    "destination": {{"x": 160, "y": 150}}}}],
+                        "rewards": [{{"position": {{"x": 160, "y": 150}}, "kind": "plant"}}]
+                    }}"#
+                ))
+                .unwrap(),
+            );
+            (1..=200).find(|_| {
+                scene.execute();
+                scene
+                    .events()
+                    .iter()
+                    .any(|event| matches!(event, SimEvent::RewardEaten { worm_id: 0, .. }))
+            })
+        };
+        // straight to the reward, the worm keeps bumping into the wall
+        assert_eq!(ticks_to_eat("greedy"), None);
+        // along the planned path, out of the U and around it
+        assert!(ticks_to_eat("astar").is_some());
+    }
+
+    #[test]
+    fn predators() {
+        let predator_scene = |predator_x: f32, config: &str| {
+            Scene::from_scenario(
+                &Scenario::parse(&format!(
+                    r#"{{
+                        "config": {{"width": 300, "height": 300, "part_size": 3.0, "worm_size": 4,
+                                    "seed": 1, {config}}},
+                        "worms": [{{"head": {{"x": 100, "y": 100}},
+                                    "destination": {{"x": 250, "y": 100}}}}],
+                        "predators": [{{"position": {{"x": {predator_x}, "y": 100}},
+                                        "destination": {{"x": 250, "y": 100}}}}]
+                    }}"#
+                ))
+                .unwrap(),
+            )
+        };
+
+        // a faster predator right behind the worm (out of its sight) eats it whole
+        let mut scene = predator_scene(
+            60.,
+            r#""predator_speed": 10, "predator_bite": 4, "predator_reproduction": 4"#,
+        );
+        let bite = (1..=30).find_map(|_| {
+            scene.execute();
+            scene.events().iter().find_map(|event| match *event {
+                SimEvent::Bitten { worm_id, parts, .. } => Some((worm_id, parts)),
+                _ => None,
+            })
+        });
+        assert_eq!(bite, Some((0, 4)));
+        assert!(matches!(
+            scene.worms().next().unwrap().0,
+            WormBehavior::Removed
+        ));
+        // it reproduced, then both starve without prey
+        assert_eq!(scene.predators().len(), 2);
+        for _ in 0..=SceneParameters::default().predators.starvation {
+            scene.execute();
+        }
+        assert_eq!(scene.predators().len(), 0);
+
+        // a worm seeing a slow predator ahead runs away from it
+        let mut scene = predator_scene(130., r#""predator_speed": 1"#);
+        for _ in 0..10 {
+            scene.execute();
+        }
+        let head = scene.worms().next().unwrap().1.head().unwrap();
+        let predator = scene.predators()[0].position;
+        assert!(head.distance_to(predator) > 50., "{head} {predator}");
+        assert!(!scene
+            .events()
+            .iter()
+            .any(|event| matches!(event, SimEvent::Bitten { .. })));
+    }
+
+    #[test]
+    fn scent() {
+        let mut scene = Scene::from_scenario(
+            &Scenario::parse(
+                r#"{
+                    "config": {"width": 300, "height": 300, "part_size": 3.0, "worm_size": 4,
+                               "vision_distance": 20, "seed": 1,
+                               "scent_deposit": 1, "scent_cell_size": 12},
+                    "worms": [{"head": {"x": 100, "y": 100}}]
+                }"#,
+            )
+            .unwrap(),
+        );
+        // nothing in sight: the worm follows the trail of scent ahead, up its (steep) gradient
+        let area = scene.area();
+        for i in 1..10 {
+            let point = Point {
+                x: 102. + 12. * i as f32,
+                y: 102. + 12. * i as f32,
+            };
+            scene.scent.deposit(area, point, (10 * i * i) as f32);
+        }
+        for _ in 0..6 {
+            scene.execute();
+        }
+        let head = scene.worms().next().unwrap().1.head().unwrap();
+        assert!(head.x > 120. && (head.x - head.y).abs() < 12., "{head}");
+        // and leaves its own scent
+        assert!(scene.scent().unwrap().level(area, head) > 0.5);
+
+        // no scent field unless configured
+        assert!(seeded_scene(1).scent().is_none());
+    }
+
+    #[test]
+    fn parallel_update() {
+        // the trajectory only depends on the seed, not on the number of threads
+        let run = |n_threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(n_threads)
+                .build()
+                .unwrap();
+            pool.install(|| {
+                let mut scene = Scene::new(
+                    300,
+                    300,
+                    SceneParameters {
+                        worm_size: 4,
+                        body_size: 3.0,
+                        starvation: 200,
+                        expiration: 20,
+                        seed: Some(9),
+                        update: UpdateMode::Parallel,
+                        ..Default::default()
+                    },
+                    40,
+                    10,
+                );
+                let mut eaten = 0;
+                let snapshots = (0..300)
+                    .map(|_| {
+                        scene.execute();
+                        eaten += scene
+                            .events()
+                            .iter()
+                            .filter(|event| matches!(event, SimEvent::RewardEaten { .. }))
+                            .count();
+                        snapshot(&scene)
+                    })
+                    .collect::<Vec<_>>();
+                (snapshots, eaten)
+            })
+        };
+        let (single, eaten) = run(1);
+        assert!(eaten > 0);
+        assert_eq!(single, run(4).0);
+    }
+
+    // Scene in parallel update with two worms heading to each other (worm "a" at x = 94.5
+    // and worm "b" at x = b_x, "a" first in the table if a_first), and a reward at x = 100 if set
+    fn rivals_scene(b_x: f32, a_first: bool, reward: bool) -> Scene {
+        let a = r#"{"head": {"x": 94.5, "y": 100}, "parts": [{"x": 88.5, "y": 100}],
+                     "destination": {"x": 290, "y": 100}}"#;
+        let b = format!(
+            r#"{{"head": {{"x": {b_x}, "y": 100}}, "parts": [{{"x": {}, "y": 100}}],
+                 "destination": {{"x": 10, "y": 100}}}}"#,
+            b_x + 6.
+        );
+        let worms = if a_first {
+            format!("{a}, {b}")
+        } else {
+            format!("{b}, {a}")
+        };
+        let rewards = if reward {
+            r#"{"position": {"x": 100, "y": 100}, "kind": "plant", "destination": {"x": 100, "y": 100}}"#
+        } else {
+            ""
+        };
+        Scene::from_scenario(
+            &Scenario::parse(&format!(
+                r#"{{
+                    "config": {{"width": 300, "height": 300, "part_size": 3.0, "worm_size": 2,
+                                "vision_distance": 50, "update": "parallel", "seed": 1}},
+                    "worms": [{worms}],
+                    "rewards": [{rewards}]
+                }}"#
+            ))
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn parallel_conflicts() {
+        // the reward goes to the closest worm ("a"), whatever the order of the worms,
+        // the other one staying in place
+        for a_first in [true, false] {
+            let mut scene = rivals_scene(111., a_first, true);
+            let (a, b) = if a_first { (0, 1) } else { (1, 0) };
+            scene.execute();
+            let eaters = scene
+                .events()
+                .iter()
+                .filter_map(|event| match event {
+                    SimEvent::RewardEaten { worm_id, .. } => Some(*worm_id),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(eaters, vec![a]);
+            let heads = scene
+                .worms()
+                .map(|(_, body, _)| body.head().unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(heads[a], Point { x: 100.5, y: 100. });
+            assert_eq!(heads[b], Point { x: 111., y: 100. });
+        }
+
+        // two heads cannot take the same spot: one of the worms moves, the other one waits
+        let mut scene = rivals_scene(112., true, false);
+        scene.execute();
+        let heads = scene
+            .worms()
+            .map(|(behavior, body, _)| {
+                assert!(matches!(behavior, WormBehavior::Alive(1)));
+                body.head().unwrap()
+            })
+            .collect::<Vec<_>>();
+        let moved = [Point { x: 100.5, y: 100. }, Point { x: 106., y: 100. }];
+        let waited = [Point { x: 94.5, y: 100. }, Point { x: 112., y: 100. }];
+        assert!((0..2).any(|i| heads[i] == moved[i] && heads[1 - i] == waited[1 - i]));
+
+        // a worm that moved first is no longer where the chaser planned to reach its tail:
+        // the merge waits (the chaser staying in place), and goes on in the other order
+        for (order, merges) in [([1, 0], false), ([0, 1], true)] {
+            let mut scene = chase_scene(4, r#"{"Alive": 0}"#, 32);
+            scene.params.update = UpdateMode::Parallel;
+            scene.index_content();
+            let view = scene.view();
+            let intents = (0..2)
+                .map(|worm_id| view.plan(worm_id, &mut SimRng::seed_from_u64(1)))
+                .collect::<Vec<_>>();
+            scene.resolve(intents, &order);
+            let merged = scene
+                .events()
+                .iter()
+                .any(|event| matches!(event, SimEvent::Merged { .. }));
+            assert_eq!(merged, merges);
+            if !merges {
+                let (behavior, body, _) = scene.worms().next().unwrap();
+                assert!(matches!(behavior, WormBehavior::Chasing));
+                assert_eq!(body.head().unwrap(), Point { x: 72., y: 100. });
+                assert_ne!(scene.head(1), Point { x: 100., y: 100. });
+            }
+        }
+    }
+
+    // Scene with a chasing worm right behind the tail of a target worm, both facing +x
+    fn chase_scene(chaser_size: usize, target_behavior: &str, max_size: usize) -> Scene {
+        Scene::from_scenario(
+            &Scenario::parse(&format!(
+                r#"{{
+                    "config": {{"width": 300, "height": 300, "part_size": 3.0, "worm_size": 4,
+                                "max_size": {max_size}, "seed": 1}},
+                    "worms": [
+                        {{"head": {{"x": 72, "y": 100}}, "size": {chaser_size},
+                          "behavior": "Chasing", "destination": {{"x": 200, "y": 100}}}},
+                        {{"head": {{"x": 100, "y": 100}}, "behavior": {target_behavior}}}
+                    ]
+                }}"#
+            ))
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn scenario_chasing() {
+        let mut scene = chase_scene(4, r#"{"Alive": 0}"#, 32);
+        assert_eq!(scene.rewards().len(), 0);
+        scene.execute();
+        assert!(matches!(
+            scene.events(),
+            [
+                SimEvent::Merged {
+                    worm_id: 0,
+                    target_id: 1,
+                    ..
+                },
+                SimEvent::Removed { worm_id: 1, .. }
+            ]
+        ));
+        // the chaser lost its head, but took the whole target body (head included)
+        let (behavior, body, _) = scene.worms().next().unwrap();
+        assert!(matches!(behavior, WormBehavior::Alive(0)));
+        assert_eq!(body.size(), 3 + 4);
+        assert_eq!(body.head().unwrap(), Point { x: 100., y: 100. });
+        assert!(matches!(
+            scene.worms().nth(1).unwrap().0,
+            WormBehavior::Removed
+        ));
+        let merged = scene.lineage().get(scene.worm_ids()[0]).unwrap();
+        assert_eq!(merged.parents, vec![0, 1]);
+
+        // dead worms are no targets (and block the way)
+        let mut scene = chase_scene(4, r#"{"Dead": 0}"#, 32);
+        scene.execute();
+        assert!(!scene
+            .events()
+            .iter()
+            .any(|event| matches!(event, SimEvent::Merged { .. })));
+    }
+
+    #[test]
+    fn scenario_partial_merge() {
+        // the chaser only has room for 3 of the 4 parts of the target
+        let mut scene = chase_scene(6, r#"{"Alive": 0}"#, 8);
+        scene.execute();
+        assert!(matches!(
+            scene.events(),
+            [SimEvent::Merged {
+                worm_id: 0,
+                target_id: 1,
+                ..
+            }]
+        ));
+        let sizes = scene
+            .worms()
+            .map(|(_, body, _)| body.size())
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, vec![8, 1]);
+        assert!(matches!(
+            scene.worms().nth(1).unwrap().0,
+            WormBehavior::Alive(_)
+        ));
+    }
+
+    #[test]
+    fn species() {
+        let species_scene = |merge_rule: &str| {
+            Scene::from_scenario(
+                &Scenario::parse(&format!(
+                    r#"{{
+                        "config": {{"width": 300, "height": 300, "part_size": 3.0, "worm_size": 4,
+                                    "seed": 1, "merge_rule": "{merge_rule}", "species": [
+                                    {{"name": "red", "count": 0}},
+                                    {{"name": "blue", "count": 0, "worm_size": 2}}]}},
+                        "worms": [
+                            {{"head": {{"x": 72, "y": 100}}, "behavior": "Chasing",
+                              "destination": {{"x": 200, "y": 100}}}},
+                            {{"head": {{"x": 100, "y": 100}}, "species": "blue"}}
+                        ]
+                    }}"#
+                ))
+                .unwrap(),
+            )
+        };
+        let scene = species_scene("same");
+        let species = scene
+            .worms()
+            .map(|(_, body, species)| (species.name.as_str(), body.size()))
+            .collect::<Vec<_>>();
+        assert_eq!(species, vec![("red", 4), ("blue", 2)]);
+        assert_eq!(scene.worm_species(), &[0, 1]);
+
+        // the chaser only merges with the worms of its own species
+        let merged = |mut scene: Scene| {
+            (0..5).any(|_| {
+                scene.execute();
+                scene
+                    .events()
+                    .iter()
+                    .any(|event| matches!(event, SimEvent::Merged { .. }))
+            })
+        };
+        assert!(!merged(scene));
+        assert!(merged(species_scene("any")));
+
+        // without species, all the worms belong to a single one
+        let scene = Scene::new(300, 300, SceneParameters::default(), 3, 0);
+        assert_eq!(scene.species().len(), 1);
+        assert_eq!(scene.species()[0].count, 3);
+        assert!(scene.worms().all(|(_, _, species)| species.name == "worms"));
+    }
+
+    #[test]
+    fn species_radii() {
+        let head_after_tick = |other: &str| {
+            let mut scene = Scene::from_scenario(
+                &Scenario::parse(&format!(
+                    r#"{{
+                        "config": {{"width": 600, "height": 300, "part_size": 3.0, "worm_size": 4,
+                                    "seed": 1, "species": [
+                                    {{"name": "small", "count": 0}},
+                                    {{"name": "big", "count": 0, "part_size": 9.0, "worm_size": 2}}]}},
+                        "worms": [
+                            {{"head": {{"x": 50, "y": 150}}, "destination": {{"x": 590, "y": 150}}}},
+                            {{"head": {{"x": 62, "y": 160}}, "species": "{other}"}}
+                        ]
+                    }}"#
+                ))
+                .unwrap(),
+            );
+            scene.execute();
+            scene.head(0)
+        };
+        // the worm keeps the sum of the radii away: it passes 10 away from the parts of a small
+        // worm (3 + 3), not from the ones of a big worm (3 + 9)
+        let ahead = Point { x: 56., y: 150. };
+        assert!(head_after_tick("small").distance_to(ahead) < 1e-3);
+        assert!(head_after_tick("big").distance_to(ahead) > 1.);
+    }
+
+    #[test]
+    fn neural_worms() {
+        let mut scene = Scene::from_scenario(
+            &Scenario::parse(
+                r#"{
+                    "config": {"width": 300, "height": 300, "part_size": 3.0, "worm_size": 2,
+                               "max_size": 4, "mutation": 0.1, "seed": 1, "species": [
+                               {"name": "scripted", "count": 0},
+                               {"name": "neural", "count": 0, "controller": "neural"}]},
+                    "worms": [
+                        {"head": {"x": 50, "y": 50}},
+                        {"head": {"x": 150, "y": 150}, "size": 4, "species": "neural"}
+                    ],
+                    "rewards": [{"position": {"x": 250, "y": 250}}]
+                }"#,
+            )
+            .unwrap(),
+        );
+        assert!(scene.genomes()[0].brain.is_none());
+        let brain = scene.genomes()[1].brain.clone().unwrap();
+        assert_eq!(brain.weights().len(), crate::neural::N_WEIGHTS);
+
+        // the full neural worm splits, its offspring inheriting a mutated brain
+        let head = scene.content.bodies.get(1).head().unwrap();
+        scene.execute();
+        assert!(matches!(
+            scene.events(),
+            [SimEvent::Split {
+                worm_id: 1,
+                offspring_id: 2,
+                ..
+            }]
+        ));
+        assert_eq!(scene.worm_species()[2], 1);
+        let offspring_brain = scene.genomes()[2].brain.as_ref().unwrap();
+        assert_ne!(*offspring_brain, brain);
+
+        // then it moves where its network says
+        scene.execute();
+        assert_ne!(scene.content.bodies.get(1).head().unwrap(), head);
+    }
+
+    // Registered behavior: the hungry worms (alive for 2 ticks, or chasing) head east for 3 ticks,
+    // then get back to life
+    struct Eastward;
+
+    struct EastwardMover<'a> {
+        details: &'a MovementDetails,
+    }
+
+    impl Mover for EastwardMover<'_> {
+        fn select_target(&self, _rng: &mut SimRng) -> (Option<usize>, Point) {
+            (None, self.details.origin + Point { x: 100., y: 0. })
+        }
+
+        fn collides(&self, _part: Point, _distance: f32) -> bool {
+            false
+        }
+
+        fn origin(&self) -> Point {
+            self.details.origin
+        }
+
+        fn details(&self) -> &MovementDetails {
+            self.details
+        }
+    }
+
+    impl Behavior for Eastward {
+        fn name(&self) -> &str {
+            "eastward"
+        }
+
+        fn enters(&self, _worm: &WormView, behavior: WormBehavior) -> bool {
+            match behavior {
+                WormBehavior::Alive(counter) => counter >= 2,
+                _ => matches!(behavior, WormBehavior::Chasing),
+            }
+        }
+
+        fn mover<'a>(&'a self, context: MoverContext<'a>) -> Box<dyn Mover + 'a> {
+            Box::new(EastwardMover {
+                details: context.details,
+            })
+        }
+
+        fn transition(&self, _worm: &WormView, counter: usize, _: &MovementResult) -> Transition {
+            match counter {
+                0..=1 => Transition::Stay,
+                _ => Transition::Switch(WormBehavior::Alive(0)),
+            }
+        }
+    }
+
+    #[test]
+    fn registered_behavior() {
+        let mut scene = Scene::from_scenario(
+            &Scenario::parse(
+                r#"{
+                    "config": {"width": 300, "height": 300, "part_size": 3.0, "worm_size": 2,
+                               "seed": 1},
+                    "worms": [{"head": {"x": 50, "y": 150}, "heading": 90,
+                               "destination": {"x": 50, "y": 290}}]
+                }"#,
+            )
+            .unwrap(),
+        );
+        let id = scene.register_behavior(Eastward);
+        assert_eq!(scene.behavior(id).unwrap().name(), "eastward");
+        let behavior = |scene: &Scene| *scene.worms().next().unwrap().0;
+
+        // two ticks alive (heading south), then three heading east
+        scene.execute();
+        scene.execute();
+        assert!(matches!(behavior(&scene), WormBehavior::Alive(2)));
+        let head = scene.content.bodies.get(0).head().unwrap();
+        for counter in 1..=2 {
+            scene.execute();
+            assert!(matches!(behavior(&scene), WormBehavior::Custom(0, n) if n == counter));
+        }
+        scene.execute();
+        assert!(matches!(behavior(&scene), WormBehavior::Alive(0)));
+        let moved = scene.content.bodies.get(0).head().unwrap() - head;
+        assert!((moved.x - 18.).abs() < 1e-3 && moved.y.abs() < 1e-3);
+    }
+
+    #[test]
+    fn chasing_worms_enter_behaviors() {
+        for update in [UpdateMode::Sequential, UpdateMode::Parallel] {
+            let mut scene = chase_scene(4, r#"{"Alive": 0}"#, 32);
+            scene.params.update = update;
+            scene.register_behavior(Eastward);
+            scene.execute();
+            // the chaser heads east instead of merging with the worm ahead
+            assert!(scene.events().is_empty());
+            assert!(matches!(
+                scene.worms().next().unwrap().0,
+                WormBehavior::Custom(0, 1)
+            ));
         }
     }
 }
@@ -0,0 +1,220 @@
+use serde::{Deserialize, Serialize};
+
+use crate::geometry::{Area, BoundaryMode, Point};
+
+/// Scent left by the worms: deposited as they move, spreading to the neighbouring cells
+/// and fading away each tick
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScentParameters {
+    /// Scent deposited by a worm at each movement
+    pub deposit: f32,
+    /// Extra scent deposited by a worm that just ate
+    pub meal_deposit: f32,
+    /// Fraction of the scent of a cell spreading to its neighbours in a tick
+    pub diffusion: f32,
+    /// Fraction of the scent lost in a tick
+    pub evaporation: f32,
+    /// Size of the cells of the field
+    pub cell_size: f32,
+    /// Weakest scent followed by the worms
+    pub threshold: f32,
+}
+
+impl Default for ScentParameters {
+    fn default() -> Self {
+        Self {
+            deposit: 1.,
+            meal_deposit: 10.,
+            diffusion: 0.2,
+            evaporation: 0.05,
+            cell_size: 28.,
+            threshold: 0.05,
+        }
+    }
+}
+
+/// Scalar field of the scent over the scene, in a grid of square cells
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ScentField {
+    cell_size: f32,
+    /// Weakest scent followed by the worms
+    threshold: f32,
+    columns: usize,
+    rows: usize,
+    /// Level of scent of each cell, row by row
+    levels: Vec<f32>,
+}
+
+impl ScentField {
+    /// Creates a field without scent covering the area
+    pub fn new(area: Area, params: &ScentParameters) -> Self {
+        let cell_size = params.cell_size;
+        let columns = 1 + (area.width as f32 / cell_size) as usize;
+        let rows = 1 + (area.height as f32 / cell_size) as usize;
+        Self {
+            cell_size,
+            threshold: params.threshold,
+            columns,
+            rows,
+            levels: vec![0.; columns * rows],
+        }
+    }
+
+    /// Checks if the field covers the area (with the cells of the parameters)
+    pub fn covers(&self, area: Area, params: &ScentParameters) -> bool {
+        let fresh = Self::new(area, params);
+        (self.columns, self.rows, self.cell_size) == (fresh.columns, fresh.rows, fresh.cell_size)
+    }
+
+    /// Adds scent in the cell of the point
+    pub fn deposit(&mut self, area: Area, point: Point, amount: f32) {
+        let cell = self.index(area.wrap(point));
+        self.levels[cell] += amount;
+    }
+
+    /// Level of scent in the cell of the point
+    pub fn level(&self, area: Area, point: Point) -> f32 {
+        self.levels[self.index(area.wrap(point))]
+    }
+
+    /// Spreads the scent to the four neighbours of each cell, then evaporates it
+    /// (the scent does not leave the scene, unless its borders wrap around)
+    pub fn update(&mut self, area: Area, params: &ScentParameters) {
+        let wraps = area.boundary == BoundaryMode::Torus;
+        let retained = 1. - params.evaporation;
+        let levels = &self.levels;
+        let (columns, rows) = (self.columns, self.rows);
+        // level of the neighbour at the offset (the cell itself beyond a closed border)
+        let neighbour = |column: usize, row: usize, dx: isize, dy: isize| {
+            let shift = |value: usize, delta: isize, limit: usize| {
+                let shifted = value as isize + delta;
+                match (0..limit as isize).contains(&shifted) {
+                    true => shifted as usize,
+                    false if wraps => shifted.rem_euclid(limit as isize) as usize,
+                    false => value,
+                }
+            };
+            levels[shift(row, dy, rows) * columns + shift(column, dx, columns)]
+        };
+        self.levels = (0..rows * columns)
+            .map(|cell| {
+                let (column, row) = (cell % columns, cell / columns);
+                let around = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                    .into_iter()
+                    .map(|(dx, dy)| neighbour(column, row, dx, dy))
+                    .sum::<f32>();
+                let spread =
+                    (1. - params.diffusion) * levels[cell] + params.diffusion * around / 4.;
+                spread * retained
+            })
+            .collect();
+    }
+
+    /// Center of the neighbouring cell with the strongest scent (at least the threshold)
+    /// among the ones accepted by the filter, if any is stronger than the cell of the point
+    /// (None at the top of the gradient)
+    pub fn strongest(
+        &self,
+        area: Area,
+        point: Point,
+        accept: impl Fn(Point) -> bool,
+    ) -> Option<Point> {
+        let center = self.center(self.index(area.wrap(point)));
+        let current = self.level(area, center);
+        [
+            (-1., -1.),
+            (0., -1.),
+            (1., -1.),
+            (-1., 0.),
+            (1., 0.),
+            (-1., 1.),
+            (0., 1.),
+            (1., 1.),
+        ]
+        .into_iter()
+        .filter_map(|(dx, dy)| area.place(center + Point { x: dx, y: dy }.scale(self.cell_size)))
+        .map(|neighbour| (neighbour, self.level(area, neighbour)))
+        .filter(|&(neighbour, level)| {
+            level >= self.threshold && level > current && accept(neighbour)
+        })
+        .max_by(|lhs, rhs| lhs.1.total_cmp(&rhs.1))
+        .map(|(neighbour, _)| neighbour)
+    }
+
+    /// Cells of the field, with their position (top left corner) and level
+    pub fn cells(&self) -> impl Iterator<Item = (Point, f32)> + '_ {
+        self.levels.iter().enumerate().map(|(cell, &level)| {
+            let half = Point { x: 0.5, y: 0.5 }.scale(self.cell_size);
+            (self.center(cell) - half, level)
+        })
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    fn index(&self, point: Point) -> usize {
+        let to_cell = |value: f32, limit: usize| {
+            ((value / self.cell_size).floor().max(0.) as usize).min(limit - 1)
+        };
+        to_cell(point.y, self.rows) * self.columns + to_cell(point.x, self.columns)
+    }
+
+    fn center(&self, cell: usize) -> Point {
+        Point {
+            x: ((cell % self.columns) as f32 + 0.5) * self.cell_size,
+            y: ((cell / self.columns) as f32 + 0.5) * self.cell_size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ScentField, ScentParameters};
+    use crate::geometry::{Area, BoundaryMode, Point};
+
+    #[test]
+    fn diffusion_and_gradient() {
+        let area = Area::new(100, 100, BoundaryMode::Bounded);
+        let params = ScentParameters {
+            cell_size: 10.,
+            evaporation: 0.,
+            threshold: 0.1,
+            ..Default::default()
+        };
+        let mut field = ScentField::new(area, &params);
+        let source = Point { x: 55., y: 55. };
+        field.deposit(area, source, 8.);
+        field.update(area, &params);
+        // the scent spreads to the four neighbours, without loss
+        assert!((field.level(area, source) - 6.4).abs() < 1e-5);
+        assert!((field.level(area, Point { x: 45., y: 55. }) - 0.4).abs() < 1e-5);
+        assert_eq!(field.level(area, Point { x: 45., y: 45. }), 0.);
+        let total = field.cells().map(|(_, level)| level).sum::<f32>();
+        assert!((total - 8.).abs() < 1e-4);
+
+        // the way to the source, from a neighbour of it
+        let next = field.strongest(area, Point { x: 65., y: 55. }, |_| true);
+        assert_eq!(next, Some(source));
+        // nothing strong enough (or accepted) around
+        assert_eq!(
+            field.strongest(area, Point { x: 5., y: 5. }, |_| true),
+            None
+        );
+        assert_eq!(
+            field.strongest(area, Point { x: 65., y: 55. }, |_| false),
+            None
+        );
+        // at the source, every neighbour is weaker: no way up the gradient
+        assert_eq!(field.strongest(area, source, |_| true), None);
+
+        // the scent fades away
+        let params = ScentParameters {
+            evaporation: 0.5,
+            ..params
+        };
+        field.update(area, &params);
+        let total = field.cells().map(|(_, level)| level).sum::<f32>();
+        assert!((total - 4.).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,561 @@
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST, FLOAT};
+use std::sync::OnceLock;
+
+use crate::{
+    behaviors::{Behavior, MoverContext, Transition, WormView},
+    composites::{WormBehavior, WormPart},
+    config::ConfigError,
+    geometry::{Point, SimRng},
+    movement::{AliveWormMover, MovementDetails, MovementResult, Mover},
+    planning::{PathPlanner, Planner},
+};
+
+/// Maximum number of operations of a call to a function of the script
+const MAX_OPERATIONS: u64 = 100_000;
+/// Maximum depth of the function calls of a script
+const MAX_CALL_LEVELS: usize = 32;
+/// Maximum nesting of the expressions of a script (in the functions as well)
+const MAX_EXPR_DEPTH: usize = 64;
+
+/// Behavior of the 'Alive' worms written in a Rhai script, registered in the scene as the
+/// other custom behaviors
+/// The script can define any of these functions (the built-in rules apply to the missing ones):
+/// - enters(worm, counter): if the worm follows the script, given its behavior ("alive" or
+///   "chasing", in worm.behavior) and the ticks since it last ate (0 for a chasing worm);
+///   only the alive worms do, if not defined
+/// - select_target(details, rewards, bodies): the index of the reward to head to (in the
+///   rewards array), a map with the x and y of a point (in the scene), or () for the built-in
+///   selection
+/// - transition(worm, counter, outcome): "stay", "alive", "chasing" or "dead", after a
+///   movement ("hit", "miss" or "blocked"); by default, the worm gets back to life when it
+///   eats, starts chasing when starving and dies when blocked
+///
+/// The scene is given as read-only copies: details is a map with the x, y, heading, vision_range
+/// (in degrees), vision_distance, width and height of the scene; rewards and bodies are the
+/// rewards and parts of the other worms in sight, with their x, y, distance and angle, plus
+/// id, kind and value (rewards) or worm and behavior (bodies); worm is a map with the id, size,
+/// split_size, starvation, energy and species of the worm
+pub struct ScriptBehavior {
+    name: String,
+    engine: Engine,
+    ast: AST,
+    /// First failure of the script (the later ones fall back to the built-in rules silently)
+    error: OnceLock<String>,
+}
+
+impl ScriptBehavior {
+    /// Reads and compiles the script file (named after it)
+    pub fn from_file(file_path: &str) -> Result<Self, ConfigError> {
+        Self::compile(file_path, &std::fs::read_to_string(file_path)?)
+    }
+
+    /// Compiles the script, with the limits of its engine: a script exceeding them (in an
+    /// endless loop or recursion) fails as any other error, instead of hanging the tick
+    pub fn compile(name: &str, script: &str) -> Result<Self, ConfigError> {
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(MAX_OPERATIONS)
+            .set_max_call_levels(MAX_CALL_LEVELS)
+            .set_max_expr_depths(MAX_EXPR_DEPTH, MAX_EXPR_DEPTH);
+        let ast = engine
+            .compile(script)
+            .map_err(|error| ConfigError::Invalid {
+                field: "script",
+                reason: format!("{name}: {error}"),
+            })?;
+        Ok(Self {
+            name: name.to_owned(),
+            engine,
+            ast,
+            error: OnceLock::new(),
+        })
+    }
+
+    fn defines(&self, function: &str) -> bool {
+        self.ast
+            .iter_functions()
+            .any(|defined| defined.name == function)
+    }
+
+    /// Calls the function of the script, if defined (None if it is not, or if it fails)
+    fn call(&self, function: &str, args: impl rhai::FuncArgs) -> Option<Dynamic> {
+        self.defines(function)
+            .then(|| {
+                self.engine
+                    .call_fn::<Dynamic>(&mut Scope::new(), &self.ast, function, args)
+            })?
+            .map_err(|error| self.report(&format!("{function}: {error}")))
+            .ok()
+    }
+
+    // Keeps the first failure of the script, reported by the scene (see Behavior::error)
+    fn report(&self, error: &str) {
+        let _ = self.error.set(error.to_owned());
+    }
+}
+
+impl Behavior for ScriptBehavior {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn enters(&self, worm: &WormView, behavior: WormBehavior) -> bool {
+        let (name, counter) = match behavior {
+            WormBehavior::Alive(counter) => ("alive", counter),
+            _ => (behavior_name(&behavior), 0),
+        };
+        // without the function, only the 'Alive' worms follow the script
+        if !self.defines("enters") {
+            return name == "alive";
+        }
+        let mut worm = worm_map(worm);
+        worm.insert("behavior".into(), name.into());
+        self.call("enters", (worm, counter as rhai::INT))
+            .is_some_and(|enters| enters.as_bool().unwrap_or(false))
+    }
+
+    fn eats(&self) -> bool {
+        true
+    }
+
+    fn mover<'a>(&'a self, context: MoverContext<'a>) -> Box<dyn Mover + 'a> {
+        // the built-in 'Alive' mover gives the senses, collisions and default selection
+        let params = context.params;
+        let senses = AliveWormMover {
+            details: context.details,
+            rewards: context.rewards,
+            reward_types: &params.reward_types,
+            selection: params.target_selection,
+            predators: context.predators,
+            flee_distance: params.predators.flee_distance,
+            bodies: context.bodies,
+            part_sizes: context.part_sizes,
+            obstacles: &params.obstacles,
+            planner: (params.planning.alive == Planner::AStar).then_some(context.planner),
+            scent: context.scent,
+            reward_index: context.reward_index,
+            body_index: context.body_index,
+        };
+        Box::new(ScriptMover {
+            script: self,
+            senses,
+            behaviors: context.behaviors,
+            worm_id: context.worm.id,
+        })
+    }
+
+    fn transition(&self, worm: &WormView, counter: usize, result: &MovementResult) -> Transition {
+        let outcome = match result {
+            MovementResult::TargetHit(..) => "hit",
+            MovementResult::TargetMiss(..) => "miss",
+            MovementResult::None => "blocked",
+        };
+        let scripted = self
+            .call(
+                "transition",
+                (worm_map(worm), counter as rhai::INT, outcome.to_owned()),
+            )
+            .and_then(|next| next.into_string().ok());
+        let next = scripted.unwrap_or_else(|| {
+            let starving = worm.starving(counter);
+            match outcome {
+                "hit" => "alive",
+                "blocked" => "dead",
+                _ if starving => "chasing",
+                _ => "stay",
+            }
+            .to_owned()
+        });
+        match next.as_str() {
+            "alive" => Transition::Switch(WormBehavior::Alive(0)),
+            "chasing" => Transition::Switch(WormBehavior::Chasing),
+            "dead" => Transition::Switch(WormBehavior::Dead(0)),
+            "stay" => Transition::Stay,
+            unknown => {
+                self.report(&format!("transition: unknown behavior {unknown}"));
+                Transition::Stay
+            }
+        }
+    }
+
+    fn error(&self) -> Option<String> {
+        self.error.get().cloned()
+    }
+}
+
+/// Mover asking the script for the target of the worm
+struct ScriptMover<'a> {
+    script: &'a ScriptBehavior,
+    senses: AliveWormMover<'a>,
+    behaviors: &'a Vec<WormBehavior>,
+    worm_id: usize,
+}
+
+impl ScriptMover<'_> {
+    // Position, distance and angle (in degrees) of something in sight
+    fn sighting(&self, point: Point) -> Map {
+        let details = self.senses.details;
+        let angle = details.area.angle(details.origin, point);
+        let mut map = point_map(point);
+        map.insert(
+            "distance".into(),
+            (details.distance_to(point) as f64).into(),
+        );
+        map.insert("angle".into(), (angle.val().to_degrees() as f64).into());
+        map
+    }
+
+    fn rewards_in_sight(&self) -> Vec<(usize, Point, Map)> {
+        let details = self.senses.details;
+        self.senses
+            .reward_index
+            .query(details.origin, details.stats.vision_distance)
+            .into_iter()
+            // the index may hold stale ids of rewards removed during the tick
+            .filter_map(|reward_id| Some((reward_id, self.senses.rewards.get(reward_id)?)))
+            .filter(|(_, reward)| details.in_range(reward.position, self.senses.obstacles))
+            .map(|(reward_id, reward)| {
+                let mut map = self.sighting(reward.position);
+                map.insert("id".into(), (reward_id as rhai::INT).into());
+                map.insert("kind".into(), reward.kind.name().into());
+                let value = self.senses.reward_types.get(reward.kind).value;
+                map.insert("value".into(), (value as f64).into());
+                (reward_id, reward.position, map)
+            })
+            .collect()
+    }
+
+    fn bodies_in_sight(&self) -> Array {
+        let details = self.senses.details;
+        self.senses
+            .body_index
+            .query(details.origin, details.stats.vision_distance)
+            .into_iter()
+            .filter(|&worm_id| worm_id != self.worm_id)
+            .flat_map(|worm_id| {
+                self.senses
+                    .bodies
+                    .get(worm_id)
+                    .iter()
+                    .filter(|&part| details.in_range(part, self.senses.obstacles))
+                    .map(move |part| {
+                        let mut map = self.sighting(part);
+                        map.insert("worm".into(), (worm_id as rhai::INT).into());
+                        let behavior = behavior_name(&self.behaviors[worm_id]);
+                        map.insert("behavior".into(), behavior.into());
+                        Dynamic::from(map)
+                    })
+            })
+            .collect()
+    }
+}
+
+impl Mover for ScriptMover<'_> {
+    fn origin(&self) -> WormPart {
+        self.senses.details.origin
+    }
+
+    fn details(&self) -> &MovementDetails {
+        self.senses.details
+    }
+
+    /// Asks the script for the reward (or point) to head to
+    /// (the built-in selection if it returns nothing valid)
+    fn select_target(&self, rng: &mut SimRng) -> (Option<usize>, Point) {
+        let rewards = self.rewards_in_sight();
+        let sighted = rewards
+            .iter()
+            .map(|(_, _, map)| Dynamic::from(map.clone()))
+            .collect::<Array>();
+        let target = self.script.call(
+            "select_target",
+            (
+                details_map(self.senses.details),
+                sighted,
+                self.bodies_in_sight(),
+            ),
+        );
+        match target {
+            Some(index) if index.is_int() => {
+                let index = index.as_int().unwrap_or(-1);
+                match usize::try_from(index)
+                    .ok()
+                    .and_then(|index| rewards.get(index))
+                {
+                    Some(&(reward_id, position, _)) => (Some(reward_id), position),
+                    None => {
+                        self.script
+                            .report(&format!("select_target: no reward at index {index}"));
+                        self.senses.select_target(rng)
+                    }
+                }
+            }
+            Some(point) if point.is_map() => {
+                let point = point.cast::<Map>();
+                let coordinate = |key: &str| {
+                    let value = point.get(key)?;
+                    value
+                        .as_float()
+                        .or_else(|_| value.as_int().map(|value| value as FLOAT))
+                        .ok()
+                };
+                let point = match (coordinate("x"), coordinate("y")) {
+                    (Some(x), Some(y)) => Point {
+                        x: x as f32,
+                        y: y as f32,
+                    },
+                    _ => {
+                        self.script
+                            .report("select_target: a point needs the x and y (as numbers)");
+                        return self.senses.select_target(rng);
+                    }
+                };
+                match self.senses.details.area.place(point) {
+                    Some(point) => (None, point),
+                    None => {
+                        self.script
+                            .report(&format!("select_target: {point} is out of the scene"));
+                        self.senses.select_target(rng)
+                    }
+                }
+            }
+            _ => self.senses.select_target(rng),
+        }
+    }
+
+    fn collides(&self, part: WormPart, radius: f32) -> bool {
+        self.senses.collides(part, radius)
+    }
+
+    fn planner(&self) -> Option<&PathPlanner> {
+        self.senses.planner()
+    }
+}
+
+fn point_map(point: Point) -> Map {
+    let mut map = Map::new();
+    map.insert("x".into(), (point.x as f64).into());
+    map.insert("y".into(), (point.y as f64).into());
+    map
+}
+
+fn details_map(details: &MovementDetails) -> Map {
+    let mut map = point_map(details.origin);
+    let heading = details.heading.map_or(Dynamic::UNIT, |heading| {
+        (heading.val().to_degrees() as f64).into()
+    });
+    map.insert("heading".into(), heading);
+    let vision_range = details.stats.vision_range.val().to_degrees();
+    map.insert("vision_range".into(), (vision_range as f64).into());
+    let vision_distance = details.stats.vision_distance;
+    map.insert("vision_distance".into(), (vision_distance as f64).into());
+    map.insert("width".into(), (details.area.width as rhai::INT).into());
+    map.insert("height".into(), (details.area.height as rhai::INT).into());
+    map
+}
+
+fn worm_map(worm: &WormView) -> Map {
+    let mut map = Map::new();
+    map.insert("id".into(), (worm.id as rhai::INT).into());
+    map.insert("size".into(), (worm.body.size() as rhai::INT).into());
+    let split_size = worm.genome.split_size;
+    map.insert("split_size".into(), (split_size as rhai::INT).into());
+    let starvation = worm.genome.starvation;
+    map.insert("starvation".into(), (starvation as rhai::INT).into());
+    map.insert("energy".into(), (worm.energy as f64).into());
+    map.insert("species".into(), worm.species.name.clone().into());
+    map
+}
+
+fn behavior_name(behavior: &WormBehavior) -> &'static str {
+    match behavior {
+        WormBehavior::Alive(_) => "alive",
+        WormBehavior::Dead(_) => "dead",
+        WormBehavior::Chasing => "chasing",
+        WormBehavior::Removed => "removed",
+        WormBehavior::Custom(..) => "custom",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScriptBehavior;
+    use crate::{
+        behaviors::Behavior,
+        composites::{RewardKind, WormBehavior},
+        config::ConfigError,
+        events::SimEvent,
+        scenario::Scenario,
+        scene::Scene,
+    };
+
+    #[test]
+    fn scripted_worms() {
+        let error = ScriptBehavior::compile("broken", "fn select_target(").err();
+        assert!(matches!(
+            error,
+            Some(ConfigError::Invalid {
+                field: "script",
+                ..
+            })
+        ));
+        assert!(ScriptBehavior::from_file("conf/scripts/valuable.rhai").is_ok());
+
+        let mut scene = Scene::from_scenario(
+            &Scenario::parse(
+                r#"{
+                    "config": {"width": 300, "height": 300, "part_size": 3.0, "worm_size": 2,
+                               "seed": 1},
+                    "worms": [{"head": {"x": 50, "y": 150}, "destination": {"x": 290, "y": 150}}],
+                    "rewards": [
+                        {"position": {"x": 62, "y": 162}, "kind": "prey", "destination": {"x": 62, "y": 162}},
+                        {"position": {"x": 80, "y": 150}, "kind": "plant", "destination": {"x": 80, "y": 150}}
+                    ]
+                }"#,
+            )
+            .unwrap(),
+        );
+        // the worms go for the plants, ignoring the (closer) prey
+        let script = ScriptBehavior::compile(
+            "plants",
+            r#"
+                fn enters(worm, counter) { worm.size > 0 }
+                fn select_target(details, rewards, bodies) {
+                    for i in 0..rewards.len() {
+                        if rewards[i].kind == "plant" { return i; }
+                    }
+                }
+            "#,
+        )
+        .unwrap();
+        scene.register_behavior(script);
+        let mut eaten = Vec::new();
+        for _ in 0..10 {
+            scene.execute();
+            eaten.extend(scene.events().iter().filter_map(|event| match event {
+                SimEvent::RewardEaten { kind, .. } => Some(*kind),
+                _ => None,
+            }));
+        }
+        assert_eq!(eaten, vec![RewardKind::Plant]);
+        assert!(matches!(
+            scene.worms().next().unwrap().0,
+            WormBehavior::Custom(0, _)
+        ));
+    }
+
+    #[test]
+    fn scripted_points() {
+        let point_scene = |target: &str| {
+            let mut scene = Scene::from_scenario(
+                &Scenario::parse(
+                    r#"{
+                        "config": {"width": 300, "height": 300, "part_size": 3.0, "worm_size": 2,
+                                   "seed": 1},
+                        "worms": [{"head": {"x": 50, "y": 150}, "destination": {"x": 290, "y": 150}}],
+                        "rewards": [
+                            {"position": {"x": 80, "y": 150}, "kind": "plant", "destination": {"x": 80, "y": 150}}
+                        ]
+                    }"#,
+                )
+                .unwrap(),
+            );
+            let source = format!("fn select_target(details, rewards, bodies) {{ {target} }}");
+            scene.register_behavior(ScriptBehavior::compile("points", &source).unwrap());
+            for _ in 0..5 {
+                scene.execute();
+            }
+            let head = scene.worms().next().and_then(|(_, body, _)| body.head());
+            (scene, head.unwrap())
+        };
+        // integer coordinates are points too: the worm heads south, away from the plant
+        let (scene, head) = point_scene("#{x: 50, y: 290}");
+        assert!(head.y > 170., "{head}");
+        assert_eq!(scene.behavior_errors().count(), 0);
+
+        // a point out of the (bounded) scene is an error: the built-in selection takes over
+        let (scene, head) = point_scene("#{x: 1000, y: 150}");
+        let errors = scene.behavior_errors().collect::<Vec<_>>();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].1.contains("out of the scene"), "{}", errors[0].1);
+        assert!(head.x < 100., "{head}");
+    }
+
+    #[test]
+    fn script_limits() {
+        let script = ScriptBehavior::compile(
+            "endless",
+            r#"
+                fn endless() { loop {} }
+                fn deep(n) { deep(n + 1) }
+            "#,
+        )
+        .unwrap();
+        assert!(script.call("endless", ()).is_none());
+        assert!(script
+            .error()
+            .is_some_and(|error| error.starts_with("endless:")));
+        assert!(script.call("deep", (0 as rhai::INT,)).is_none());
+
+        // the ticks go on, with the built-in selection
+        let mut scene = Scene::from_scenario(
+            &Scenario::parse(
+                r#"{
+                    "config": {"width": 300, "height": 300, "part_size": 3.0, "worm_size": 2,
+                               "seed": 1},
+                    "worms": [{"head": {"x": 50, "y": 150}, "destination": {"x": 290, "y": 150}}],
+                    "rewards": [
+                        {"position": {"x": 80, "y": 150}, "kind": "plant", "destination": {"x": 80, "y": 150}}
+                    ]
+                }"#,
+            )
+            .unwrap(),
+        );
+        scene.register_behavior(
+            ScriptBehavior::compile(
+                "endless",
+                "fn select_target(details, rewards, bodies) { loop {} }",
+            )
+            .unwrap(),
+        );
+        let mut events = Vec::new();
+        for _ in 0..5 {
+            scene.execute();
+            events.extend_from_slice(scene.events());
+        }
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, SimEvent::RewardEaten { .. })));
+        // the failure is reported once
+        let failures = events
+            .iter()
+            .filter(|event| matches!(event, SimEvent::BehaviorFailed { behavior: 0, .. }))
+            .count();
+        assert_eq!(failures, 1);
+        let errors = scene.behavior_errors().collect::<Vec<_>>();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, "endless");
+    }
+
+    #[test]
+    fn scripted_starvation() {
+        // the worm runs out of energy long before the starvation of its genome
+        let mut scene = Scene::from_scenario(
+            &Scenario::parse(
+                r#"{
+                    "config": {"width": 300, "height": 300, "part_size": 3.0, "worm_size": 2,
+                               "seed": 1, "starvation": 100000, "metabolism": "energy",
+                               "energy_initial": 1.0, "energy_per_move": 5.0},
+                    "worms": [{"head": {"x": 50, "y": 150}, "destination": {"x": 290, "y": 150}}]
+                }"#,
+            )
+            .unwrap(),
+        );
+        scene.register_behavior(ScriptBehavior::compile("default", "").unwrap());
+        scene.execute();
+        assert!(matches!(
+            scene.worms().next().unwrap().0,
+            WormBehavior::Chasing
+        ));
+    }
+}
@@ -0,0 +1,149 @@
+use crate::geometry::{Area, BoundaryMode, Point};
+
+/// Uniform grid bucketing the indexes of composites by the cells their points fall in
+/// A query returns the indexes having at least one point in the cells touched by the query circle,
+/// i.e a superset of the composites in range: the exact distances still have to be checked
+/// (on a torus, the queries wrap around the borders)
+pub struct SpatialGrid {
+    area: Area,
+    cell_size: f32,
+    columns: usize,
+    rows: usize,
+    cells: Vec<Vec<usize>>,
+}
+
+impl Default for SpatialGrid {
+    /// An empty grid covering no area
+    fn default() -> Self {
+        Self::new(Area::default(), 1.)
+    }
+}
+
+impl SpatialGrid {
+    pub fn new(area: Area, cell_size: f32) -> Self {
+        let columns = 1 + (area.width as f32 / cell_size) as usize;
+        let rows = 1 + (area.height as f32 / cell_size) as usize;
+        Self {
+            area,
+            cell_size,
+            columns,
+            rows,
+            cells: vec![Vec::new(); columns * rows],
+        }
+    }
+
+    /// Empties the grid, adapting it to the (possibly new) area
+    pub fn reset(&mut self, area: Area) {
+        let resized = Self::new(area, self.cell_size);
+        if resized.columns != self.columns || resized.rows != self.rows {
+            *self = resized;
+        } else {
+            self.area = area;
+            self.cells.iter_mut().for_each(Vec::clear);
+        }
+    }
+
+    /// Registers a point of the composite with the given index
+    /// (inserting the same index again in the same cell has no effect)
+    pub fn insert(&mut self, id: usize, point: Point) {
+        let (column, row) = self.cell(self.area.wrap(point));
+        let cell = &mut self.cells[row * self.columns + column];
+        if !cell.contains(&id) {
+            cell.push(id);
+        }
+    }
+
+    /// Returns the (sorted and unique) indexes of the composites that may be
+    /// at a distance less than radius from the center
+    pub fn query(&self, center: Point, radius: f32) -> Vec<usize> {
+        let center = self.area.wrap(center);
+        let x_spans = self.spans(center.x, radius, self.area.width);
+        let y_spans = self.spans(center.y, radius, self.area.height);
+        let mut found = Vec::new();
+        for &(min_x, max_x) in &x_spans {
+            for &(min_y, max_y) in &y_spans {
+                let (min_column, min_row) = self.cell(Point { x: min_x, y: min_y });
+                let (max_column, max_row) = self.cell(Point { x: max_x, y: max_y });
+                for row in min_row..=max_row {
+                    for column in min_column..=max_column {
+                        found.extend_from_slice(&self.cells[row * self.columns + column]);
+                    }
+                }
+            }
+        }
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+
+    /// Coordinate ranges covered by [center - radius, center + radius] in a dimension
+    /// (on a torus, the parts beyond the borders continue on the opposite side)
+    fn spans(&self, center: f32, radius: f32, limit: usize) -> Vec<(f32, f32)> {
+        let (low, high, limit) = (center - radius, center + radius, limit as f32);
+        if self.area.boundary != BoundaryMode::Torus {
+            return vec![(low, high)];
+        }
+        if high - low >= limit {
+            return vec![(0., limit)];
+        }
+        let mut spans = vec![(low.max(0.), high.min(limit))];
+        if low < 0. {
+            spans.push((low + limit, limit));
+        }
+        if high > limit {
+            spans.push((0., high - limit));
+        }
+        spans
+    }
+
+    /// Cell containing the point (points outside the area are clamped to the border cells)
+    fn cell(&self, point: Point) -> (usize, usize) {
+        let to_cell = |value: f32, limit: usize| {
+            ((value / self.cell_size).floor().max(0.) as usize).min(limit - 1)
+        };
+        (to_cell(point.x, self.columns), to_cell(point.y, self.rows))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::{Area, BoundaryMode, Point};
+
+    use super::SpatialGrid;
+
+    #[test]
+    fn query() {
+        let area = Area::new(100, 100, BoundaryMode::Bounded);
+        let mut grid = SpatialGrid::new(area, 10.);
+        grid.insert(0, Point { x: 5., y: 5. });
+        grid.insert(1, Point { x: 55., y: 55. });
+        grid.insert(1, Point { x: 56., y: 54. });
+        grid.insert(2, Point { x: 95., y: 5. });
+        // points outside the area are kept in the border cells
+        grid.insert(3, Point { x: -20., y: 130. });
+
+        assert_eq!(grid.query(Point { x: 0., y: 0. }, 8.), vec![0]);
+        assert_eq!(grid.query(Point { x: 50., y: 50. }, 8.), vec![1]);
+        assert_eq!(grid.query(Point { x: 50., y: 50. }, 45.), vec![0, 1, 2]);
+        assert_eq!(grid.query(Point { x: 50., y: 50. }, 50.), vec![0, 1, 2, 3]);
+        assert_eq!(grid.query(Point { x: 0., y: 100. }, 1.), vec![3]);
+        assert!(grid.query(Point { x: 30., y: 70. }, 5.).is_empty());
+
+        grid.reset(area);
+        assert!(grid.query(Point { x: 50., y: 50. }, 100.).is_empty());
+    }
+
+    #[test]
+    fn torus_query() {
+        let mut grid = SpatialGrid::new(Area::new(100, 100, BoundaryMode::Torus), 10.);
+        grid.insert(0, Point { x: 5., y: 5. });
+        grid.insert(1, Point { x: 95., y: 50. });
+        // points outside the area are wrapped around
+        grid.insert(2, Point { x: -5., y: 105. });
+
+        assert_eq!(grid.query(Point { x: 98., y: 98. }, 8.), vec![0, 2]);
+        assert_eq!(grid.query(Point { x: 2., y: 50. }, 5.), vec![1]);
+        assert_eq!(grid.query(Point { x: 50., y: 50. }, 60.), vec![0, 1, 2]);
+        assert!(grid.query(Point { x: 50., y: 50. }, 20.).is_empty());
+    }
+}
@@ -0,0 +1,265 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+use crate::{
+    geometry::{angle_serde, Angle, Area, Point, SimRng},
+    obstacles::ObstacleMap,
+};
+
+/// Number of positions tried for a reward (or a worm) before accepting one inside an obstacle
+pub const MAX_ATTEMPTS: usize = 100;
+
+/// Where the new rewards appear in the scene
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum SpawnPattern {
+    /// Anywhere, with the same probability
+    #[default]
+    Uniform,
+    /// Around a number of fixed centers (chosen when the scene is created),
+    /// inside a disc of the given radius
+    Patches { count: usize, radius: f32 },
+    /// Anywhere, with a density growing linearly across the scene towards the direction,
+    /// from min_density (relative to the densest border) to 1
+    Gradient {
+        #[serde(with = "angle_serde")]
+        direction: Angle,
+        min_density: f32,
+    },
+}
+
+/// Periodic abundance and scarcity: the number of rewards follows a cosine,
+/// from the full number (at the start of each period) down to (1 - amplitude) of it
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Seasons {
+    /// Length of a cycle, in ticks
+    pub period: u64,
+    /// Fraction of the rewards missing at the peak of the scarcity (between 0 and 1)
+    pub amplitude: f32,
+}
+
+/// How the rewards of the scene are spawned
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SpawnParameters {
+    pub pattern: SpawnPattern,
+    /// Seasonal variation of the number of rewards (constant if not set)
+    pub seasons: Option<Seasons>,
+    /// Number of rewards regrown per tick, up to the current number of rewards
+    /// (an eaten reward is replaced right away if not set)
+    pub regrowth: Option<f32>,
+}
+
+impl SpawnParameters {
+    /// Fraction of the full number of rewards present at the tick
+    pub fn abundance(&self, tick: u64) -> f32 {
+        match self.seasons {
+            Some(seasons) if seasons.period > 0 => {
+                let phase = (tick % seasons.period) as f32 / seasons.period as f32;
+                1. - seasons.amplitude * (1. - (2. * PI * phase).cos()) / 2.
+            }
+            _ => 1.,
+        }
+    }
+}
+
+/// State of the spawning of the rewards in a scene
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Spawner {
+    /// Number of rewards at the peak of the abundance
+    capacity: usize,
+    /// Centers of the patches (only used by the patches pattern)
+    patches: Vec<Point>,
+    /// Fraction of a reward accumulated by the regrowth, not spawned yet
+    regrowth_credit: f32,
+}
+
+impl Spawner {
+    /// Creates the spawner of a scene, choosing the center of its patches
+    /// (no random number is drawn for the other patterns)
+    pub fn new(params: &SpawnParameters, capacity: usize, area: Area, rng: &mut SimRng) -> Self {
+        let patches = match params.pattern {
+            SpawnPattern::Patches { count, .. } => {
+                (0..count).map(|_| area.rand_point(rng)).collect()
+            }
+            _ => Vec::new(),
+        };
+        Self {
+            capacity,
+            patches,
+            regrowth_credit: 0.,
+        }
+    }
+
+    /// Number of rewards the scene should have at the tick
+    pub fn target(&self, params: &SpawnParameters, tick: u64) -> usize {
+        (self.capacity as f32 * params.abundance(tick)).round() as usize
+    }
+
+    /// Checks if an eaten reward is replaced right away (otherwise it is removed,
+    /// regrowing later)
+    pub fn respawns(&self, params: &SpawnParameters, n_rewards: usize, tick: u64) -> bool {
+        params.regrowth.is_none() && n_rewards <= self.target(params, tick)
+    }
+
+    /// Number of rewards to spawn (positive) or to wither (negative) at the end of the tick
+    pub fn adjustment(&mut self, params: &SpawnParameters, n_rewards: usize, tick: u64) -> isize {
+        let missing = self.target(params, tick) as isize - n_rewards as isize;
+        match params.regrowth {
+            _ if missing <= 0 => {
+                self.regrowth_credit = 0.;
+                missing
+            }
+            None => missing,
+            Some(rate) => {
+                self.regrowth_credit += rate;
+                let regrown = (self.regrowth_credit as isize).min(missing);
+                self.regrowth_credit -= regrown as f32;
+                regrown
+            }
+        }
+    }
+
+    /// Position of a new reward, following the pattern (and out of the obstacles, if possible)
+    pub fn position(
+        &self,
+        params: &SpawnParameters,
+        obstacles: &ObstacleMap,
+        area: Area,
+        rng: &mut SimRng,
+    ) -> Point {
+        let mut position = self.sample(params, area, rng);
+        for _ in 1..MAX_ATTEMPTS {
+            if !obstacles.covers(position) {
+                break;
+            }
+            position = self.sample(params, area, rng);
+        }
+        position
+    }
+
+    // Position following the pattern
+    fn sample(&self, params: &SpawnParameters, area: Area, rng: &mut SimRng) -> Point {
+        match params.pattern {
+            SpawnPattern::Uniform => area.rand_point(rng),
+            SpawnPattern::Patches { radius, .. } if !self.patches.is_empty() => loop {
+                // uniform inside the disc (retrying the points out of a bounded area)
+                let center = self.patches[rng.gen_range(0..self.patches.len())];
+                let angle = Angle::new(rng.gen_range(0. ..2. * PI));
+                let distance = radius * rng.gen::<f32>().sqrt();
+                if let Some(position) = area.place(center.project(angle, distance)) {
+                    break position;
+                }
+            },
+            SpawnPattern::Patches { .. } => area.rand_point(rng),
+            SpawnPattern::Gradient {
+                direction,
+                min_density,
+            } => loop {
+                // rejection sampling: accepted with a probability equal to the density
+                let position = area.rand_point(rng);
+                if rng.gen::<f32>() < Self::density(area, position, direction, min_density) {
+                    break position;
+                }
+            },
+        }
+    }
+
+    // Density of the gradient at the position, between min_density and 1
+    fn density(area: Area, position: Point, direction: Angle, min_density: f32) -> f32 {
+        let (cos, sin) = (direction.val().cos(), direction.val().sin());
+        let (width, height) = (area.width as f32, area.height as f32);
+        // projection of the position on the direction, relative to the center of the area
+        let projection = (position.x - width / 2.) * cos + (position.y - height / 2.) * sin;
+        let half_extent = ((cos.abs() * width + sin.abs() * height) / 2.).max(f32::EPSILON);
+        let progress = ((projection / half_extent + 1.) / 2.).clamp(0., 1.);
+        min_density + (1. - min_density) * progress
+    }
+
+    /// Number of rewards at the peak of the abundance
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Centers of the patches (empty for the other patterns)
+    pub fn patches(&self) -> &[Point] {
+        &self.patches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::{Seasons, SpawnParameters, SpawnPattern, Spawner};
+    use crate::{
+        geometry::{Angle, Area, BoundaryMode, SimRng},
+        obstacles::ObstacleMap,
+    };
+
+    #[test]
+    fn patterns() {
+        let mut rng = SimRng::seed_from_u64(1);
+        let area = Area::new(400, 200, BoundaryMode::Bounded);
+        let obstacles = ObstacleMap::default();
+
+        let patches = SpawnParameters {
+            pattern: SpawnPattern::Patches {
+                count: 3,
+                radius: 20.,
+            },
+            ..Default::default()
+        };
+        let spawner = Spawner::new(&patches, 10, area, &mut rng);
+        assert_eq!(spawner.patches().len(), 3);
+        for _ in 0..100 {
+            let position = spawner.position(&patches, &obstacles, area, &mut rng);
+            assert!(area.contains(position));
+            assert!(spawner
+                .patches()
+                .iter()
+                .any(|&center| position.distance_to(center) <= 20.01));
+        }
+
+        // most of the rewards on the right half, towards the direction of the gradient
+        let gradient = SpawnParameters {
+            pattern: SpawnPattern::Gradient {
+                direction: Angle::new(0.),
+                min_density: 0.,
+            },
+            ..Default::default()
+        };
+        let spawner = Spawner::new(&gradient, 10, area, &mut rng);
+        let right = (0..400)
+            .filter(|_| spawner.position(&gradient, &obstacles, area, &mut rng).x > 200.)
+            .count();
+        assert!(right > 280, "{right}");
+    }
+
+    #[test]
+    fn seasons_and_regrowth() {
+        let mut rng = SimRng::seed_from_u64(1);
+        let area = Area::new(100, 100, BoundaryMode::Bounded);
+        let params = SpawnParameters {
+            seasons: Some(Seasons {
+                period: 100,
+                amplitude: 0.5,
+            }),
+            regrowth: Some(0.5),
+            ..Default::default()
+        };
+        let mut spawner = Spawner::new(&params, 20, area, &mut rng);
+        assert_eq!(spawner.target(&params, 0), 20);
+        assert_eq!(spawner.target(&params, 50), 10);
+        assert_eq!(spawner.target(&params, 100), 20);
+        assert!(!spawner.respawns(&params, 20, 0));
+
+        // half a reward regrown per tick, up to the target
+        assert_eq!(spawner.adjustment(&params, 18, 0), 0);
+        assert_eq!(spawner.adjustment(&params, 18, 0), 1);
+        assert_eq!(spawner.adjustment(&params, 19, 0), 0);
+        assert_eq!(spawner.adjustment(&params, 19, 0), 1);
+        assert_eq!(spawner.adjustment(&params, 20, 0), 0);
+        // the rewards beyond the target wither at once
+        assert_eq!(spawner.adjustment(&params, 20, 50), -10);
+    }
+}
@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    composites::{Genome, WormStats},
+    geometry::SimRng,
+    neural::{Brain, Controller},
+};
+
+/// A species of worms, with its own sizes, hunger, vision and colors
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Species {
+    pub name: String,
+    /// Number of worms of the species created with the scene
+    pub count: usize,
+    /// Initial number of parts of a worm (also the number of parts split from a full worm)
+    pub worm_size: usize,
+    /// Radius of a worm part
+    pub body_size: f32,
+    pub starvation: usize,
+    pub stats: WormStats,
+    /// What drives the movements of the alive worms of the species
+    #[serde(default)]
+    pub controller: Controller,
+    /// Colors of the heads and bodies of the worms (when alive)
+    pub head_color: [u8; 3],
+    pub body_color: [u8; 3],
+}
+
+impl Species {
+    /// Colors of the worms when the species are not configured
+    pub const HEAD_COLOR: [u8; 3] = [0x2E, 0xBF, 0xA5];
+    pub const BODY_COLOR: [u8; 3] = [0x7D, 0xDE, 0x92];
+
+    /// Genome of the worms of the species created with the scene
+    /// (with a random brain, if they are driven by a network)
+    pub fn genome(&self, max_size: usize, rng: &mut SimRng) -> Genome {
+        Genome {
+            stats: self.stats,
+            speed: 1.,
+            starvation: self.starvation,
+            split_size: max_size,
+            brain: (self.controller == Controller::Neural).then(|| Brain::rand(rng)),
+        }
+    }
+}
+
+/// Which worms a chasing worm may merge with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeRule {
+    /// Any alive worm
+    #[default]
+    Any,
+    /// Only the alive worms of its own species
+    Same,
+}